@@ -0,0 +1,160 @@
+use crate::errors::SQLError;
+use std::io::{BufRead, Write};
+
+/// Reads RFC 4180 records from a buffered reader, one `Vec<String>` per record.
+///
+/// Fields may be wrapped in double quotes, in which case they can contain commas,
+/// line breaks and escaped quotes (`""`). Both LF and CRLF record terminators are
+/// accepted.
+pub struct CsvReader<R: BufRead> {
+    reader: R,
+}
+
+impl<R: BufRead> CsvReader<R> {
+    pub fn new(reader: R) -> CsvReader<R> {
+        CsvReader { reader }
+    }
+
+    fn read_line(&mut self, buffer: &mut String) -> Result<usize, SQLError> {
+        self.reader
+            .read_line(buffer)
+            .map_err(|_| SQLError::InvalidTable("Failed to read table row".to_string()))
+    }
+}
+
+impl<R: BufRead> Iterator for CsvReader<R> {
+    type Item = Result<Vec<String>, SQLError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Skip blank lines between records
+        let mut line = String::new();
+        loop {
+            match self.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) if line.trim_end_matches(['\r', '\n']).is_empty() => line.clear(),
+                Ok(_) => break,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut in_quotes = false;
+        loop {
+            let mut chars = line.chars().peekable();
+            while let Some(ch) = chars.next() {
+                if in_quotes {
+                    match ch {
+                        '"' if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        '"' => in_quotes = false,
+                        _ => field.push(ch),
+                    }
+                } else {
+                    match ch {
+                        '"' if field.is_empty() => in_quotes = true,
+                        ',' => fields.push(std::mem::take(&mut field)),
+                        '\r' if chars.peek() == Some(&'\n') => {}
+                        '\n' => {}
+                        _ => field.push(ch),
+                    }
+                }
+            }
+
+            if !in_quotes {
+                break;
+            }
+
+            // The line break belongs to a quoted field; keep reading the record
+            line.clear();
+            match self.read_line(&mut line) {
+                Ok(0) => {
+                    return Some(Err(SQLError::InvalidTable(
+                        "Unterminated quoted field in table".to_string(),
+                    )))
+                }
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        fields.push(field);
+
+        Some(Ok(fields))
+    }
+}
+
+/// Formats a record as a single CSV line terminated by `\n`, quoting the fields
+/// that need it.
+pub fn format_record(fields: &[String]) -> String {
+    // A lone empty field is quoted so the record is not mistaken for a blank line
+    if fields.len() == 1 && fields[0].is_empty() {
+        return "\"\"\n".to_string();
+    }
+    let mut line = fields
+        .iter()
+        .map(|field| escape_field(field))
+        .collect::<Vec<String>>()
+        .join(",");
+    line.push('\n');
+    line
+}
+
+pub fn write_record<W: Write>(writer: &mut W, fields: &[String]) -> Result<(), SQLError> {
+    writer
+        .write_all(format_record(fields).as_bytes())
+        .map_err(|_| SQLError::GenericError("Failed to write to table file".to_string()))
+}
+
+fn escape_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_all(input: &str) -> Result<Vec<Vec<String>>, SQLError> {
+        CsvReader::new(input.as_bytes()).collect()
+    }
+
+    #[test]
+    fn test_read_plain_and_quoted_fields() {
+        let records = read_all("id,name\r\n1,\"López, Ana\"\n2,\"say \"\"hi\"\"\"\n").unwrap();
+        assert_eq!(
+            records,
+            vec![
+                vec!["id", "name"],
+                vec!["1", "López, Ana"],
+                vec!["2", "say \"hi\""],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_embedded_newline_and_empty_fields() {
+        let records = read_all("1,\"two\r\nlines\",\n\n,,").unwrap();
+        assert_eq!(
+            records,
+            vec![vec!["1", "two\r\nlines", ""], vec!["", "", ""]]
+        );
+    }
+
+    #[test]
+    fn test_read_unterminated_quote() {
+        assert!(read_all("1,\"open\n").is_err());
+    }
+
+    #[test]
+    fn test_format_record_round_trip() {
+        let fields: Vec<String> = vec!["a,b".into(), "say \"hi\"".into(), "x\ny".into(), "".into()];
+        let line = format_record(&fields);
+        assert_eq!(line, "\"a,b\",\"say \"\"hi\"\"\",\"x\ny\",\n");
+        assert_eq!(read_all(&line).unwrap(), vec![fields]);
+    }
+}
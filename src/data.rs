@@ -1,27 +1,371 @@
+use crate::csv::CsvReader;
 use crate::errors::SQLError;
+use crate::utils::did_you_mean;
+use crate::value::{DataType, Value};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::path::Path;
 
 pub struct TableSchema {
-    pub columns: HashMap<String, usize>, // Column name to index
+    pub columns: HashMap<String, usize>,    // Column name to index
+    pub definitions: Vec<ColumnDefinition>, // Column metadata in file order
+    pub null_marker: String,                // Raw field text that stands for NULL
+}
+
+/// Metadata for a single column. Columns that are not declared in a schema
+/// sidecar file are untyped, nullable and have no default.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDefinition {
+    pub name: String,
+    pub data_type: Option<DataType>,
+    pub nullable: bool,
+    pub default: Option<String>,
+}
+
+impl ColumnDefinition {
+    fn untyped(name: &str) -> ColumnDefinition {
+        ColumnDefinition {
+            name: name.to_string(),
+            data_type: None,
+            nullable: true,
+            default: None,
+        }
+    }
+}
+
+impl TableSchema {
+    /// Builds an untyped schema from the column names of a CSV header.
+    pub fn from_header(header: &[&str]) -> TableSchema {
+        let columns = header
+            .iter()
+            .enumerate()
+            .map(|(idx, col_name)| (col_name.to_string(), idx))
+            .collect();
+        let definitions = header
+            .iter()
+            .map(|col_name| ColumnDefinition::untyped(col_name))
+            .collect();
+        TableSchema {
+            columns,
+            definitions,
+            null_marker: String::new(),
+        }
+    }
+
+    /// The index of the column called `name`, suggesting a close name when there
+    /// is no such column.
+    pub fn column_index(&self, name: &str) -> Result<usize, SQLError> {
+        self.columns.get(name).copied().ok_or_else(|| {
+            SQLError::InvalidColumn(format!(
+                "Column '{}' does not exist{}",
+                name,
+                self.did_you_mean(name)
+            ))
+        })
+    }
+
+    fn did_you_mean(&self, name: &str) -> String {
+        did_you_mean(
+            name,
+            self.definitions.iter().map(|column| column.name.as_str()),
+        )
+    }
+
+    /// Reads the raw field at `idx` as a typed value, using the declared column
+    /// type when there is one. A field of a column without one is kept as text,
+    /// exactly as written, and compared as the number or boolean it reads as. A
+    /// field equal to the table's NULL marker is NULL.
+    pub fn field_value(&self, idx: usize, field: &str) -> Result<Value, SQLError> {
+        if field == self.null_marker {
+            return Ok(Value::Null);
+        }
+        let definition = &self.definitions[idx];
+        match definition.data_type {
+            Some(data_type) => data_type.parse_field(field).ok_or_else(|| {
+                SQLError::TypeMismatch(format!(
+                    "Value '{}' in column '{}' is not a valid {}",
+                    field, definition.name, data_type
+                ))
+            }),
+            None => Ok(Value::Text(field.to_string())),
+        }
+    }
+
+    /// Reads every raw field of a row as a typed value.
+    pub fn parse_row(&self, fields: &[String]) -> Result<Vec<Value>, SQLError> {
+        fields
+            .iter()
+            .enumerate()
+            .map(|(idx, field)| self.field_value(idx, field))
+            .collect()
+    }
+
+    /// Converts a value about to be written to the column at `idx` into its raw
+    /// field, checking it against the declared type and nullability. A value
+    /// written the same way as the NULL marker is rejected, since it would read
+    /// back as NULL.
+    pub fn to_field(&self, idx: usize, value: &Value) -> Result<String, SQLError> {
+        let definition = &self.definitions[idx];
+        let field = match value {
+            Value::Null => self.null_marker.clone(),
+            _ => value.to_string(),
+        };
+        if field == self.null_marker && !value.is_null() {
+            return Err(SQLError::TypeMismatch(format!(
+                "Value '{}' in column '{}' is the table's NULL marker '{}' and would read back as NULL",
+                field, definition.name, self.null_marker
+            )));
+        }
+        if field == self.null_marker {
+            if !definition.nullable {
+                return Err(SQLError::TypeMismatch(format!(
+                    "Column '{}' cannot be NULL",
+                    definition.name
+                )));
+            }
+            return Ok(field);
+        }
+        self.field_value(idx, &field)?;
+        Ok(field)
+    }
 }
 
 pub fn read_table_schema(file_path: &str) -> Result<TableSchema, SQLError> {
     let file = File::open(file_path)
         .map_err(|_| SQLError::InvalidTable(format!("Cannot open table file '{}'", file_path)))?;
-    let mut reader = BufReader::new(file);
-    let mut header_line = String::new();
-    reader
-        .read_line(&mut header_line)
-        .map_err(|_| SQLError::InvalidTable("Failed to read table header".to_string()))?;
-
-    let columns: HashMap<String, usize> = header_line
-        .trim_end()
-        .split(',')
-        .enumerate()
-        .map(|(idx, col_name)| (col_name.to_string(), idx))
-        .collect();
-
-    Ok(TableSchema { columns })
+    let header = match CsvReader::new(BufReader::new(file)).next() {
+        Some(record) => record?,
+        None => {
+            return Err(SQLError::InvalidTable(
+                "Failed to read table header".to_string(),
+            ))
+        }
+    };
+
+    let header: Vec<&str> = header.iter().map(|col_name| col_name.as_str()).collect();
+    let mut schema = TableSchema::from_header(&header);
+
+    // Merge the optional `<table>.schema` sidecar into the header columns
+    let sidecar_path = Path::new(file_path).with_extension("schema");
+    if sidecar_path.exists() {
+        read_schema_sidecar(&sidecar_path, &mut schema)?;
+    }
+
+    Ok(schema)
+}
+
+/// Reads a schema sidecar file into `schema`. Each non-empty line that is not a
+/// `#` comment either declares one column:
+///
+/// ```text
+/// <name> <type> [NOT NULL | NULL] [DEFAULT <value>]
+/// ```
+///
+/// where the default is either a bare word or a single-quoted string, or sets a
+/// table option:
+///
+/// ```text
+/// @null_marker '<text>'
+/// ```
+///
+/// By default an empty field is NULL. Declaring another marker, such as `'\N'`,
+/// makes empty fields empty strings and uses the marker for NULL instead.
+fn read_schema_sidecar(path: &Path, schema: &mut TableSchema) -> Result<(), SQLError> {
+    let file = File::open(path).map_err(|_| {
+        SQLError::InvalidTable(format!("Cannot open schema file '{}'", path.display()))
+    })?;
+    let reader = BufReader::new(file);
+
+    for (line_number, line_result) in reader.lines().enumerate() {
+        let line = line_result
+            .map_err(|_| SQLError::InvalidTable("Failed to read schema file".to_string()))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        apply_sidecar_line(line, schema).map_err(|msg| {
+            SQLError::InvalidTable(format!(
+                "{} (line {} of '{}')",
+                msg,
+                line_number + 1,
+                path.display()
+            ))
+        })?;
+    }
+    Ok(())
+}
+
+fn apply_sidecar_line(line: &str, schema: &mut TableSchema) -> Result<(), String> {
+    if let Some(option) = line.strip_prefix('@') {
+        let (name, rest) = split_word(option);
+        return match name {
+            "null_marker" => {
+                let (marker, rest) = split_default(rest)?;
+                if !rest.is_empty() {
+                    return Err(format!("Unexpected '{}' after NULL marker", rest));
+                }
+                schema.null_marker = marker;
+                Ok(())
+            }
+            _ => Err(format!("Unknown table option '@{}'", name)),
+        };
+    }
+
+    let definition = parse_column_definition(line)?;
+    let idx = *schema.columns.get(&definition.name).ok_or_else(|| {
+        format!(
+            "Schema file declares column '{}' which is not in the table header{}",
+            definition.name,
+            schema.did_you_mean(&definition.name)
+        )
+    })?;
+    schema.definitions[idx] = definition;
+    Ok(())
+}
+
+fn parse_column_definition(line: &str) -> Result<ColumnDefinition, String> {
+    let (name, rest) = split_word(line);
+    let (type_name, mut rest) = split_word(rest);
+    if type_name.is_empty() {
+        return Err(format!("Missing type for column '{}'", name));
+    }
+    let data_type = DataType::from_name(type_name)
+        .ok_or_else(|| format!("Unknown type '{}' for column '{}'", type_name, name))?;
+
+    let mut definition = ColumnDefinition {
+        name: name.to_string(),
+        data_type: Some(data_type),
+        nullable: true,
+        default: None,
+    };
+
+    while !rest.is_empty() {
+        let (word, remaining) = split_word(rest);
+        rest = remaining;
+        match word.to_ascii_uppercase().as_str() {
+            "NOT" => {
+                let (null_word, remaining) = split_word(rest);
+                if !null_word.eq_ignore_ascii_case("NULL") {
+                    return Err("Expected 'NULL' after 'NOT'".to_string());
+                }
+                rest = remaining;
+                definition.nullable = false;
+            }
+            "NULL" => definition.nullable = true,
+            "DEFAULT" => {
+                let (value, remaining) = split_default(rest)?;
+                rest = remaining;
+                if data_type.parse_field(&value).is_none() {
+                    return Err(format!(
+                        "Default '{}' for column '{}' is not a valid {}",
+                        value, name, data_type
+                    ));
+                }
+                definition.default = Some(value);
+            }
+            other => return Err(format!("Unexpected '{}' in column definition", other)),
+        }
+    }
+
+    Ok(definition)
+}
+
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    match text.find(char::is_whitespace) {
+        Some(end) => (&text[..end], text[end..].trim_start()),
+        None => (text, ""),
+    }
+}
+
+fn split_default(text: &str) -> Result<(String, &str), String> {
+    match text.strip_prefix('\'') {
+        Some(quoted) => {
+            let end = quoted
+                .find('\'')
+                .ok_or_else(|| "Unterminated default value".to_string())?;
+            Ok((quoted[..end].to_string(), quoted[end + 1..].trim_start()))
+        }
+        None => {
+            let (word, rest) = split_word(text);
+            if word.is_empty() {
+                return Err("Expected a value after 'DEFAULT'".to_string());
+            }
+            Ok((word.to_string(), rest))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_column_definition() {
+        let definition = parse_column_definition("quantity INTEGER NOT NULL DEFAULT 1").unwrap();
+        assert_eq!(definition.name, "quantity");
+        assert_eq!(definition.data_type, Some(DataType::Integer));
+        assert!(!definition.nullable);
+        assert_eq!(definition.default, Some("1".to_string()));
+
+        let definition = parse_column_definition("product TEXT DEFAULT 'No name'").unwrap();
+        assert!(definition.nullable);
+        assert_eq!(definition.default, Some("No name".to_string()));
+    }
+
+    #[test]
+    fn test_parse_column_definition_errors() {
+        assert!(parse_column_definition("quantity").is_err());
+        assert!(parse_column_definition("quantity MONEY").is_err());
+        assert!(parse_column_definition("quantity INTEGER DEFAULT abc").is_err());
+        assert!(parse_column_definition("quantity INTEGER NOT").is_err());
+    }
+
+    #[test]
+    fn test_to_field_validates_values() {
+        let mut schema = TableSchema::from_header(&["id", "quantity"]);
+        apply_sidecar_line("quantity INTEGER NOT NULL", &mut schema).unwrap();
+
+        let text = |s: &str| Value::Text(s.to_string());
+        assert_eq!(schema.to_field(0, &text("anything")).unwrap(), "anything");
+        assert_eq!(schema.to_field(0, &Value::Null).unwrap(), "");
+        assert!(matches!(
+            schema.to_field(0, &text("")),
+            Err(SQLError::TypeMismatch(message)) if message == "Value '' in column 'id' is the table's NULL marker '' and would read back as NULL"
+        ));
+        assert_eq!(schema.to_field(1, &Value::Integer(3)).unwrap(), "3");
+        assert!(matches!(
+            schema.to_field(1, &text("three")),
+            Err(SQLError::TypeMismatch(_))
+        ));
+        assert!(matches!(
+            schema.to_field(1, &Value::Null),
+            Err(SQLError::TypeMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_null_marker_option() {
+        let mut schema = TableSchema::from_header(&["name"]);
+        assert_eq!(schema.field_value(0, "").unwrap(), Value::Null);
+
+        apply_sidecar_line("@null_marker '\\N'", &mut schema).unwrap();
+        assert_eq!(
+            schema.field_value(0, "").unwrap(),
+            Value::Text(String::new())
+        );
+        assert_eq!(schema.field_value(0, "\\N").unwrap(), Value::Null);
+        assert_eq!(schema.to_field(0, &Value::Null).unwrap(), "\\N");
+        // Empty text and NULL stay distinct, and text that looks like the marker
+        // is not written as NULL
+        assert_eq!(schema.to_field(0, &Value::Text(String::new())).unwrap(), "");
+        assert!(matches!(
+            schema.to_field(0, &Value::Text("\\N".to_string())),
+            Err(SQLError::TypeMismatch(_))
+        ));
+
+        assert!(apply_sidecar_line("@nulls 'x'", &mut schema).is_err());
+        assert!(apply_sidecar_line("missing TEXT", &mut schema).is_err());
+    }
 }
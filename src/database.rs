@@ -0,0 +1,304 @@
+use crate::errors::SQLError;
+use crate::executor::execute_query;
+use crate::parser::{parse, tokenize};
+use crate::result::{ResultSet, RowIter};
+use std::path::Path;
+
+/// A directory of CSV tables that SQL statements run against. Each table is a
+/// `<name>.csv` file whose header names its columns, with an optional
+/// `<name>.schema` sidecar declaring their types.
+#[derive(Debug, Clone)]
+pub struct Database {
+    tables_path: String,
+}
+
+impl Database {
+    /// Opens the tables in the directory at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Database, SQLError> {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            return Err(SQLError::InvalidTable(format!(
+                "Cannot open tables directory '{}'",
+                path.display()
+            )));
+        }
+        Ok(Database {
+            tables_path: path.to_string_lossy().into_owned(),
+        })
+    }
+
+    /// Runs a statement and returns the rows it produced. Statements other than
+    /// SELECT produce only the rows of their RETURNING clause, if they have one.
+    pub fn query(&self, sql: &str) -> Result<ResultSet, SQLError> {
+        self.query_iter(sql)?.into_result_set()
+    }
+
+    /// Like [`Database::query`], but returns the rows as they are read instead
+    /// of all at once. Errors in the query itself, such as unknown columns, are
+    /// returned before any row is read. An INSERT, UPDATE or DELETE has already
+    /// changed the table when this returns, and reports how many rows it changed
+    /// through [`RowIter::rows_affected`]. The rows can be read on another
+    /// thread than the one that ran the query.
+    pub fn query_iter(&self, sql: &str) -> Result<RowIter, SQLError> {
+        let tokens = tokenize(sql)?;
+        execute_query(parse(&tokens)?, &self.tables_path)
+    }
+
+    /// Runs a statement and returns how many rows it inserted, updated or
+    /// deleted. For a SELECT, that is how many rows it returned.
+    pub fn execute(&self, sql: &str) -> Result<usize, SQLError> {
+        let mut rows = self.query_iter(sql)?;
+        match rows.rows_affected() {
+            Some(count) => Ok(count),
+            None => rows.try_fold(0, |count, row| row.map(|_| count + 1)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::result::{Column, ResultSet};
+    use crate::value::{DataType, Value};
+
+    fn database(name: &str) -> (Database, std::path::PathBuf) {
+        let dir =
+            std::env::temp_dir().join(format!("rustic_sql_db_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("ordenes.csv"),
+            "id,product,quantity\n1,Mouse,2\n2,Laptop,\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("ordenes.schema"), "id INTEGER NOT NULL\n").unwrap();
+        (Database::open(&dir).unwrap(), dir)
+    }
+
+    /// Each row as its values joined by commas, with NULL as an empty field.
+    fn text_rows(results: ResultSet) -> Vec<String> {
+        results
+            .into_iter()
+            .map(|row| {
+                let fields: Vec<String> = row.values().iter().map(Value::to_string).collect();
+                fields.join(",")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_query_returns_columns_and_typed_rows() {
+        let (database, dir) = database("query");
+        let results = database
+            .query("SELECT id, product, quantity * 1.5 AS total, id::FLOAT FROM ordenes;")
+            .unwrap();
+        assert_eq!(
+            results.columns(),
+            &[
+                Column {
+                    name: "id".to_string(),
+                    data_type: Some(DataType::Integer)
+                },
+                Column {
+                    name: "product".to_string(),
+                    data_type: None
+                },
+                Column {
+                    name: "total".to_string(),
+                    data_type: None
+                },
+                Column {
+                    name: "id::FLOAT".to_string(),
+                    data_type: Some(DataType::Float)
+                },
+            ]
+        );
+        let rows: Vec<Vec<Value>> = results.into_iter().map(|row| row.into_values()).collect();
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    Value::Integer(1),
+                    Value::Text("Mouse".to_string()),
+                    Value::Float(3.0),
+                    Value::Float(1.0)
+                ],
+                vec![
+                    Value::Integer(2),
+                    Value::Text("Laptop".to_string()),
+                    Value::Null,
+                    Value::Float(2.0)
+                ],
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_execute_returns_affected_rows() {
+        let (database, dir) = database("execute");
+        assert_eq!(
+            database
+                .execute("INSERT INTO ordenes VALUES (3, 'Cable', 1), (4, 'Hub', 5);")
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            database
+                .execute("UPDATE ordenes SET quantity = 0 WHERE quantity > 1;")
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            database
+                .execute("UPDATE ordenes SET quantity = 0 WHERE id = 9;")
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            database
+                .execute("DELETE FROM ordenes WHERE quantity = 0;")
+                .unwrap(),
+            2
+        );
+        assert_eq!(database.execute("SELECT * FROM ordenes;").unwrap(), 2);
+
+        // Statements that change rows have no result columns
+        let results = database.query("DELETE FROM ordenes WHERE id > 0;").unwrap();
+        assert!(results.columns().is_empty() && results.is_empty());
+        assert!(database.query("SELECT * FROM ordenes;").unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        let (database, dir) = database("errors");
+        assert!(matches!(
+            database.query("SELECT * FROM missing;"),
+            Err(SQLError::InvalidTable(_))
+        ));
+        let error = database.query("SELECT * FORM ordenes;").unwrap_err();
+        assert!(matches!(error.kind(), SQLError::InvalidSyntax(_)));
+        assert!(error.span().is_some());
+        assert!(matches!(
+            Database::open(dir.join("ordenes.csv")),
+            Err(SQLError::InvalidTable(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_query_iter_reads_rows_on_demand() {
+        let (database, dir) = database("query_iter");
+        // A malformed last record only fails the query once it is read
+        std::fs::write(
+            dir.join("ordenes.csv"),
+            "id,product,quantity\n1,Mouse,2\n2,Laptop,\n3\n",
+        )
+        .unwrap();
+
+        let mut rows = database
+            .query_iter("SELECT product FROM ordenes WHERE id > 0;")
+            .unwrap();
+        assert_eq!(rows.columns()[0].name, "product");
+        let first = rows.next().unwrap().unwrap();
+        assert_eq!(first[0], Value::Text("Mouse".to_string()));
+        assert!(rows.next().unwrap().is_ok());
+        assert!(rows.next().unwrap().is_err());
+
+        let rows = database
+            .query_iter("SELECT * FROM ordenes LIMIT 2;")
+            .unwrap();
+        assert_eq!(rows.count(), 2);
+        // Sorting reads every row before producing the first
+        let mut rows = database
+            .query_iter("SELECT * FROM ordenes ORDER BY id DESC;")
+            .unwrap();
+        assert!(rows.next().unwrap().is_err());
+        let rows = database
+            .query_iter("SELECT id FROM ordenes LIMIT 2;")
+            .unwrap();
+        let read = std::thread::spawn(move || rows.count()).join().unwrap();
+        assert_eq!(read, 2);
+        // Mistakes in the query are reported before reading any row
+        assert!(matches!(
+            database.query_iter("SELECT nombre FROM ordenes;"),
+            Err(SQLError::InvalidColumn(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_returning_outputs_changed_rows() {
+        let (database, dir) = database("returning");
+        let values = |results: ResultSet| -> Vec<Vec<Value>> {
+            results.into_iter().map(|row| row.into_values()).collect()
+        };
+
+        let inserted = database
+            .query("INSERT INTO ordenes (id, product) VALUES (3, 'Cable') RETURNING *;")
+            .unwrap();
+        assert_eq!(
+            values(inserted),
+            vec![vec![
+                Value::Integer(3),
+                Value::Text("Cable".to_string()),
+                Value::Null
+            ]]
+        );
+
+        let updated = database
+            .query_iter("UPDATE ordenes SET quantity = 7 WHERE id >= 2 RETURNING id, quantity * 2 AS double;")
+            .unwrap();
+        assert_eq!(updated.rows_affected(), Some(2));
+        let names: Vec<&str> = updated.columns().iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["id", "double"]);
+        assert_eq!(
+            values(updated.into_result_set().unwrap()),
+            vec![
+                vec![Value::Integer(2), Value::Integer(14)],
+                vec![Value::Integer(3), Value::Integer(14)],
+            ]
+        );
+
+        let deleted = database
+            .query("DELETE FROM ordenes WHERE product = 'Mouse' RETURNING ordenes.*;")
+            .unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted.rows()[0][0], Value::Integer(1));
+
+        // The RETURNING list is checked before the table is changed
+        assert!(database
+            .execute("DELETE FROM ordenes WHERE id > 0 RETURNING price;")
+            .is_err());
+        assert!(database
+            .execute("UPDATE ordenes SET quantity = 1 RETURNING COUNT(*);")
+            .is_err());
+        assert_eq!(database.execute("SELECT * FROM ordenes;").unwrap(), 2);
+        assert_eq!(
+            database
+                .query_iter("SELECT * FROM ordenes;")
+                .unwrap()
+                .rows_affected(),
+            None
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_expressions_in_queries() {
+        let (database, dir) = database("expressions");
+        let results = database
+            .query(
+                "SELECT id, UPPER(product), DATE '2024-01-31' + INTERVAL '1 month', \
+                 CASE WHEN quantity IS NULL THEN 'unknown' END, (SELECT COUNT(*) FROM ordenes) \
+                 FROM ordenes WHERE id IN (SELECT id FROM ordenes WHERE quantity IS NULL);",
+            )
+            .unwrap();
+        assert_eq!(results.columns()[1].name, "UPPER(product)");
+        assert_eq!(
+            text_rows(results),
+            vec!["2,LAPTOP,2024-02-29 00:00:00,unknown,2"]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,1038 @@
+use crate::errors::SQLError;
+use crate::value::Value;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MICROS_PER_MINUTE: i64 = 60 * MICROS_PER_SECOND;
+const MICROS_PER_HOUR: i64 = 60 * MICROS_PER_MINUTE;
+const MICROS_PER_DAY: i64 = 24 * MICROS_PER_HOUR;
+
+/// The years a date can be written with, in four digits. Arithmetic that lands
+/// outside them is out of range, since the result could not be read back.
+const YEARS: std::ops::RangeInclusive<i64> = 0..=9999;
+
+/// How many days a month counts as when intervals are compared or scaled.
+const DAYS_PER_MONTH: i64 = 30;
+
+const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// A calendar date, counted in days since 1970-01-01.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    days: i64,
+}
+
+/// A time of day, counted in microseconds since midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Time {
+    micros: i64,
+}
+
+/// A date and time of day without a time zone, counted in microseconds since
+/// 1970-01-01 00:00:00.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp {
+    micros: i64,
+}
+
+/// A span of time. Months and days are kept apart from the rest since how long
+/// they are depends on the date they are added to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    months: i64,
+    days: i64,
+    micros: i64,
+}
+
+impl Date {
+    pub fn from_ymd(year: i64, month: u32, day: u32) -> Option<Date> {
+        if !YEARS.contains(&year)
+            || !(1..=12).contains(&month)
+            || day < 1
+            || day > days_in_month(year, month)
+        {
+            return None;
+        }
+        Some(Date {
+            days: days_from_civil(year, month, day),
+        })
+    }
+
+    /// Reads an ISO-8601 date, `YYYY-MM-DD`.
+    pub fn parse(text: &str) -> Option<Date> {
+        let mut parts = text.split('-');
+        let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+        if parts.next().is_some() || year.len() != 4 {
+            return None;
+        }
+        Date::from_ymd(
+            parse_digits(year, 4)?,
+            parse_digits(month, 2)? as u32,
+            parse_digits(day, 2)? as u32,
+        )
+    }
+
+    pub fn ymd(&self) -> (i64, u32, u32) {
+        civil_from_days(self.days)
+    }
+
+    /// The date `days` after 1970-01-01, if it is in a year of `YEARS`.
+    fn from_days(days: i64) -> Option<Date> {
+        let first = days_from_civil(*YEARS.start(), 1, 1);
+        let last = days_from_civil(*YEARS.end(), 12, 31);
+        (first..=last).contains(&days).then_some(Date { days })
+    }
+
+    fn add_days(&self, days: i64) -> Option<Date> {
+        self.days.checked_add(days).and_then(Date::from_days)
+    }
+
+    /// Moves the date by whole months, keeping the day of the month unless the
+    /// new month is shorter.
+    fn add_months(&self, months: i64) -> Option<Date> {
+        let (year, month, day) = self.ymd();
+        let total = (year * 12 + month as i64 - 1).checked_add(months)?;
+        let (year, month) = (total.div_euclid(12), total.rem_euclid(12) as u32 + 1);
+        Date::from_ymd(year, month, day.min(days_in_month(year, month)))
+    }
+
+    /// The ISO day of the week, from 1 for Monday to 7 for Sunday.
+    fn iso_weekday(&self) -> i64 {
+        // 1970-01-01 was a Thursday
+        (self.days + 3).rem_euclid(7) + 1
+    }
+
+    fn day_of_year(&self) -> i64 {
+        let (year, _, _) = self.ymd();
+        self.days - days_from_civil(year, 1, 1) + 1
+    }
+
+    /// The ISO-8601 week number, where week 1 is the one with the year's first
+    /// Thursday.
+    fn iso_week(&self) -> i64 {
+        let (year, _, _) = self.ymd();
+        let week = (self.day_of_year() - self.iso_weekday() + 10) / 7;
+        if week < 1 {
+            iso_weeks_in_year(year - 1)
+        } else if week > iso_weeks_in_year(year) {
+            1
+        } else {
+            week
+        }
+    }
+}
+
+impl Time {
+    /// Reads a time of day, `HH:MM[:SS[.ffffff]]`.
+    pub fn parse(text: &str) -> Option<Time> {
+        let micros = parse_clock(text)?;
+        (micros < MICROS_PER_DAY).then_some(Time { micros })
+    }
+
+    fn add_micros(&self, micros: i64) -> Time {
+        Time {
+            micros: (self.micros + micros % MICROS_PER_DAY).rem_euclid(MICROS_PER_DAY),
+        }
+    }
+}
+
+impl Timestamp {
+    pub fn new(date: Date, time: Time) -> Timestamp {
+        Timestamp {
+            micros: date.days * MICROS_PER_DAY + time.micros,
+        }
+    }
+
+    /// The current date and time in UTC, as told by the system clock.
+    pub fn now() -> Timestamp {
+        let micros = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_micros() as i64);
+        Timestamp { micros }
+    }
+
+    /// Reads an ISO-8601 date and time, with a `T` or a space between them and
+    /// an optional trailing `Z`. A date alone is read as its midnight.
+    pub fn parse(text: &str) -> Option<Timestamp> {
+        let (date, time) = match text.split_once(['T', ' ']) {
+            Some((date, time)) => {
+                let time = time.trim();
+                (date, Time::parse(time.strip_suffix('Z').unwrap_or(time))?)
+            }
+            None => (text, Time { micros: 0 }),
+        };
+        Some(Timestamp::new(Date::parse(date)?, time))
+    }
+
+    pub fn date(&self) -> Date {
+        Date {
+            days: self.micros.div_euclid(MICROS_PER_DAY),
+        }
+    }
+
+    pub fn time(&self) -> Time {
+        Time {
+            micros: self.micros.rem_euclid(MICROS_PER_DAY),
+        }
+    }
+
+    fn add_interval(&self, interval: &Interval) -> Option<Timestamp> {
+        let date = self.date().add_months(interval.months)?;
+        let micros = date
+            .days
+            .checked_add(interval.days)?
+            .checked_mul(MICROS_PER_DAY)?
+            .checked_add(self.time().micros)?
+            .checked_add(interval.micros)?;
+        Date::from_days(micros.div_euclid(MICROS_PER_DAY))?;
+        Some(Timestamp { micros })
+    }
+}
+
+impl From<Date> for Timestamp {
+    fn from(date: Date) -> Timestamp {
+        Timestamp::new(date, Time { micros: 0 })
+    }
+}
+
+/// The time elapsed since midnight.
+impl From<Time> for Interval {
+    fn from(time: Time) -> Interval {
+        Interval {
+            months: 0,
+            days: 0,
+            micros: time.micros,
+        }
+    }
+}
+
+impl Interval {
+    /// Reads an interval written as amounts of units, such as `1 year 2 months`,
+    /// `-3 days` or `1.5 hours`, optionally followed by a `[-]HH:MM[:SS]` clock.
+    pub fn parse(text: &str) -> Option<Interval> {
+        let (mut months, mut days, mut micros) = (0.0, 0.0, 0.0);
+        let mut words = text.split_whitespace();
+        let mut empty = true;
+        while let Some(word) = words.next() {
+            empty = false;
+            if word.contains(':') {
+                let (sign, clock) = match word.strip_prefix('-') {
+                    Some(clock) => (-1.0, clock),
+                    None => (1.0, word),
+                };
+                micros += sign * parse_clock(clock)? as f64;
+                continue;
+            }
+            let amount: f64 = word
+                .parse()
+                .ok()
+                .filter(|amount: &f64| amount.is_finite())?;
+            match words.next()?.to_ascii_lowercase().as_str() {
+                "year" | "years" => months += amount * 12.0,
+                "month" | "months" | "mon" | "mons" => months += amount,
+                "week" | "weeks" => days += amount * 7.0,
+                "day" | "days" => days += amount,
+                "hour" | "hours" => micros += amount * MICROS_PER_HOUR as f64,
+                "minute" | "minutes" | "min" | "mins" => {
+                    micros += amount * MICROS_PER_MINUTE as f64
+                }
+                "second" | "seconds" | "sec" | "secs" => {
+                    micros += amount * MICROS_PER_SECOND as f64
+                }
+                _ => return None,
+            }
+        }
+        if empty {
+            return None;
+        }
+        Interval::from_parts(months, days, micros)
+    }
+
+    /// Builds an interval from fractional amounts, carrying the fraction of a
+    /// month into days and the fraction of a day into microseconds.
+    fn from_parts(months: f64, days: f64, micros: f64) -> Option<Interval> {
+        let days = days + months.fract() * DAYS_PER_MONTH as f64;
+        let micros = micros + days.fract() * MICROS_PER_DAY as f64;
+        let whole = |amount: f64| (amount.abs() < i64::MAX as f64).then_some(amount as i64);
+        Some(Interval {
+            months: whole(months.trunc())?,
+            days: whole(days.trunc())?,
+            micros: whole(micros.round())?,
+        })
+    }
+
+    /// The difference between two timestamps, in days and the time left over.
+    fn between(start: Timestamp, end: Timestamp) -> Interval {
+        let micros = end.micros - start.micros;
+        Interval {
+            months: 0,
+            days: micros / MICROS_PER_DAY,
+            micros: micros % MICROS_PER_DAY,
+        }
+    }
+
+    /// The approximate length of the interval, taking a month as 30 days, used
+    /// to compare intervals.
+    pub fn total_micros(&self) -> i128 {
+        (self.months as i128 * DAYS_PER_MONTH as i128 + self.days as i128) * MICROS_PER_DAY as i128
+            + self.micros as i128
+    }
+
+    fn negate(&self) -> Interval {
+        Interval {
+            months: -self.months,
+            days: -self.days,
+            micros: -self.micros,
+        }
+    }
+
+    fn add(&self, other: &Interval) -> Option<Interval> {
+        Some(Interval {
+            months: self.months.checked_add(other.months)?,
+            days: self.days.checked_add(other.days)?,
+            micros: self.micros.checked_add(other.micros)?,
+        })
+    }
+
+    fn scale(&self, factor: f64) -> Option<Interval> {
+        Interval::from_parts(
+            self.months as f64 * factor,
+            self.days as f64 * factor,
+            self.micros as f64 * factor,
+        )
+    }
+}
+
+/// Applies an arithmetic operator when either operand is a date, time, timestamp
+/// or interval, or returns `None` when neither is:
+///
+/// * a date plus or minus an integer moves it by that many days, and the
+///   difference of two dates is a number of days;
+/// * a date, time or timestamp plus or minus an interval is moved by it (a date
+///   becoming a timestamp), and the difference of two times or timestamps is an
+///   interval;
+/// * intervals add, subtract, and multiply or divide by numbers.
+///
+/// Text next to one of these is read as an interval, or else as the other
+/// operand's type.
+pub fn arithmetic(left: &Value, op: &str, right: &Value) -> Option<Result<Value, SQLError>> {
+    if !is_temporal(left) && !is_temporal(right) {
+        return None;
+    }
+    if left.is_null() || right.is_null() {
+        return Some(Ok(Value::Null));
+    }
+    let (left, right) = (coerce_text(left, right), coerce_text(right, left));
+    if let (Value::Date(date), Value::Timestamp(_)) = (&left, &right) {
+        return arithmetic(&Value::Timestamp(Timestamp::from(*date)), op, &right);
+    }
+    if let (Value::Timestamp(_), Value::Date(date)) = (&left, &right) {
+        return arithmetic(&left, op, &Value::Timestamp(Timestamp::from(*date)));
+    }
+
+    let result = match (&left, op, &right) {
+        (Value::Date(date), "+", Value::Integer(days))
+        | (Value::Integer(days), "+", Value::Date(date)) => date.add_days(*days).map(Value::Date),
+        (Value::Date(date), "-", Value::Integer(days)) => days
+            .checked_neg()
+            .and_then(|days| date.add_days(days))
+            .map(Value::Date),
+        (Value::Date(a), "-", Value::Date(b)) => Some(Value::Integer(a.days - b.days)),
+        (Value::Date(date), "+", Value::Time(time))
+        | (Value::Time(time), "+", Value::Date(date)) => {
+            Some(Value::Timestamp(Timestamp::new(*date, *time)))
+        }
+        (Value::Date(date), "+" | "-", Value::Interval(interval)) => {
+            let interval = if op == "-" {
+                interval.negate()
+            } else {
+                *interval
+            };
+            Timestamp::from(*date)
+                .add_interval(&interval)
+                .map(Value::Timestamp)
+        }
+        (Value::Interval(interval), "+", Value::Date(date)) => Timestamp::from(*date)
+            .add_interval(interval)
+            .map(Value::Timestamp),
+        (Value::Timestamp(timestamp), "+" | "-", Value::Interval(interval)) => {
+            let interval = if op == "-" {
+                interval.negate()
+            } else {
+                *interval
+            };
+            timestamp.add_interval(&interval).map(Value::Timestamp)
+        }
+        (Value::Interval(interval), "+", Value::Timestamp(timestamp)) => {
+            timestamp.add_interval(interval).map(Value::Timestamp)
+        }
+        (Value::Timestamp(a), "-", Value::Timestamp(b)) => {
+            Some(Value::Interval(Interval::between(*b, *a)))
+        }
+        (Value::Time(time), "+" | "-", Value::Interval(interval)) => {
+            let micros = if op == "-" {
+                -interval.micros
+            } else {
+                interval.micros
+            };
+            Some(Value::Time(time.add_micros(micros)))
+        }
+        (Value::Interval(interval), "+", Value::Time(time)) => {
+            Some(Value::Time(time.add_micros(interval.micros)))
+        }
+        (Value::Time(a), "-", Value::Time(b)) => Some(Value::Interval(Interval {
+            months: 0,
+            days: 0,
+            micros: a.micros - b.micros,
+        })),
+        (Value::Interval(a), "+", Value::Interval(b)) => a.add(b).map(Value::Interval),
+        (Value::Interval(a), "-", Value::Interval(b)) => a.add(&b.negate()).map(Value::Interval),
+        (Value::Interval(interval), "*", factor) | (factor, "*", Value::Interval(interval)) => {
+            match factor {
+                Value::Integer(n) => interval.scale(*n as f64).map(Value::Interval),
+                Value::Float(n) => interval.scale(*n).map(Value::Interval),
+                _ => return Some(Err(undefined_operator(op, &left, &right))),
+            }
+        }
+        (Value::Interval(interval), "/", Value::Integer(_) | Value::Float(_)) => {
+            let divisor = match right {
+                Value::Integer(n) => n as f64,
+                Value::Float(n) => n,
+                _ => 0.0,
+            };
+            if divisor == 0.0 {
+                return Some(Err(SQLError::GenericError(format!(
+                    "Division by zero in {} / {}",
+                    left, right
+                ))));
+            }
+            interval.scale(1.0 / divisor).map(Value::Interval)
+        }
+        _ => return Some(Err(undefined_operator(op, &left, &right))),
+    };
+    Some(result.ok_or_else(|| {
+        SQLError::GenericError(format!("{} {} {} is out of range", left, op, right))
+    }))
+}
+
+fn is_temporal(value: &Value) -> bool {
+    matches!(
+        value,
+        Value::Date(_) | Value::Time(_) | Value::Timestamp(_) | Value::Interval(_)
+    )
+}
+
+/// Reads text next to a temporal operand as an interval, or else as the type of
+/// `other`. Anything else is left as it is.
+fn coerce_text(value: &Value, other: &Value) -> Value {
+    let Value::Text(text) = value else {
+        return value.clone();
+    };
+    if !is_temporal(other) {
+        return value.clone();
+    }
+    let text = text.trim();
+    if let Some(interval) = Interval::parse(text) {
+        return Value::Interval(interval);
+    }
+    let parsed = match other {
+        Value::Time(_) => Time::parse(text).map(Value::Time),
+        Value::Date(_) => Date::parse(text).map(Value::Date),
+        _ => Timestamp::parse(text).map(Value::Timestamp),
+    };
+    parsed.unwrap_or_else(|| value.clone())
+}
+
+fn undefined_operator(op: &str, left: &Value, right: &Value) -> SQLError {
+    SQLError::TypeMismatch(format!(
+        "Operator '{}' is not defined for '{}' and '{}'",
+        op, left, right
+    ))
+}
+
+/// `EXTRACT(field FROM value)`: one field of a date, time, timestamp or interval
+/// as a number.
+pub fn extract(field: &str, value: &Value) -> Result<Value, SQLError> {
+    let field = field.to_ascii_uppercase();
+    let unsupported = || {
+        SQLError::InvalidSyntax(format!(
+            "EXTRACT field '{}' is not supported for '{}'",
+            field, value
+        ))
+    };
+    let (date, micros) = match value {
+        Value::Date(date) => (Some(*date), 0),
+        Value::Timestamp(timestamp) => (Some(timestamp.date()), timestamp.time().micros),
+        Value::Time(time) => (None, time.micros),
+        Value::Interval(interval) => {
+            return extract_interval(&field, interval).ok_or_else(unsupported)
+        }
+        _ => return Err(expected_temporal("EXTRACT", value)),
+    };
+    let seconds = |micros: i64| seconds_value(micros);
+    let number = match (field.as_str(), date) {
+        ("HOUR", _) => Value::Integer(micros / MICROS_PER_HOUR),
+        ("MINUTE", _) => Value::Integer(micros / MICROS_PER_MINUTE % 60),
+        ("SECOND", _) => seconds(micros % MICROS_PER_MINUTE),
+        ("EPOCH", None) => seconds(micros),
+        ("EPOCH", Some(date)) => seconds(date.days * MICROS_PER_DAY + micros),
+        (_, None) => return Err(unsupported()),
+        ("YEAR", Some(date)) => Value::Integer(date.ymd().0),
+        ("QUARTER", Some(date)) => Value::Integer((date.ymd().1 as i64 - 1) / 3 + 1),
+        ("MONTH", Some(date)) => Value::Integer(date.ymd().1 as i64),
+        ("DAY", Some(date)) => Value::Integer(date.ymd().2 as i64),
+        ("WEEK", Some(date)) => Value::Integer(date.iso_week()),
+        ("DOW", Some(date)) => Value::Integer(date.iso_weekday() % 7),
+        ("ISODOW", Some(date)) => Value::Integer(date.iso_weekday()),
+        ("DOY", Some(date)) => Value::Integer(date.day_of_year()),
+        _ => return Err(unsupported()),
+    };
+    Ok(number)
+}
+
+fn extract_interval(field: &str, interval: &Interval) -> Option<Value> {
+    Some(match field {
+        "YEAR" => Value::Integer(interval.months / 12),
+        "MONTH" => Value::Integer(interval.months % 12),
+        "DAY" => Value::Integer(interval.days),
+        "HOUR" => Value::Integer(interval.micros / MICROS_PER_HOUR),
+        "MINUTE" => Value::Integer(interval.micros / MICROS_PER_MINUTE % 60),
+        "SECOND" => seconds_value(interval.micros % MICROS_PER_MINUTE),
+        "EPOCH" => seconds_value(i64::try_from(interval.total_micros()).ok()?),
+        _ => return None,
+    })
+}
+
+/// A number of seconds, as an integer when it is whole.
+fn seconds_value(micros: i64) -> Value {
+    match micros % MICROS_PER_SECOND {
+        0 => Value::Integer(micros / MICROS_PER_SECOND),
+        _ => Value::Float(micros as f64 / MICROS_PER_SECOND as f64),
+    }
+}
+
+/// `DATE_TRUNC(unit, value)`: a date or timestamp rounded down to the start of
+/// its year, quarter, month, week (a Monday), day, hour, minute or second. A
+/// date stays a date.
+pub fn truncate(unit: &str, value: &Value) -> Result<Value, SQLError> {
+    let unit = unit.to_ascii_uppercase();
+    let timestamp = match value {
+        Value::Date(date) => Timestamp::from(*date),
+        Value::Timestamp(timestamp) => *timestamp,
+        _ => return Err(expected_date("DATE_TRUNC", value)),
+    };
+    let date = timestamp.date();
+    let (year, month, _) = date.ymd();
+    let start_of = |micros_per_unit: i64| Timestamp {
+        micros: timestamp.micros - timestamp.micros.rem_euclid(micros_per_unit),
+    };
+    let truncated = match unit.as_str() {
+        "YEAR" => Timestamp::from(Date::from_ymd(year, 1, 1).unwrap_or(date)),
+        "QUARTER" => {
+            Timestamp::from(Date::from_ymd(year, (month - 1) / 3 * 3 + 1, 1).unwrap_or(date))
+        }
+        "MONTH" => Timestamp::from(Date::from_ymd(year, month, 1).unwrap_or(date)),
+        "WEEK" => Timestamp::from(Date {
+            days: date.days - (date.iso_weekday() - 1),
+        }),
+        "DAY" => start_of(MICROS_PER_DAY),
+        "HOUR" => start_of(MICROS_PER_HOUR),
+        "MINUTE" => start_of(MICROS_PER_MINUTE),
+        "SECOND" => start_of(MICROS_PER_SECOND),
+        _ => return Err(unknown_unit("DATE_TRUNC", &unit)),
+    };
+    Ok(match value {
+        Value::Date(_) => Value::Date(truncated.date()),
+        _ => Value::Timestamp(truncated),
+    })
+}
+
+/// `DATE_DIFF(unit, start, end)`: how many boundaries of the unit lie between
+/// two dates or timestamps, such as how many month starts there are from
+/// January 31st to February 1st (one). Negative when `end` is before `start`.
+pub fn difference(unit: &str, start: &Value, end: &Value) -> Result<Value, SQLError> {
+    let unit = unit.to_ascii_uppercase();
+    let as_timestamp = |value: &Value| match value {
+        Value::Date(date) => Ok(Timestamp::from(*date)),
+        Value::Timestamp(timestamp) => Ok(*timestamp),
+        _ => Err(expected_date("DATE_DIFF", value)),
+    };
+    let (start, end) = (as_timestamp(start)?, as_timestamp(end)?);
+    let months = |timestamp: Timestamp| {
+        let (year, month, _) = timestamp.date().ymd();
+        year * 12 + month as i64 - 1
+    };
+    let units = |micros_per_unit: i64| {
+        end.micros.div_euclid(micros_per_unit) - start.micros.div_euclid(micros_per_unit)
+    };
+    let difference = match unit.as_str() {
+        "YEAR" => months(end).div_euclid(12) - months(start).div_euclid(12),
+        "QUARTER" => months(end).div_euclid(3) - months(start).div_euclid(3),
+        "MONTH" => months(end) - months(start),
+        // Weeks start on Monday, and 1970-01-05 was one
+        "WEEK" => (end.date().days - 4).div_euclid(7) - (start.date().days - 4).div_euclid(7),
+        "DAY" => units(MICROS_PER_DAY),
+        "HOUR" => units(MICROS_PER_HOUR),
+        "MINUTE" => units(MICROS_PER_MINUTE),
+        "SECOND" => units(MICROS_PER_SECOND),
+        _ => return Err(unknown_unit("DATE_DIFF", &unit)),
+    };
+    Ok(Value::Integer(difference))
+}
+
+/// `STRFTIME(value, format)`: a date or timestamp written out with `format`,
+/// whose `%` specifiers follow C's `strftime`: `%Y`, `%m`, `%d`, `%H`, `%M`,
+/// `%S`, `%f` (microseconds), `%j`, `%a`, `%b`, `%F`, `%T` and so on.
+pub fn format(value: &Value, format: &str) -> Result<String, SQLError> {
+    let timestamp = match value {
+        Value::Date(date) => Timestamp::from(*date),
+        Value::Timestamp(timestamp) => *timestamp,
+        _ => return Err(expected_date("STRFTIME", value)),
+    };
+    let date = timestamp.date();
+    let (year, month, day) = date.ymd();
+    let micros = timestamp.time().micros;
+    let hour = micros / MICROS_PER_HOUR;
+    let weekday = date.iso_weekday();
+
+    let mut output = String::new();
+    let mut chars = format.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            output.push(ch);
+            continue;
+        }
+        let written = match chars.next() {
+            Some('Y') => format!("{:04}", year),
+            Some('y') => format!("{:02}", year.rem_euclid(100)),
+            Some('m') => format!("{:02}", month),
+            Some('d') => format!("{:02}", day),
+            Some('H') => format!("{:02}", hour),
+            Some('I') => format!("{:02}", (hour + 11) % 12 + 1),
+            Some('p') => (if hour < 12 { "AM" } else { "PM" }).to_string(),
+            Some('M') => format!("{:02}", micros / MICROS_PER_MINUTE % 60),
+            Some('S') => format!("{:02}", micros / MICROS_PER_SECOND % 60),
+            Some('f') => format!("{:06}", micros % MICROS_PER_SECOND),
+            Some('j') => format!("{:03}", date.day_of_year()),
+            Some('u') => weekday.to_string(),
+            Some('w') => (weekday % 7).to_string(),
+            Some('a') => WEEKDAYS[weekday as usize - 1][..3].to_string(),
+            Some('A') => WEEKDAYS[weekday as usize - 1].to_string(),
+            Some('b') => MONTHS[month as usize - 1][..3].to_string(),
+            Some('B') => MONTHS[month as usize - 1].to_string(),
+            Some('F') => date.to_string(),
+            Some('T') => format!(
+                "{}",
+                Time {
+                    micros: micros - micros % MICROS_PER_SECOND
+                }
+            ),
+            Some('%') => "%".to_string(),
+            Some(other) => {
+                return Err(SQLError::InvalidSyntax(format!(
+                    "Unknown STRFTIME specifier '%{}'",
+                    other
+                )))
+            }
+            None => {
+                return Err(SQLError::InvalidSyntax(
+                    "STRFTIME format ends with '%'".to_string(),
+                ))
+            }
+        };
+        output.push_str(&written);
+    }
+    Ok(output)
+}
+
+fn expected_temporal(function: &str, value: &Value) -> SQLError {
+    SQLError::TypeMismatch(format!(
+        "{} expects a date, time, timestamp or interval, got '{}'",
+        function, value
+    ))
+}
+
+fn expected_date(function: &str, value: &Value) -> SQLError {
+    SQLError::TypeMismatch(format!(
+        "{} expects a date or timestamp, got '{}'",
+        function, value
+    ))
+}
+
+fn unknown_unit(function: &str, unit: &str) -> SQLError {
+    SQLError::InvalidSyntax(format!("Unknown {} unit '{}'", function, unit))
+}
+
+/// Reads a number of at most `max_len` digits.
+fn parse_digits(text: &str, max_len: usize) -> Option<i64> {
+    let valid =
+        !text.is_empty() && text.len() <= max_len && text.chars().all(|ch| ch.is_ascii_digit());
+    valid.then(|| text.parse().ok()).flatten()
+}
+
+/// Reads `H:MM[:SS[.ffffff]]` as microseconds, without limiting the hours.
+fn parse_clock(text: &str) -> Option<i64> {
+    let mut parts = text.split(':');
+    let (hours, minutes) = (parts.next()?, parts.next()?);
+    let seconds = parts.next().unwrap_or("0");
+    if parts.next().is_some() || hours.is_empty() || !hours.chars().all(|ch| ch.is_ascii_digit()) {
+        return None;
+    }
+    let (whole, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+    let (minutes, whole) = (parse_digits(minutes, 2)?, parse_digits(whole, 2)?);
+    if minutes > 59 || whole > 59 || fraction.len() > 9 {
+        return None;
+    }
+    if !fraction.chars().all(|ch| ch.is_ascii_digit()) {
+        return None;
+    }
+    // Digits past microseconds are dropped
+    let fraction_micros = format!("{:0<6}", fraction)[..6].parse::<i64>().ok()?;
+    hours
+        .parse::<i64>()
+        .ok()?
+        .checked_mul(MICROS_PER_HOUR)?
+        .checked_add(minutes * MICROS_PER_MINUTE + whole * MICROS_PER_SECOND + fraction_micros)
+}
+
+/// Writes microseconds as `HH:MM:SS`, followed by the fraction of a second
+/// when there is one.
+fn write_clock(f: &mut fmt::Formatter<'_>, micros: i64) -> fmt::Result {
+    if micros < 0 {
+        write!(f, "-")?;
+    }
+    let micros = micros.unsigned_abs();
+    let (hours, micros) = (
+        micros / MICROS_PER_HOUR as u64,
+        micros % MICROS_PER_HOUR as u64,
+    );
+    let (minutes, micros) = (
+        micros / MICROS_PER_MINUTE as u64,
+        micros % MICROS_PER_MINUTE as u64,
+    );
+    let (seconds, micros) = (
+        micros / MICROS_PER_SECOND as u64,
+        micros % MICROS_PER_SECOND as u64,
+    );
+    write!(f, "{:02}:{:02}:{:02}", hours, minutes, seconds)?;
+    if micros > 0 {
+        let fraction = format!("{:06}", micros);
+        write!(f, ".{}", fraction.trim_end_matches('0'))?;
+    }
+    Ok(())
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// A year has 53 ISO weeks when it starts on a Thursday, or on a Wednesday in a
+/// leap year.
+fn iso_weeks_in_year(year: i64) -> i64 {
+    let first_weekday = Date {
+        days: days_from_civil(year, 1, 1),
+    }
+    .iso_weekday();
+    match first_weekday == 4 || (first_weekday == 3 && is_leap_year(year)) {
+        true => 53,
+        false => 52,
+    }
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    // Count from March so that the leap day ends the year
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_from_march = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The year, month and day of a number of days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = ((month_from_march + 2) % 12 + 1) as u32;
+    let year = era * 400 + year_of_era + i64::from(month <= 2);
+    (year, month, day)
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = self.ymd();
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_clock(f, self.micros)
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.date(), self.time())
+    }
+}
+
+/// Written the way PostgreSQL does, as in `1 year 2 mons 3 days 04:05:06`, which
+/// reads back as the same interval.
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (years, months) = (self.months / 12, self.months % 12);
+        let mut parts = Vec::new();
+        for (amount, unit) in [(years, "year"), (months, "mon"), (self.days, "day")] {
+            match amount {
+                0 => {}
+                1 => parts.push(format!("1 {}", unit)),
+                _ => parts.push(format!("{} {}s", amount, unit)),
+            }
+        }
+        write!(f, "{}", parts.join(" "))?;
+        if self.micros != 0 || parts.is_empty() {
+            if !parts.is_empty() {
+                write!(f, " ")?;
+            }
+            write_clock(f, self.micros)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> Value {
+        Value::Date(Date::parse(text).unwrap())
+    }
+
+    fn timestamp(text: &str) -> Value {
+        Value::Timestamp(Timestamp::parse(text).unwrap())
+    }
+
+    fn interval(text: &str) -> Value {
+        Value::Interval(Interval::parse(text).unwrap())
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        for text in [
+            "1970-01-01",
+            "2024-02-29",
+            "1999-12-31",
+            "0001-01-01",
+            "9999-12-31",
+        ] {
+            assert_eq!(Date::parse(text).unwrap().to_string(), text);
+        }
+        for text in [
+            "2023-02-29",
+            "2024-13-01",
+            "2024-1-1x",
+            "24-01-01",
+            "2024-01",
+        ] {
+            assert_eq!(Date::parse(text), None, "{}", text);
+        }
+        assert_eq!(Date::parse("2024-3-5").unwrap().to_string(), "2024-03-05");
+        assert_eq!(Time::parse("7:05").unwrap().to_string(), "07:05:00");
+        assert_eq!(
+            Time::parse("23:59:59.250").unwrap().to_string(),
+            "23:59:59.25"
+        );
+        assert_eq!(Time::parse("24:00:00"), None);
+        assert_eq!(
+            Timestamp::parse("2024-01-15T08:30:00Z")
+                .unwrap()
+                .to_string(),
+            "2024-01-15 08:30:00"
+        );
+        assert_eq!(
+            Timestamp::parse("2024-01-15").unwrap().to_string(),
+            "2024-01-15 00:00:00"
+        );
+        assert_eq!(
+            Interval::parse("1 year 14 months -3 days 1.5 hours")
+                .unwrap()
+                .to_string(),
+            "2 years 2 mons -3 days 01:30:00"
+        );
+        assert_eq!(Interval::parse("1 week").unwrap().to_string(), "7 days");
+        assert_eq!(
+            Interval::parse("-00:00:01").unwrap().to_string(),
+            "-00:00:01"
+        );
+        assert_eq!(Interval::parse("0 days").unwrap().to_string(), "00:00:00");
+        for text in ["", "1", "day", "1 fortnight", "1 day 2"] {
+            assert_eq!(Interval::parse(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_calendar_arithmetic() {
+        let add = |left: Value, op: &str, right: Value| arithmetic(&left, op, &right).unwrap();
+        assert_eq!(
+            add(date("2024-02-28"), "+", Value::Integer(2)).unwrap(),
+            date("2024-03-01")
+        );
+        assert_eq!(
+            add(date("2024-03-01"), "-", date("2023-03-01")).unwrap(),
+            Value::Integer(366)
+        );
+        // Adding months keeps the day of the month where the month is long enough
+        assert_eq!(
+            add(date("2024-01-31"), "+", interval("1 month")).unwrap(),
+            timestamp("2024-02-29")
+        );
+        assert_eq!(
+            add(
+                timestamp("2024-12-31 23:00"),
+                "+",
+                Value::Text("2 hours".to_string())
+            )
+            .unwrap(),
+            timestamp("2025-01-01 01:00")
+        );
+        assert_eq!(
+            add(
+                timestamp("2024-01-02 01:00"),
+                "-",
+                timestamp("2024-01-01 00:00")
+            )
+            .unwrap()
+            .to_string(),
+            "1 day 01:00:00"
+        );
+        assert_eq!(
+            add(
+                Value::Time(Time::parse("23:30").unwrap()),
+                "+",
+                interval("45 minutes")
+            )
+            .unwrap()
+            .to_string(),
+            "00:15:00"
+        );
+        assert_eq!(
+            add(interval("1 day 12:00"), "*", Value::Float(1.5)).unwrap(),
+            interval("1 day 30:00")
+        );
+        assert!(arithmetic(&Value::Integer(1), "+", &Value::Integer(1)).is_none());
+        assert_eq!(
+            add(Value::Null, "+", interval("1 day")).unwrap(),
+            Value::Null
+        );
+        assert!(add(date("2024-01-01"), "*", Value::Integer(2)).is_err());
+        assert!(add(interval("1 day"), "/", Value::Integer(0)).is_err());
+        // Results past year 9999 could not be read back
+        assert!(add(date("9999-12-31"), "+", interval("1 day")).is_err());
+        assert!(add(date("9999-12-31"), "+", Value::Integer(1)).is_err());
+        assert!(add(timestamp("9999-12-31 23:00"), "+", interval("2 hours")).is_err());
+        assert!(add(date("0001-06-01"), "-", interval("2 years")).is_err());
+        assert!(add(date("2024-01-01"), "+", interval("100000 years")).is_err());
+        assert_eq!(
+            add(date("9999-12-30"), "+", Value::Integer(1)).unwrap(),
+            date("9999-12-31")
+        );
+    }
+
+    #[test]
+    fn test_date_functions() {
+        let moment = timestamp("2021-01-03 14:05:09.5");
+        let field = |name: &str| extract(name, &moment).unwrap();
+        assert_eq!(field("year"), Value::Integer(2021));
+        assert_eq!(field("QUARTER"), Value::Integer(1));
+        // 2021-01-03 is a Sunday in the last ISO week of 2020
+        assert_eq!(field("WEEK"), Value::Integer(53));
+        assert_eq!(field("DOW"), Value::Integer(0));
+        assert_eq!(field("SECOND"), Value::Float(9.5));
+        assert_eq!(
+            extract("WEEK", &date("2024-12-30")).unwrap(),
+            Value::Integer(1)
+        );
+        assert_eq!(
+            extract("EPOCH", &date("1970-01-02")).unwrap(),
+            Value::Integer(86_400)
+        );
+        assert_eq!(
+            extract("DAY", &interval("3 days")).unwrap(),
+            Value::Integer(3)
+        );
+        assert!(extract("YEAR", &Value::Time(Time::parse("10:00").unwrap())).is_err());
+
+        assert_eq!(
+            truncate("month", &date("2024-05-17")).unwrap(),
+            date("2024-05-01")
+        );
+        assert_eq!(
+            truncate("week", &date("2024-05-17")).unwrap(),
+            date("2024-05-13")
+        );
+        assert_eq!(
+            truncate("hour", &moment).unwrap(),
+            timestamp("2021-01-03 14:00:00")
+        );
+        assert!(truncate("decade", &moment).is_err());
+
+        let diff = |unit: &str, start: &str, end: &str| {
+            difference(unit, &timestamp(start), &timestamp(end)).unwrap()
+        };
+        assert_eq!(diff("month", "2024-01-31", "2024-02-01"), Value::Integer(1));
+        assert_eq!(diff("year", "2024-12-31", "2023-01-01"), Value::Integer(-1));
+        assert_eq!(
+            diff("day", "2024-01-01 23:00", "2024-01-02 01:00"),
+            Value::Integer(1)
+        );
+        assert_eq!(diff("week", "2024-05-12", "2024-05-13"), Value::Integer(1));
+
+        assert_eq!(
+            format(&moment, "%A %d %B %Y, %I:%M %p (%j) %F %T.%f %%").unwrap(),
+            "Sunday 03 January 2021, 02:05 PM (003) 2021-01-03 14:05:09.500000 %"
+        );
+        assert!(format(&moment, "%Q").is_err());
+    }
+}
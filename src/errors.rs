@@ -1,3 +1,4 @@
+use crate::parser::Span;
 use std::fmt;
 
 #[derive(Debug)]
@@ -5,7 +6,86 @@ pub enum SQLError {
     InvalidTable(String),
     InvalidColumn(String),
     InvalidSyntax(String),
+    TypeMismatch(String),
     GenericError(String),
+    /// An error caused by a known part of the query text
+    Located {
+        error: Box<SQLError>,
+        span: Span,
+    },
+}
+
+impl SQLError {
+    /// Attaches the part of the query text that caused the error, unless it
+    /// already has one.
+    pub fn at(self, span: Span) -> SQLError {
+        match self {
+            SQLError::Located { .. } => self,
+            error => SQLError::Located {
+                error: Box::new(error),
+                span,
+            },
+        }
+    }
+
+    /// The error itself, without the location a parse error carries, for
+    /// matching on its kind: `InvalidSyntax` whether or not it was located.
+    pub fn kind(&self) -> &SQLError {
+        match self {
+            SQLError::Located { error, .. } => error,
+            error => error,
+        }
+    }
+
+    /// The part of the query text that caused the error, if it is known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            SQLError::Located { span, .. } => Some(*span),
+            _ => None,
+        }
+    }
+
+    /// Formats the error for the user. A located error also shows its line and
+    /// column in `sql`, with the offending text underlined:
+    ///
+    /// ```text
+    /// INVALID_SYNTAX: Expected a SQL command (did you mean 'SELECT'?)
+    ///  --> line 1, column 1
+    ///   |
+    /// 1 | SELEC id FROM ordenes;
+    ///   | ^^^^^
+    /// ```
+    pub fn render(&self, sql: &str) -> String {
+        let SQLError::Located { error, span } = self else {
+            return self.to_string();
+        };
+        let start = span.start.min(sql.len());
+        let line_start = sql[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = sql[start..].find('\n').map_or(sql.len(), |i| start + i);
+        let line = sql[..start].matches('\n').count() + 1;
+        let column = sql[line_start..start].chars().count() + 1;
+        // Underline at least one character, and no further than the end of the line
+        let width = sql[start..span.end.clamp(start, line_end)]
+            .chars()
+            .count()
+            .max(1);
+
+        let number = line.to_string();
+        let gutter = " ".repeat(number.len());
+        format!(
+            "{}\n{}--> line {}, column {}\n{} |\n{} | {}\n{} | {}{}",
+            error,
+            gutter,
+            line,
+            column,
+            gutter,
+            number,
+            &sql[line_start..line_end],
+            gutter,
+            " ".repeat(column - 1),
+            "^".repeat(width)
+        )
+    }
 }
 
 impl fmt::Display for SQLError {
@@ -14,9 +94,73 @@ impl fmt::Display for SQLError {
             SQLError::InvalidTable(msg) => write!(f, "INVALID_TABLE: {}", msg),
             SQLError::InvalidColumn(msg) => write!(f, "INVALID_COLUMN: {}", msg),
             SQLError::InvalidSyntax(msg) => write!(f, "INVALID_SYNTAX: {}", msg),
+            SQLError::TypeMismatch(msg) => write!(f, "TYPE_MISMATCH: {}", msg),
             SQLError::GenericError(msg) => write!(f, "ERROR: {}", msg),
+            SQLError::Located { error, .. } => write!(f, "{}", error),
         }
     }
 }
 
 impl std::error::Error for SQLError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn located(message: &str, start: usize, end: usize) -> SQLError {
+        SQLError::InvalidSyntax(message.to_string()).at(Span { start, end })
+    }
+
+    #[test]
+    fn test_render_points_at_the_span() {
+        let sql = "SELECT id\nFROM ordenes\nWHERE id = 'x;";
+        assert_eq!(
+            located("Unterminated string literal", 34, sql.len()).render(sql),
+            [
+                "INVALID_SYNTAX: Unterminated string literal",
+                " --> line 3, column 12",
+                "  |",
+                "3 | WHERE id = 'x;",
+                "  |            ^^^",
+            ]
+            .join("\n")
+        );
+        // The end of the input is just past the last character
+        assert_eq!(
+            located("Expected table name", 13, 13).render("SELECT * FROM"),
+            [
+                "INVALID_SYNTAX: Expected table name",
+                " --> line 1, column 14",
+                "  |",
+                "1 | SELECT * FROM",
+                "  |              ^",
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            SQLError::InvalidColumn("Column 'x' does not exist".to_string()).render(sql),
+            "INVALID_COLUMN: Column 'x' does not exist"
+        );
+    }
+
+    #[test]
+    fn test_render_counts_characters() {
+        let error = located("Unexpected character: '!'", 25, 26);
+        assert_eq!(
+            error.render("SELECT 'Teléfono' AS ñ ! 1;"),
+            [
+                "INVALID_SYNTAX: Unexpected character: '!'",
+                " --> line 1, column 24",
+                "  |",
+                "1 | SELECT 'Teléfono' AS ñ ! 1;",
+                "  |                        ^",
+            ]
+            .join("\n")
+        );
+        // The first location is kept
+        let error = error.at(Span { start: 0, end: 1 });
+        assert_eq!(error.span(), Some(Span { start: 25, end: 26 }));
+        assert!(matches!(error.kind(), SQLError::InvalidSyntax(_)));
+        assert_eq!(SQLError::GenericError("x".to_string()).span(), None);
+    }
+}
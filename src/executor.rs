@@ -1,73 +1,541 @@
+use crate::csv::{format_record, write_record, CsvReader};
 use crate::data::{read_table_schema, TableSchema};
+use crate::datetime::Interval;
 use crate::errors::SQLError;
-use crate::parser::{DeleteQuery, Expression, InsertQuery, SQLQuery, SelectQuery, UpdateQuery};
+use crate::functions;
+use crate::parser::{
+    is_arithmetic_operator, is_comparison_operator, DeleteQuery, Distinct, Expression, InsertQuery,
+    InsertSource, JoinKind, SQLQuery, SelectItem, SelectQuery, UpdateQuery,
+};
+use crate::result::{Column, RowIter};
+use crate::utils::{did_you_mean, like_match};
+use crate::value::{DataType, Value};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::BufReader;
+use std::sync::Arc;
 
-pub fn execute_query(query: SQLQuery, tables_path: &str) -> Result<(), SQLError> {
+/// Runs a statement, returning the rows of a query or, for a statement that
+/// changes a table, how many rows it changed and the rows of its RETURNING clause.
+pub fn execute_query(query: SQLQuery, tables_path: &str) -> Result<RowIter, SQLError> {
     match query {
-        SQLQuery::Select(select_query) => execute_select(select_query, tables_path),
+        SQLQuery::Select(select_query) => {
+            let (columns, rows) = select_stream(select_query, tables_path)?;
+            Ok(RowIter::new(columns, rows))
+        }
         SQLQuery::Insert(insert_query) => execute_insert(insert_query, tables_path),
         SQLQuery::Update(update_query) => execute_update(update_query, tables_path),
         SQLQuery::Delete(delete_query) => execute_delete(delete_query, tables_path),
-        // Other query types...
-        _ => Err(SQLError::GenericError("Unsupported query type".to_string())),
     }
 }
 
-fn execute_select(select_query: SelectQuery, tables_path: &str) -> Result<(), SQLError> {
-    // Construct file path
-    let table_file = format!("{}/{}.csv", tables_path, select_query.table);
+/// Runs a SELECT query, returning its output columns and every row.
+fn select_rows(
+    select_query: SelectQuery,
+    tables_path: &str,
+) -> Result<(Vec<Column>, Vec<Vec<Value>>), SQLError> {
+    let (columns, rows) = select_stream(select_query, tables_path)?;
+    Ok((columns, rows.collect::<Result<_, _>>()?))
+}
 
-    // Read table schema
-    let schema = read_table_schema(&table_file)?;
+/// Plans a SELECT query as a pipeline of row operators, returning its output
+/// columns and the stream of its rows. Tables are read as rows are pulled from
+/// the stream.
+fn select_stream(
+    mut select_query: SelectQuery,
+    tables_path: &str,
+) -> Result<(Vec<Column>, RowStream), SQLError> {
+    // Read the rows of the FROM clause, from a table file or a derived table
+    let label = table_label(&select_query.table, &select_query.alias).to_string();
+    let (mut scope, from_rows): (Scope, RowStream) = match select_query.subquery.take() {
+        Some(subquery) => {
+            let (columns, rows) = select_stream(*subquery, tables_path)?;
+            (Scope::from_columns(tables_path, &label, &columns), rows)
+        }
+        None => {
+            let table_file = format!("{}/{}.csv", tables_path, select_query.table);
+            let schema = read_table_schema(&table_file)?;
+            let scope = Scope::from_table(tables_path, &label, &schema);
+            (scope, Box::new(scan_table(&table_file, schema)?))
+        }
+    };
 
-    // Validate selected columns
-    let selected_indices = get_selected_indices(&select_query, &schema)?;
+    // Read the schema of every joined table, widening the scope as we go
+    let mut join_plans = Vec::new();
+    for join in &mut select_query.joins {
+        let join_file = format!("{}/{}.csv", tables_path, join.table);
+        let join_schema = read_table_schema(&join_file)?;
+        let label = table_label(&join.table, &join.alias);
+        if scope.has_table(label) {
+            return Err(SQLError::InvalidTable(format!(
+                "Table name '{}' is specified more than once; use an alias",
+                label
+            )));
+        }
+        let right_scope = Scope::from_table(tables_path, label, &join_schema);
+        let joined_scope = scope.join(&right_scope);
+        check_columns(&join.condition, &joined_scope)?;
+        if join.condition.contains_aggregate() {
+            return Err(SQLError::InvalidSyntax(
+                "Aggregate functions are not allowed in ON".to_string(),
+            ));
+        }
+        bind_subqueries(&mut join.condition, &joined_scope)?;
+        let plan = JoinPlan {
+            kind: join.kind,
+            condition: join.condition.clone(),
+            left_scope: scope,
+            right_scope,
+        };
+        join_plans.push((join_file, join_schema, plan));
+        scope = joined_scope;
+    }
 
-    // Open the table file
-    let file = File::open(&table_file)
+    // Validate the columns referenced by the query
+    let mut expressions: Vec<&Expression> = Vec::new();
+    for item in &select_query.columns {
+        match item {
+            SelectItem::Expression { expr, .. } => expressions.push(expr),
+            SelectItem::QualifiedWildcard(table) => {
+                if !scope.has_table(table) {
+                    return Err(SQLError::InvalidTable(format!(
+                        "Table '{}' is not in the FROM clause",
+                        table
+                    )));
+                }
+            }
+            SelectItem::Wildcard => {}
+        }
+    }
+    expressions.extend(select_query.where_clause.iter());
+    expressions.extend(select_query.group_by.iter());
+    expressions.extend(select_query.having.iter());
+    for expr in expressions {
+        check_columns(expr, &scope)?;
+    }
+    if let Some(ref where_clause) = select_query.where_clause {
+        if where_clause.contains_aggregate() {
+            return Err(SQLError::InvalidSyntax(
+                "Aggregate functions are not allowed in WHERE".to_string(),
+            ));
+        }
+    }
+
+    // Stream the base table through each join, which reads the table on its right
+    // into memory
+    let mut rows = from_rows;
+    for (join_file, join_schema, plan) in join_plans {
+        let right_rows =
+            scan_table(&join_file, join_schema)?.collect::<Result<Vec<_>, SQLError>>()?;
+        rows = Box::new(HashJoin::new(plan, rows, right_rows)?);
+    }
+
+    process_rows(rows, scope, select_query)
+}
+
+/// The name a table is referred to by in the rest of the query.
+fn table_label<'a>(table: &'a str, alias: &'a Option<String>) -> &'a str {
+    alias.as_deref().unwrap_or(table)
+}
+
+/// Opens a table file and reads its records, after the header, as typed rows.
+fn scan_table(
+    table_file: &str,
+    schema: TableSchema,
+) -> Result<impl Iterator<Item = Result<Vec<Value>, SQLError>>, SQLError> {
+    let file = File::open(table_file)
         .map_err(|_| SQLError::InvalidTable(format!("Cannot open table file '{}'", table_file)))?;
-    let reader = BufReader::new(file);
+    let records = CsvReader::new(BufReader::new(file)).skip(1); // Skip header
+    Ok(records.map(move |record| schema.parse_row(&check_row(record?, &schema)?)))
+}
 
-    // Process rows
-    process_rows(reader, &schema, &select_query, selected_indices)
+/// Checks that every column referenced by `expr` exists, and unambiguously so.
+fn check_columns(expr: &Expression, scope: &Scope) -> Result<(), SQLError> {
+    match expr {
+        Expression::Column(col_name) => {
+            scope.resolve(None, col_name)?;
+        }
+        Expression::QualifiedColumn { table, column } => {
+            scope.resolve(Some(table), column)?;
+        }
+        _ => {}
+    }
+    expr.children()
+        .into_iter()
+        .try_for_each(|child| check_columns(child, scope))
 }
 
-fn get_selected_indices(
-    select_query: &SelectQuery,
-    schema: &TableSchema,
-) -> Result<Vec<usize>, SQLError> {
-    let mut indices = Vec::new();
-    if select_query.columns.len() == 1 && select_query.columns[0] == "*" {
-        let mut all_indices: Vec<usize> = schema.columns.values().cloned().collect();
-        all_indices.sort();
-        indices = all_indices;
-    } else {
-        for col in &select_query.columns {
-            if let Some(&idx) = schema.columns.get(col) {
-                indices.push(idx);
-            } else {
-                return Err(SQLError::InvalidColumn(format!(
-                    "Column '{}' does not exist",
-                    col
-                )));
+/// The columns visible to expressions: those of the FROM table followed by the
+/// columns of each joined table, in the order their values appear in a row.
+#[derive(Debug, Clone)]
+struct Scope {
+    columns: Vec<ScopeColumn>,
+    /// The directory of the tables, which subqueries read theirs from too
+    tables_path: String,
+}
+
+#[derive(Debug, Clone)]
+struct ScopeColumn {
+    table: String,
+    name: String,
+    data_type: Option<DataType>,
+}
+
+impl Scope {
+    fn from_table(tables_path: &str, table: &str, schema: &TableSchema) -> Scope {
+        let columns = schema
+            .definitions
+            .iter()
+            .map(|definition| ScopeColumn {
+                table: table.to_string(),
+                name: definition.name.clone(),
+                data_type: definition.data_type,
+            })
+            .collect();
+        Scope {
+            columns,
+            tables_path: tables_path.to_string(),
+        }
+    }
+
+    /// The scope of a derived table, whose columns are the output columns of its
+    /// query.
+    fn from_columns(tables_path: &str, table: &str, columns: &[Column]) -> Scope {
+        let columns = columns
+            .iter()
+            .map(|column| ScopeColumn {
+                table: table.to_string(),
+                name: column.name.clone(),
+                data_type: column.data_type,
+            })
+            .collect();
+        Scope {
+            columns,
+            tables_path: tables_path.to_string(),
+        }
+    }
+
+    /// The scope of rows made of a row of `self` followed by a row of `other`.
+    fn join(&self, other: &Scope) -> Scope {
+        let mut columns = self.columns.clone();
+        columns.extend(other.columns.iter().cloned());
+        Scope {
+            columns,
+            tables_path: self.tables_path.clone(),
+        }
+    }
+
+    fn has_table(&self, table: &str) -> bool {
+        self.columns.iter().any(|column| column.table == table)
+    }
+
+    /// Whether a column reference matches any column, even ambiguously.
+    fn contains(&self, table: Option<&str>, name: &str) -> bool {
+        self.columns
+            .iter()
+            .any(|column| column.name == name && table.is_none_or(|table| column.table == table))
+    }
+
+    /// Positions of the columns that belong to `table`.
+    fn table_columns<'a>(&'a self, table: &'a str) -> impl Iterator<Item = usize> + 'a {
+        self.columns
+            .iter()
+            .enumerate()
+            .filter(move |(_, column)| column.table == table)
+            .map(|(idx, _)| idx)
+    }
+
+    /// Finds the position of a column, optionally qualified by a table name or
+    /// alias. An unqualified name must match exactly one column.
+    fn resolve(&self, table: Option<&str>, name: &str) -> Result<usize, SQLError> {
+        let mut matches = self.columns.iter().enumerate().filter(|(_, column)| {
+            column.name == name && table.is_none_or(|table| column.table == table)
+        });
+        let display_name = match table {
+            Some(table) => format!("{}.{}", table, name),
+            None => name.to_string(),
+        };
+        match (matches.next(), matches.next()) {
+            (Some((idx, _)), None) => Ok(idx),
+            (Some(_), Some(_)) => Err(SQLError::InvalidColumn(format!(
+                "Column '{}' is ambiguous",
+                display_name
+            ))),
+            (None, _) => match table {
+                Some(table) if !self.has_table(table) => Err(SQLError::InvalidTable(format!(
+                    "Table '{}' is not in the FROM clause",
+                    table
+                ))),
+                _ => {
+                    let candidates = self
+                        .columns
+                        .iter()
+                        .filter(|column| table.is_none_or(|table| column.table == table))
+                        .map(|column| column.name.as_str());
+                    Err(SQLError::InvalidColumn(format!(
+                        "Column '{}' does not exist{}",
+                        display_name,
+                        did_you_mean(name, candidates)
+                    )))
+                }
+            },
+        }
+    }
+
+    /// Whether every column `expr` reads resolves in this scope, and it reads at
+    /// least one.
+    fn covers(&self, expr: &Expression) -> bool {
+        let mut found = false;
+        let resolves = self.covers_columns(expr, &mut found);
+        resolves && found
+    }
+
+    fn covers_columns(&self, expr: &Expression, found: &mut bool) -> bool {
+        let resolved = match expr {
+            Expression::Column(col_name) => Some(self.resolve(None, col_name).is_ok()),
+            Expression::QualifiedColumn { table, column } => {
+                Some(self.resolve(Some(table), column).is_ok())
+            }
+            _ => None,
+        };
+        match resolved {
+            Some(ok) => {
+                *found = true;
+                ok
+            }
+            None => expr
+                .children()
+                .into_iter()
+                .all(|child| self.covers_columns(child, found)),
+        }
+    }
+}
+
+/// A join and the columns on either side of it.
+struct JoinPlan {
+    kind: JoinKind,
+    condition: Expression,
+    /// Columns of the rows on the left of the join
+    left_scope: Scope,
+    right_scope: Scope,
+}
+
+/// Joins the rows streamed from the left with the rows of the right table, which
+/// are held in memory. Equality conditions between a column of each side are used
+/// to build a hash table over the right rows; without any, every pair of rows is
+/// tested. Either way the whole ON condition decides which pairs match, and outer
+/// joins pad unmatched rows with NULLs.
+struct HashJoin {
+    plan: JoinPlan,
+    /// Columns of the joined rows
+    scope: Scope,
+    left_rows: RowStream,
+    left_keys: Vec<Expression>,
+    right_rows: Vec<Vec<Value>>,
+    /// Positions of the right rows by their join key, when there are equality
+    /// conditions
+    index: Option<HashMap<Vec<String>, Vec<usize>>>,
+    right_matched: Vec<bool>,
+    /// Joined rows made from the last left row that are still to be returned
+    pending: std::vec::IntoIter<Vec<Value>>,
+    left_done: bool,
+}
+
+impl HashJoin {
+    fn new(
+        plan: JoinPlan,
+        left_rows: RowStream,
+        right_rows: Vec<Vec<Value>>,
+    ) -> Result<HashJoin, SQLError> {
+        let mut conjuncts = Vec::new();
+        split_conjuncts(&plan.condition, &mut conjuncts);
+        let mut left_keys = Vec::new();
+        let mut right_keys = Vec::new();
+        for conjunct in conjuncts {
+            if let Expression::BinaryOp { left, op, right } = conjunct {
+                if op != "=" {
+                    continue;
+                }
+                if plan.left_scope.covers(left) && plan.right_scope.covers(right) {
+                    left_keys.push(left.as_ref().clone());
+                    right_keys.push(right.as_ref());
+                } else if plan.left_scope.covers(right) && plan.right_scope.covers(left) {
+                    left_keys.push(right.as_ref().clone());
+                    right_keys.push(left.as_ref());
+                }
+            }
+        }
+
+        let index = if left_keys.is_empty() {
+            None
+        } else {
+            let mut index: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+            for (idx, row) in right_rows.iter().enumerate() {
+                let context = RowContext::new(&plan.right_scope, row);
+                if let Some(key) = join_key(&right_keys, &context)? {
+                    index.entry(key).or_default().push(idx);
+                }
+            }
+            Some(index)
+        };
+
+        Ok(HashJoin {
+            scope: plan.left_scope.join(&plan.right_scope),
+            right_matched: vec![false; right_rows.len()],
+            plan,
+            left_rows,
+            left_keys,
+            right_rows,
+            index,
+            pending: Vec::new().into_iter(),
+            left_done: false,
+        })
+    }
+
+    /// The joined rows a left row makes with the right rows it matches.
+    fn probe(&mut self, left_row: Vec<Value>) -> Result<Vec<Vec<Value>>, SQLError> {
+        let candidates = match self.index {
+            Some(ref index) => {
+                let context = RowContext::new(&self.plan.left_scope, &left_row);
+                let keys: Vec<&Expression> = self.left_keys.iter().collect();
+                match join_key(&keys, &context)? {
+                    Some(key) => index.get(&key).cloned().unwrap_or_default(),
+                    None => Vec::new(),
+                }
+            }
+            None => (0..self.right_rows.len()).collect(),
+        };
+
+        let mut results = Vec::new();
+        for idx in candidates {
+            let combined = [left_row.as_slice(), self.right_rows[idx].as_slice()].concat();
+            let context = RowContext::new(&self.scope, &combined);
+            if evaluate_where_clause(&self.plan.condition, &context)? {
+                self.right_matched[idx] = true;
+                results.push(combined);
+            }
+        }
+        if results.is_empty() && matches!(self.plan.kind, JoinKind::Left | JoinKind::Full) {
+            let mut padded = left_row;
+            padded.resize(self.scope.columns.len(), Value::Null);
+            results.push(padded);
+        }
+        Ok(results)
+    }
+
+    /// The right rows no left row matched, padded with NULLs, for the joins
+    /// that keep them.
+    fn unmatched_right_rows(&mut self) -> Vec<Vec<Value>> {
+        if !matches!(self.plan.kind, JoinKind::Right | JoinKind::Full) {
+            return Vec::new();
+        }
+        let left_width = self.plan.left_scope.columns.len();
+        let right_rows = std::mem::take(&mut self.right_rows);
+        right_rows
+            .into_iter()
+            .zip(&self.right_matched)
+            .filter(|(_, matched)| !**matched)
+            .map(|(right_row, _)| {
+                let mut padded = vec![Value::Null; left_width];
+                padded.extend(right_row);
+                padded
+            })
+            .collect()
+    }
+}
+
+impl Iterator for HashJoin {
+    type Item = Result<Vec<Value>, SQLError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.pending.next() {
+                return Some(Ok(row));
+            }
+            if self.left_done {
+                return None;
             }
+            let rows = match self.left_rows.next() {
+                Some(Ok(left_row)) => self.probe(left_row),
+                Some(Err(e)) => Err(e),
+                None => {
+                    self.left_done = true;
+                    Ok(self.unmatched_right_rows())
+                }
+            };
+            match rows {
+                Ok(rows) => self.pending = rows.into_iter(),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+fn split_conjuncts<'a>(expr: &'a Expression, conjuncts: &mut Vec<&'a Expression>) {
+    match expr {
+        Expression::BinaryOp { left, op, right } if op == "AND" => {
+            split_conjuncts(left, conjuncts);
+            split_conjuncts(right, conjuncts);
         }
+        _ => conjuncts.push(expr),
+    }
+}
+
+/// The hash key of a row for an equi-join, or `None` when a key value is NULL
+/// and the row cannot match. Values that `=` may find equal share a key: text
+/// that reads as a number, boolean, date, timestamp, time or interval takes the
+/// key of that value, and a time takes the key of an interval as long, since
+/// text such as `'10:30'` reads as either. Rows whose keys match are still
+/// checked against the whole ON condition.
+fn join_key(keys: &[&Expression], context: &RowContext) -> Result<Option<Vec<String>>, SQLError> {
+    let mut key = Vec::new();
+    for expr in keys {
+        let value = match get_value(expr, context)? {
+            Value::Null => return Ok(None),
+            Value::Text(text) => read_key_text(text.trim()),
+            value => value,
+        };
+        let value = match value {
+            Value::Time(time) => Value::Interval(Interval::from(time)),
+            value => value,
+        };
+        key.push(value.group_key());
+    }
+    Ok(Some(key))
+}
+
+/// Reads join key text as the first type it fits, as `Value::compare` would
+/// convert it when compared with a value of that type.
+fn read_key_text(text: &str) -> Value {
+    let temporal_types = [
+        DataType::Date,
+        DataType::Timestamp,
+        DataType::Time,
+        DataType::Interval,
+    ];
+    match Value::from_field(text) {
+        Value::Text(_) => temporal_types
+            .iter()
+            .find_map(|data_type| data_type.parse_field(text))
+            .unwrap_or_else(|| Value::Text(text.to_string())),
+        value => value,
     }
-    Ok(indices)
 }
 
 use std::fs::OpenOptions;
 // use std::io::Write;
 use std::io::{BufWriter, Write};
 
-fn execute_insert(insert_query: InsertQuery, tables_path: &str) -> Result<(), SQLError> {
+/// Appends the rows of an INSERT, returning how many were inserted.
+fn execute_insert(insert_query: InsertQuery, tables_path: &str) -> Result<RowIter, SQLError> {
     // Construct the file path
     let table_file = format!("{}/{}.csv", tables_path, insert_query.table);
 
     // Read the table schema
     let schema = read_table_schema(&table_file)?;
+    let scope = Scope::from_table(tables_path, &insert_query.table, &schema);
+    check_returning(&insert_query.returning, &scope)?;
 
     // Determine the columns to insert into
     let columns_to_insert = if insert_query.columns.is_empty() {
@@ -81,30 +549,56 @@ fn execute_insert(insert_query: InsertQuery, tables_path: &str) -> Result<(), SQ
 
     // Validate that all specified columns exist
     for col in &columns_to_insert {
-        if !schema.columns.contains_key(col) {
-            return Err(SQLError::InvalidColumn(format!(
-                "Column '{}' does not exist",
-                col
-            )));
-        }
+        schema.column_index(col)?;
     }
 
-    // Ensure the number of values matches the number of columns
-    if columns_to_insert.len() != insert_query.values.len() {
-        return Err(SQLError::InvalidSyntax(
-            "Number of columns and values do not match".to_string(),
-        ));
+    // Collect the rows to insert. The rows of a SELECT are all read before any is
+    // written, so it can read from the table it inserts into.
+    let rows = match insert_query.source {
+        InsertSource::Values(rows) => rows,
+        InsertSource::Select(select_query) => select_rows(*select_query, tables_path)?.1,
+    };
+
+    // Declared defaults fill the columns that are not inserted into
+    let mut default_values = vec![Value::Null; schema.columns.len()];
+    for (idx, definition) in schema.definitions.iter().enumerate() {
+        if let Some(ref default) = definition.default {
+            default_values[idx] = schema.field_value(idx, default)?;
+        }
     }
 
-    // Prepare the new row with empty strings
-    let num_columns = schema.columns.len();
-    let mut new_row = vec!["".to_string(); num_columns];
+    // Check every row against the declared column types before writing any
+    let mut records = String::new();
+    let mut inserted = Vec::new();
+    for values in &rows {
+        // Ensure the number of values matches the number of columns
+        if columns_to_insert.len() != values.len() {
+            return Err(SQLError::InvalidSyntax(
+                "Number of columns and values do not match".to_string(),
+            ));
+        }
 
-    // Fill in the values for the specified columns
-    for (col, val) in columns_to_insert.iter().zip(insert_query.values.iter()) {
-        let idx = schema.columns[col];
-        new_row[idx] = val.clone();
+        let mut new_values = default_values.clone();
+        for (col, val) in columns_to_insert.iter().zip(values.iter()) {
+            new_values[schema.columns[col]] = val.clone();
+        }
+        let new_row = new_values
+            .iter()
+            .enumerate()
+            .map(|(idx, value)| schema.to_field(idx, value))
+            .collect::<Result<Vec<String>, SQLError>>()?;
+        records.push_str(&format_record(&new_row));
+        if !insert_query.returning.is_empty() {
+            inserted.push(schema.parse_row(&new_row)?);
+        }
     }
+    let output = affected_rows(
+        rows.len(),
+        insert_query.returning,
+        &insert_query.table,
+        scope,
+        inserted,
+    )?;
 
     // Open the CSV file in append mode
     let mut file = OpenOptions::new()
@@ -112,31 +606,53 @@ fn execute_insert(insert_query: InsertQuery, tables_path: &str) -> Result<(), SQ
         .open(&table_file)
         .map_err(|_| SQLError::InvalidTable(format!("Cannot open table '{}'", table_file)))?;
 
-    // Write the new row to the file
-    let row_line = new_row.join(",") + "\n";
-    file.write_all(row_line.as_bytes())
+    // Append the new rows in a single write
+    file.write_all(records.as_bytes())
         .map_err(|_| SQLError::GenericError("Failed to write to table file".to_string()))?;
 
-    Ok(())
+    Ok(output)
 }
 
-fn execute_update(update_query: UpdateQuery, tables_path: &str) -> Result<(), SQLError> {
+/// Rewrites the table with the rows an UPDATE matches changed, returning how
+/// many it matched.
+fn execute_update(mut update_query: UpdateQuery, tables_path: &str) -> Result<RowIter, SQLError> {
     // Construct the file paths
     let table_file = format!("{}/{}.csv", tables_path, update_query.table);
     let temp_file = format!("{}/{}.tmp", tables_path, update_query.table);
 
     // Read the table schema
     let schema = read_table_schema(&table_file)?;
+    let scope = Scope::from_table(tables_path, &update_query.table, &schema);
+    let assigned_values = update_query
+        .assignments
+        .iter_mut()
+        .map(|assignment| &mut assignment.value);
+    for expr in assigned_values.chain(update_query.where_clause.iter_mut()) {
+        bind_subqueries(expr, &scope)?;
+    }
 
-    // Validate assignment columns
+    // Validate the assigned columns and the expressions assigned to them
+    let mut assigned_columns: Vec<(usize, &Expression)> = Vec::new();
     for assignment in &update_query.assignments {
-        if !schema.columns.contains_key(&assignment.column) {
-            return Err(SQLError::InvalidColumn(format!(
-                "Column '{}' does not exist",
+        let idx = schema.column_index(&assignment.column)?;
+        if assigned_columns
+            .iter()
+            .any(|(assigned, _)| *assigned == idx)
+        {
+            return Err(SQLError::InvalidSyntax(format!(
+                "Column '{}' is assigned more than once",
                 assignment.column
             )));
         }
+        check_columns(&assignment.value, &scope)?;
+        if assignment.value.contains_aggregate() {
+            return Err(SQLError::InvalidSyntax(
+                "Aggregate functions are not allowed in UPDATE".to_string(),
+            ));
+        }
+        assigned_columns.push((idx, &assignment.value));
     }
+    check_returning(&update_query.returning, &scope)?;
 
     // Open the table file for reading
     let file = File::open(&table_file)
@@ -148,15 +664,10 @@ fn execute_update(update_query: UpdateQuery, tables_path: &str) -> Result<(), SQ
         .map_err(|_| SQLError::GenericError("Failed to create temporary file".to_string()))?;
     let mut writer = BufWriter::new(temp_file_handle);
 
-    let mut lines = reader.lines();
+    let mut records = CsvReader::new(reader);
     // Write the header line
-    if let Some(Ok(header_line)) = lines.next() {
-        writer
-            .write_all(header_line.as_bytes())
-            .map_err(|_| SQLError::GenericError("Failed to write to temporary file".to_string()))?;
-        writer.write_all(b"\n").map_err(|_| {
-            SQLError::GenericError("Failed to write newline to temporary file".to_string())
-        })?;
+    if let Some(Ok(header)) = records.next() {
+        write_record(&mut writer, &header)?;
     } else {
         return Err(SQLError::InvalidTable(
             "Table is empty or corrupted".to_string(),
@@ -164,45 +675,65 @@ fn execute_update(update_query: UpdateQuery, tables_path: &str) -> Result<(), SQ
     }
 
     // Process each row
-    for line_result in lines {
-        let line = line_result
-            .map_err(|_| SQLError::InvalidTable("Failed to read table row".to_string()))?;
-        let mut row_values: Vec<String> = line.split(',').map(|s| s.to_string()).collect();
+    let mut updated = 0;
+    let mut returned = Vec::new();
+    for record in records {
+        let mut row_values = check_row(record?, &schema)?;
+        let typed_values = schema.parse_row(&row_values)?;
+        let context = RowContext::new(&scope, &typed_values);
 
         let mut should_update = true;
         if let Some(ref where_clause) = update_query.where_clause {
-            should_update = evaluate_where_clause(where_clause, &schema, &row_values)?;
+            should_update = evaluate_where_clause(where_clause, &context)?;
         }
 
         if should_update {
-            // Apply the assignments
-            for assignment in &update_query.assignments {
-                let idx = schema.columns[&assignment.column];
-                row_values[idx] = assignment.value.clone();
+            updated += 1;
+            // Every assignment reads the row as it was before any of them applied
+            for (idx, expr) in &assigned_columns {
+                row_values[*idx] = schema.to_field(*idx, &get_value(expr, &context)?)?;
+            }
+            if !update_query.returning.is_empty() {
+                returned.push(schema.parse_row(&row_values)?);
             }
         }
 
         // Write the updated (or original) row to the temp file
-        let updated_line = row_values.join(",") + "\n";
-        writer
-            .write_all(updated_line.as_bytes())
-            .map_err(|_| SQLError::GenericError("Failed to write to temporary file".to_string()))?;
+        write_record(&mut writer, &row_values)?;
     }
 
+    let output = affected_rows(
+        updated,
+        update_query.returning,
+        &update_query.table,
+        scope,
+        returned,
+    )?;
+
     // Replace the original file with the temp file
+    writer
+        .flush()
+        .map_err(|_| SQLError::GenericError("Failed to write to temporary file".to_string()))?;
     std::fs::rename(&temp_file, &table_file)
         .map_err(|_| SQLError::GenericError("Failed to replace original table file".to_string()))?;
 
-    Ok(())
+    Ok(output)
 }
 
-fn execute_delete(delete_query: DeleteQuery, tables_path: &str) -> Result<(), SQLError> {
+/// Rewrites the table without the rows a DELETE matches, returning how many it
+/// removed.
+fn execute_delete(mut delete_query: DeleteQuery, tables_path: &str) -> Result<RowIter, SQLError> {
     // Construct the file paths
     let table_file = format!("{}/{}.csv", tables_path, delete_query.table);
     let temp_file = format!("{}/{}.tmp", tables_path, delete_query.table);
 
     // Read the table schema
     let schema = read_table_schema(&table_file)?;
+    let scope = Scope::from_table(tables_path, &delete_query.table, &schema);
+    check_returning(&delete_query.returning, &scope)?;
+    if let Some(ref mut where_clause) = delete_query.where_clause {
+        bind_subqueries(where_clause, &scope)?;
+    }
 
     // Open the table file for reading
     let file = File::open(&table_file)
@@ -214,15 +745,10 @@ fn execute_delete(delete_query: DeleteQuery, tables_path: &str) -> Result<(), SQ
         .map_err(|_| SQLError::GenericError("Failed to create temporary file".to_string()))?;
     let mut writer = BufWriter::new(temp_file_handle);
 
-    let mut lines = reader.lines();
+    let mut records = CsvReader::new(reader);
     // Write the header line
-    if let Some(Ok(header_line)) = lines.next() {
-        writer
-            .write_all(header_line.as_bytes())
-            .map_err(|_| SQLError::GenericError("Failed to write to temporary file".to_string()))?;
-        writer.write_all(b"\n").map_err(|_| {
-            SQLError::GenericError("Failed to write newline to temporary file".to_string())
-        })?;
+    if let Some(Ok(header)) = records.next() {
+        write_record(&mut writer, &header)?;
     } else {
         return Err(SQLError::InvalidTable(
             "Table is empty or corrupted".to_string(),
@@ -230,173 +756,2467 @@ fn execute_delete(delete_query: DeleteQuery, tables_path: &str) -> Result<(), SQ
     }
 
     // Process each row
-    for line_result in lines {
-        let line = line_result
-            .map_err(|_| SQLError::InvalidTable("Failed to read table row".to_string()))?;
-        let row_values: Vec<String> = line.split(',').map(|s| s.to_string()).collect();
+    let mut deleted = 0;
+    let mut returned = Vec::new();
+    for record in records {
+        let row_values = check_row(record?, &schema)?;
 
         let mut should_delete = false;
+        let mut typed_values = Vec::new();
         if let Some(ref where_clause) = delete_query.where_clause {
-            should_delete = evaluate_where_clause(where_clause, &schema, &row_values)?;
+            typed_values = schema.parse_row(&row_values)?;
+            let context = RowContext::new(&scope, &typed_values);
+            should_delete = evaluate_where_clause(where_clause, &context)?;
         }
 
-        if !should_delete {
+        if should_delete {
+            deleted += 1;
+            if !delete_query.returning.is_empty() {
+                returned.push(typed_values);
+            }
+        } else {
             // Write the row to the temp file
-            let row_line = row_values.join(",") + "\n";
-            writer.write_all(row_line.as_bytes()).map_err(|_| {
-                SQLError::GenericError("Failed to write to temporary file".to_string())
-            })?;
+            write_record(&mut writer, &row_values)?;
         }
     }
 
+    let output = affected_rows(
+        deleted,
+        delete_query.returning,
+        &delete_query.table,
+        scope,
+        returned,
+    )?;
+
     // Replace the original file with the temp file
+    writer
+        .flush()
+        .map_err(|_| SQLError::GenericError("Failed to write to temporary file".to_string()))?;
     std::fs::rename(&temp_file, &table_file)
         .map_err(|_| SQLError::GenericError("Failed to replace original table file".to_string()))?;
 
+    Ok(output)
+}
+
+/// Checks the RETURNING list of a statement against the columns of its table,
+/// before any row is changed.
+fn check_returning(returning: &[SelectItem], scope: &Scope) -> Result<(), SQLError> {
+    for item in returning {
+        match item {
+            SelectItem::Expression { expr, .. } => {
+                check_columns(expr, scope)?;
+                if expr.contains_aggregate() {
+                    return Err(SQLError::InvalidSyntax(
+                        "Aggregate functions are not allowed in RETURNING".to_string(),
+                    ));
+                }
+            }
+            SelectItem::QualifiedWildcard(table) => {
+                if !scope.has_table(table) {
+                    return Err(SQLError::InvalidTable(format!(
+                        "Table '{}' is not the table being changed",
+                        table
+                    )));
+                }
+            }
+            SelectItem::Wildcard => {}
+        }
+    }
     Ok(())
 }
 
+/// The output of a statement that changed `count` rows: the RETURNING list
+/// evaluated against each of the changed `rows`, if the statement has one.
+fn affected_rows(
+    count: usize,
+    returning: Vec<SelectItem>,
+    table: &str,
+    scope: Scope,
+    rows: Vec<Vec<Value>>,
+) -> Result<RowIter, SQLError> {
+    if returning.is_empty() {
+        return Ok(RowIter::affected(count, Vec::new(), Vec::new()));
+    }
+    // Projected as a SELECT over the changed rows
+    let select_query = SelectQuery {
+        distinct: Distinct::All,
+        columns: returning,
+        table: table.to_string(),
+        alias: None,
+        subquery: None,
+        joins: Vec::new(),
+        where_clause: None,
+        group_by: Vec::new(),
+        having: None,
+        order_by: Vec::new(),
+        limit: None,
+        offset: 0,
+    };
+    let (columns, rows) = process_rows(Box::new(rows.into_iter().map(Ok)), scope, select_query)?;
+    let rows = rows.collect::<Result<Vec<_>, SQLError>>()?;
+    Ok(RowIter::affected(count, columns, rows))
+}
+
+/// Rows flowing from one operator of a query pipeline to the next.
+type RowStream = Box<dyn Iterator<Item = Result<Vec<Value>, SQLError>> + Send>;
+
+/// Builds the rest of a SELECT pipeline over the rows of its FROM clause:
+/// filter, project or group, sort, deduplicate and limit. Rows are pulled through
+/// one at a time, except by grouping and sorting, which read every row first.
+/// Returns the output columns and rows.
 fn process_rows(
-    reader: BufReader<File>,
-    schema: &TableSchema,
-    select_query: &SelectQuery,
-    selected_indices: Vec<usize>,
-) -> Result<(), SQLError> {
-    let mut lines = reader.lines().skip(1); // Skip header
-    let mut results = Vec::new();
+    rows: RowStream,
+    scope: Scope,
+    mut select_query: SelectQuery,
+) -> Result<(Vec<Column>, RowStream), SQLError> {
+    let grouped = is_aggregate_query(&select_query);
+    let columns = output_columns(&select_query, &scope);
+    // Output columns are named after the subqueries, so those are bound after
+    for expr in select_query.expressions_mut() {
+        bind_subqueries(expr, &scope)?;
+    }
+    let plan = plan_output(&select_query, &columns, &scope)?;
+    if grouped {
+        check_grouped_query(&select_query, &scope, &plan.hidden)?;
+    }
+    let width = columns.len();
+    let (offset, limit) = (select_query.offset, select_query.limit);
+    let query = Arc::new(select_query);
+    let scope = Arc::new(scope);
+    let plan = Arc::new(plan);
+
+    let mut rows = rows;
+    if let Some(where_clause) = query.where_clause.clone() {
+        let scope = Arc::clone(&scope);
+        rows = filter_rows(rows, move |values| {
+            evaluate_where_clause(&where_clause, &RowContext::new(&scope, values))
+        });
+    }
+
+    rows = if grouped {
+        // Aggregates need every row of a group before producing output
+        let (query, scope, plan) = (Arc::clone(&query), Arc::clone(&scope), Arc::clone(&plan));
+        materialize(rows, move |rows| {
+            group_rows(rows, &scope, &query, &plan.hidden)
+        })
+    } else {
+        let (query, scope, plan) = (Arc::clone(&query), Arc::clone(&scope), Arc::clone(&plan));
+        map_rows(rows, move |values| {
+            project_row(
+                &query.columns,
+                &plan.hidden,
+                &RowContext::new(&scope, &values),
+            )
+        })
+    };
+
+    if !plan.sort_keys.is_empty() {
+        let plan = Arc::clone(&plan);
+        rows = materialize(rows, move |mut rows| {
+            sort_results(&mut rows, &plan.sort_keys);
+            Ok(rows)
+        });
+    }
+
+    // Duplicates are dropped after sorting, so the first row of each in ORDER BY
+    // order is the one kept
+    if plan.distinct_keys.is_some() {
+        let plan = Arc::clone(&plan);
+        let mut seen = HashSet::new();
+        rows = filter_rows(rows, move |values| {
+            Ok(is_first_distinct(values, &plan, &mut seen))
+        });
+    }
+
+    // Drop the values only needed for sorting and deduplicating
+    if !plan.hidden.is_empty() {
+        rows = map_rows(rows, move |mut values| {
+            values.truncate(width);
+            Ok(values)
+        });
+    }
+
+    let rows = Limit {
+        rows,
+        offset,
+        remaining: limit,
+    };
+    Ok((columns, Box::new(rows)))
+}
+
+/// Keeps the rows for which `keep` is true, passing errors through.
+fn filter_rows(
+    rows: RowStream,
+    mut keep: impl FnMut(&[Value]) -> Result<bool, SQLError> + Send + 'static,
+) -> RowStream {
+    Box::new(rows.filter_map(move |row| match row {
+        Ok(values) => match keep(&values) {
+            Ok(true) => Some(Ok(values)),
+            Ok(false) => None,
+            Err(e) => Some(Err(e)),
+        },
+        Err(e) => Some(Err(e)),
+    }))
+}
 
-    while let Some(Ok(line)) = lines.next() {
-        let row_values: Vec<String> = line.split(',').map(|s| s.to_string()).collect();
+/// Transforms every row, passing errors through.
+fn map_rows(
+    rows: RowStream,
+    mut transform: impl FnMut(Vec<Value>) -> Result<Vec<Value>, SQLError> + Send + 'static,
+) -> RowStream {
+    Box::new(rows.map(move |row| row.and_then(&mut transform)))
+}
 
-        let mut include_row = true;
-        if let Some(ref where_clause) = select_query.where_clause {
-            include_row = evaluate_where_clause(where_clause, schema, &row_values)?;
+/// A blocking operator: reads every row, then produces the rows `process` makes
+/// of them. Nothing is read until the first output row is requested.
+fn materialize(
+    rows: RowStream,
+    process: impl FnOnce(Vec<Vec<Value>>) -> Result<Vec<Vec<Value>>, SQLError> + Send + 'static,
+) -> RowStream {
+    let results =
+        std::iter::once_with(move || rows.collect::<Result<Vec<_>, _>>().and_then(process));
+    Box::new(results.flat_map(|results| -> RowStream {
+        match results {
+            Ok(rows) => Box::new(rows.into_iter().map(Ok)),
+            Err(e) => Box::new(std::iter::once(Err(e))),
         }
+    }))
+}
 
-        if include_row {
-            let selected_values: Vec<String> = selected_indices
-                .iter()
-                .map(|&idx| row_values[idx].clone())
-                .collect();
-            results.push(selected_values);
+/// Skips the first `offset` rows and ends after `remaining` more, if limited.
+/// No more rows are pulled from the operators below once the limit is reached,
+/// so a query without blocking operators stops reading its tables there.
+struct Limit {
+    rows: RowStream,
+    offset: usize,
+    remaining: Option<usize>,
+}
+
+impl Iterator for Limit {
+    type Item = Result<Vec<Value>, SQLError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.remaining == Some(0) {
+                return None;
+            }
+            match self.rows.next()? {
+                Ok(_) if self.offset > 0 => self.offset -= 1,
+                Ok(values) => {
+                    if let Some(ref mut remaining) = self.remaining {
+                        *remaining -= 1;
+                    }
+                    return Some(Ok(values));
+                }
+                Err(e) => return Some(Err(e)),
+            }
         }
     }
+}
 
-    // Handle ORDER BY if present
-    if let Some(ref order_by) = select_query.order_by {
-        sort_results(&mut results, &selected_indices, schema, order_by)?;
+/// Whether `values` is the first row seen with its DISTINCT key. Every row is
+/// distinct when the query does not deduplicate.
+fn is_first_distinct(values: &[Value], plan: &OutputPlan, seen: &mut HashSet<Vec<String>>) -> bool {
+    match plan.distinct_keys {
+        Some(ref keys) => seen.insert(keys.iter().map(|&idx| values[idx].group_key()).collect()),
+        None => true,
     }
+}
 
-    // Output the results
-    output_results(&selected_indices, schema, &results)?;
+/// A query aggregates when it groups rows, filters groups, or calls an aggregate
+/// function in its select list or ORDER BY.
+fn is_aggregate_query(select_query: &SelectQuery) -> bool {
+    !select_query.group_by.is_empty()
+        || select_query.having.is_some()
+        || select_query
+            .order_by
+            .iter()
+            .any(|key| key.expression.contains_aggregate())
+        || select_query.columns.iter().any(|item| match item {
+            SelectItem::Expression { expr, .. } => expr.contains_aggregate(),
+            SelectItem::Wildcard | SelectItem::QualifiedWildcard(_) => false,
+        })
+}
 
+/// Checks that the output of an aggregate query, including the `hidden` sort
+/// keys, is made of grouped columns and aggregates.
+fn check_grouped_query(
+    select_query: &SelectQuery,
+    scope: &Scope,
+    hidden: &[Expression],
+) -> Result<(), SQLError> {
+    for expr in hidden {
+        check_grouped(expr, &select_query.group_by, scope)?;
+    }
+    for item in &select_query.columns {
+        match item {
+            SelectItem::Expression { expr, .. } => {
+                check_grouped(expr, &select_query.group_by, scope)?
+            }
+            SelectItem::Wildcard | SelectItem::QualifiedWildcard(_) => {
+                return Err(SQLError::InvalidSyntax(
+                    "'*' cannot be selected in an aggregate query".to_string(),
+                ))
+            }
+        }
+    }
+    if let Some(ref having) = select_query.having {
+        check_grouped(having, &select_query.group_by, scope)?;
+    }
     Ok(())
 }
 
-fn evaluate_where_clause(
-    expr: &Expression,
-    schema: &TableSchema,
-    row_values: &[String],
-) -> Result<bool, SQLError> {
-    match expr {
-        Expression::BinaryOp { left, op, right } => {
-            let left_value = get_value(left, schema, row_values)?;
-            let right_value = get_value(right, schema, row_values)?;
+/// Partitions rows by their GROUP BY values, in order of first appearance, and
+/// produces one output row per group that passes the HAVING clause.
+fn group_rows(
+    rows: Vec<Vec<Value>>,
+    scope: &Scope,
+    select_query: &SelectQuery,
+    hidden: &[Expression],
+) -> Result<Vec<Vec<Value>>, SQLError> {
+    let mut groups: Vec<Vec<Vec<Value>>> = Vec::new();
+    let mut group_positions: HashMap<Vec<String>, usize> = HashMap::new();
+    for row in rows {
+        let context = RowContext::new(scope, &row);
+        let key = select_query
+            .group_by
+            .iter()
+            .map(|expr| get_value(expr, &context).map(|value| value.group_key()))
+            .collect::<Result<Vec<String>, SQLError>>()?;
+        match group_positions.get(&key) {
+            Some(&position) => groups[position].push(row),
+            None => {
+                group_positions.insert(key, groups.len());
+                groups.push(vec![row]);
+            }
+        }
+    }
 
-            match op.as_str() {
-                "=" => Ok(left_value == right_value),
-                "<" => Ok(left_value < right_value),
-                ">" => Ok(left_value > right_value),
-                "<=" => Ok(left_value <= right_value),
-                ">=" => Ok(left_value >= right_value),
-                "<>" => Ok(left_value != right_value),
-                _ => Err(SQLError::InvalidSyntax(format!(
-                    "Unknown operator '{}'",
-                    op
-                ))),
+    // Without GROUP BY the whole table is one group, even when it has no rows
+    if select_query.group_by.is_empty() && groups.is_empty() {
+        groups.push(Vec::new());
+    }
+
+    let mut results = Vec::new();
+    for group in &groups {
+        // Grouping columns have the same value on every row, so read them from the first
+        let context = RowContext {
+            scope,
+            values: group.first().map_or(&[], |row| row.as_slice()),
+            group: Some(group),
+        };
+        if let Some(ref having) = select_query.having {
+            if !evaluate_where_clause(having, &context)? {
+                continue;
             }
         }
-        _ => Err(SQLError::InvalidSyntax(
-            "Unsupported expression in WHERE clause".to_string(),
-        )),
+        results.push(project_row(&select_query.columns, hidden, &context)?);
     }
+
+    Ok(results)
 }
 
-fn get_value(
+/// Checks that `expr` only reads columns through GROUP BY expressions or aggregates.
+/// A column is grouped when a GROUP BY column resolves to it, however either of
+/// them is qualified.
+fn check_grouped(
     expr: &Expression,
-    schema: &TableSchema,
-    row_values: &[String],
-) -> Result<String, SQLError> {
+    group_by: &[Expression],
+    scope: &Scope,
+) -> Result<(), SQLError> {
+    if group_by.contains(expr) {
+        return Ok(());
+    }
+    if let Some(idx) = column_position(expr, scope) {
+        if group_by
+            .iter()
+            .any(|group_expr| column_position(group_expr, scope) == Some(idx))
+        {
+            return Ok(());
+        }
+    }
     match expr {
-        Expression::Literal(val) => Ok(val.clone()),
-        Expression::Column(col_name) => {
-            if let Some(&idx) = schema.columns.get(col_name) {
-                Ok(row_values[idx].clone())
-            } else {
-                Err(SQLError::InvalidColumn(format!(
-                    "Column '{}' does not exist",
-                    col_name
-                )))
+        Expression::Aggregate { argument, .. } => {
+            if argument
+                .as_ref()
+                .is_some_and(|arg| arg.contains_aggregate())
+            {
+                return Err(SQLError::InvalidSyntax(
+                    "Aggregate function calls cannot be nested".to_string(),
+                ));
             }
+            Ok(())
         }
-        _ => Err(SQLError::InvalidSyntax(
-            "Unsupported expression".to_string(),
-        )),
+        Expression::Column(_) | Expression::QualifiedColumn { .. } => {
+            Err(SQLError::InvalidColumn(format!(
+                "Column '{}' must appear in the GROUP BY clause or be used in an aggregate function",
+                expr
+            )))
+        }
+        // A correlated subquery reads the columns of the group it is evaluated for
+        Expression::Subquery(_) | Expression::Exists(_) | Expression::InSubquery { .. } => {
+            let mut references = Vec::new();
+            outer_references(expr, &mut references);
+            references
+                .into_iter()
+                .chain(expr.children())
+                .try_for_each(|child| check_grouped(child, group_by, scope))
+        }
+        _ => expr
+            .children()
+            .into_iter()
+            .try_for_each(|child| check_grouped(child, group_by, scope)),
     }
 }
 
-fn sort_results(
-    results: &mut [Vec<String>],
-    selected_indices: &[usize],
-    schema: &TableSchema,
-    order_by: &crate::parser::OrderBy,
-) -> Result<(), SQLError> {
-    let order_idx = schema.columns.get(&order_by.column).ok_or_else(|| {
-        SQLError::InvalidColumn(format!("Column '{}' does not exist", order_by.column))
-    })?;
-
-    let pos_in_selected = selected_indices
-        .iter()
-        .position(|&idx| idx == *order_idx)
-        .ok_or_else(|| {
-            SQLError::InvalidColumn(format!(
-                "Column '{}' is not in the selected columns",
-                order_by.column
-            ))
-        })?;
-
-    if order_by.ascending {
-        results.sort_by(|a, b| a[pos_in_selected].cmp(&b[pos_in_selected]));
-    } else {
-        results.sort_by(|a, b| b[pos_in_selected].cmp(&a[pos_in_selected]));
+/// Collects the references to the row of an enclosing query in `expr`, including
+/// those in the subqueries nested in it.
+fn outer_references<'a>(expr: &'a Expression, references: &mut Vec<&'a Expression>) {
+    match expr {
+        Expression::OuterColumn { reference, .. } => references.push(reference),
+        Expression::Subquery(query)
+        | Expression::Exists(query)
+        | Expression::InSubquery { query, .. } => query
+            .expressions()
+            .into_iter()
+            .for_each(|expr| outer_references(expr, references)),
+        _ => {}
+    }
+    for child in expr.children() {
+        outer_references(child, references);
     }
+}
 
-    Ok(())
+/// The row position a column reference resolves to, if `expr` is one.
+fn column_position(expr: &Expression, scope: &Scope) -> Option<usize> {
+    match expr {
+        Expression::Column(col_name) => scope.resolve(None, col_name).ok(),
+        Expression::QualifiedColumn { table, column } => scope.resolve(Some(table), column).ok(),
+        _ => None,
+    }
 }
 
-fn output_results(
-    selected_indices: &[usize],
-    schema: &TableSchema,
-    results: &[Vec<String>],
-) -> Result<(), SQLError> {
-    // Print header
-    let headers: Vec<_> = selected_indices
-        .iter()
-        .map(|&idx| {
-            schema
-                .columns
-                .iter()
-                .find(|&(_, &i)| i == idx)
-                .map(|(name, _)| name.clone())
-                .unwrap()
-        })
-        .collect();
-    println!("{}", headers.join(","));
+/// Evaluates the select list for one output row, followed by the `hidden`
+/// expressions that are only needed to sort it.
+fn project_row(
+    columns: &[SelectItem],
+    hidden: &[Expression],
+    context: &RowContext,
+) -> Result<Vec<Value>, SQLError> {
+    let mut values = Vec::new();
+    for item in columns {
+        match item {
+            SelectItem::Wildcard => values.extend(context.values.iter().cloned()),
+            SelectItem::QualifiedWildcard(table) => values.extend(
+                context
+                    .scope
+                    .table_columns(table)
+                    .map(|idx| context.values[idx].clone()),
+            ),
+            SelectItem::Expression { expr, .. } => values.push(get_value(expr, context)?),
+        }
+    }
+    for expr in hidden {
+        values.push(get_value(expr, context)?);
+    }
+    Ok(values)
+}
 
-    // Print rows
-    for row in results {
-        println!("{}", row.join(","));
+fn output_columns(select_query: &SelectQuery, scope: &Scope) -> Vec<Column> {
+    let scope_column = |idx: usize| Column {
+        name: scope.columns[idx].name.clone(),
+        data_type: scope.columns[idx].data_type,
+    };
+    let mut columns = Vec::new();
+    for item in &select_query.columns {
+        match item {
+            SelectItem::Wildcard => columns.extend((0..scope.columns.len()).map(scope_column)),
+            SelectItem::QualifiedWildcard(table) => {
+                columns.extend(scope.table_columns(table).map(scope_column))
+            }
+            SelectItem::Expression { expr, alias } => {
+                let name = match (alias, expr) {
+                    (Some(alias), _) => alias.clone(),
+                    // A column is headed by its bare, unquoted name, like a wildcard column
+                    (None, Expression::QualifiedColumn { column, .. })
+                    | (None, Expression::Column(column)) => column.clone(),
+                    (None, expr) => expr.to_string(),
+                };
+                let data_type = match expr {
+                    Expression::Cast { data_type, .. } => Some(*data_type),
+                    expr => {
+                        column_position(expr, scope).and_then(|idx| scope.columns[idx].data_type)
+                    }
+                };
+                columns.push(Column { name, data_type });
+            }
+        }
     }
+    columns
+}
 
-    Ok(())
+/// Rejects records whose field count does not match the table header.
+fn check_row(row_values: Vec<String>, schema: &TableSchema) -> Result<Vec<String>, SQLError> {
+    if row_values.len() != schema.definitions.len() {
+        return Err(SQLError::InvalidTable(format!(
+            "Row has {} fields but the table has {} columns",
+            row_values.len(),
+            schema.definitions.len()
+        )));
+    }
+    Ok(row_values)
+}
+
+/// The row an expression is evaluated against.
+struct RowContext<'a> {
+    scope: &'a Scope,
+    values: &'a [Value],
+    /// Every row of the current group, when evaluating an aggregate query
+    group: Option<&'a [Vec<Value>]>,
+}
+
+impl<'a> RowContext<'a> {
+    fn new(scope: &'a Scope, values: &'a [Value]) -> RowContext<'a> {
+        RowContext {
+            scope,
+            values,
+            group: None,
+        }
+    }
+}
+
+fn evaluate_where_clause(expr: &Expression, context: &RowContext) -> Result<bool, SQLError> {
+    // A row only matches when the condition is TRUE, not FALSE or UNKNOWN
+    Ok(evaluate_condition(expr, context)? == Some(true))
+}
+
+/// Evaluates a condition with SQL three-valued logic, where `None` is UNKNOWN:
+/// comparisons involving NULL are UNKNOWN, and AND, OR and NOT propagate it.
+fn evaluate_condition(expr: &Expression, context: &RowContext) -> Result<Option<bool>, SQLError> {
+    match expr {
+        // The right operand is only evaluated when it can change the outcome
+        Expression::BinaryOp { left, op, right } if op == "AND" => {
+            let left_result = evaluate_condition(left, context)?;
+            if left_result == Some(false) {
+                return Ok(Some(false));
+            }
+            match evaluate_condition(right, context)? {
+                Some(false) => Ok(Some(false)),
+                Some(true) => Ok(left_result),
+                None => Ok(None),
+            }
+        }
+        Expression::BinaryOp { left, op, right } if op == "OR" => {
+            let left_result = evaluate_condition(left, context)?;
+            if left_result == Some(true) {
+                return Ok(Some(true));
+            }
+            match evaluate_condition(right, context)? {
+                Some(true) => Ok(Some(true)),
+                Some(false) => Ok(left_result),
+                None => Ok(None),
+            }
+        }
+        Expression::BinaryOp { left, op, right } if is_comparison_operator(op) => {
+            let left_value = get_value(left, context)?;
+            let right_value = get_value(right, context)?;
+
+            let Some(ordering) = left_value.compare(&right_value) else {
+                return Ok(None);
+            };
+            match op.as_str() {
+                "=" => Ok(Some(ordering == Ordering::Equal)),
+                "<" => Ok(Some(ordering == Ordering::Less)),
+                ">" => Ok(Some(ordering == Ordering::Greater)),
+                "<=" => Ok(Some(ordering != Ordering::Greater)),
+                ">=" => Ok(Some(ordering != Ordering::Less)),
+                "<>" | "!=" => Ok(Some(ordering != Ordering::Equal)),
+                _ => Err(SQLError::InvalidSyntax(format!(
+                    "Unknown operator '{}'",
+                    op
+                ))),
+            }
+        }
+        Expression::UnaryOp { op, operand } if op == "NOT" => {
+            Ok(evaluate_condition(operand, context)?.map(|result| !result))
+        }
+        Expression::IsNull { operand, negated } => {
+            let value = get_value(operand, context)?;
+            Ok(Some(value.is_null() != *negated))
+        }
+        Expression::InList {
+            operand,
+            list,
+            negated,
+        } => {
+            let value = get_value(operand, context)?;
+            let items = list.iter().map(|item| get_value(item, context));
+            Ok(in_list(&value, items)?.map(|found| found != *negated))
+        }
+        Expression::InSubquery {
+            operand,
+            query,
+            negated,
+        } => {
+            let value = get_value(operand, context)?;
+            let query = correlated(query, context);
+            let items = subquery_values(query, &context.scope.tables_path)?;
+            Ok(in_list(&value, items.into_iter().map(Ok))?.map(|found| found != *negated))
+        }
+        Expression::Exists(query) => {
+            let query = correlated(query, context);
+            Ok(Some(subquery_exists(query, &context.scope.tables_path)?))
+        }
+        Expression::Between {
+            operand,
+            low,
+            high,
+            negated,
+        } => {
+            let value = get_value(operand, context)?;
+            let above_low = value
+                .compare(&get_value(low, context)?)
+                .map(|ordering| ordering != Ordering::Less);
+            let below_high = value
+                .compare(&get_value(high, context)?)
+                .map(|ordering| ordering != Ordering::Greater);
+            // Both bounds must hold, as with AND
+            let between = match (above_low, below_high) {
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            };
+            Ok(between.map(|between| between != *negated))
+        }
+        Expression::Like {
+            operand,
+            pattern,
+            escape,
+            negated,
+            case_insensitive,
+        } => {
+            let value = get_value(operand, context)?;
+            let pattern = get_value(pattern, context)?;
+            if value.is_null() || pattern.is_null() {
+                return Ok(None);
+            }
+            // Values that are not text match by their text, as with ||
+            let matched = like_match(
+                &value.to_string(),
+                &pattern.to_string(),
+                escape.unwrap_or('\\'),
+                *case_insensitive,
+            )?;
+            Ok(Some(matched != *negated))
+        }
+        _ => match *get_value(expr, context)?.inferred() {
+            Value::Boolean(b) => Ok(Some(b)),
+            Value::Null => Ok(None),
+            _ => Err(SQLError::InvalidSyntax(
+                "Unsupported expression in WHERE clause".to_string(),
+            )),
+        },
+    }
+}
+
+fn get_value(expr: &Expression, context: &RowContext) -> Result<Value, SQLError> {
+    match expr {
+        Expression::Literal(val) => Ok(val.clone()),
+        Expression::Column(col_name) => {
+            Ok(context.values[context.scope.resolve(None, col_name)?].clone())
+        }
+        Expression::QualifiedColumn { table, column } => {
+            Ok(context.values[context.scope.resolve(Some(table), column)?].clone())
+        }
+        // Replaced by its value before the subquery it is in runs
+        Expression::OuterColumn { reference, .. } => Err(SQLError::GenericError(format!(
+            "Column '{}' of an enclosing query read outside of its row",
+            reference
+        ))),
+        Expression::Aggregate {
+            function,
+            argument,
+            distinct,
+        } => match context.group {
+            Some(rows) => {
+                evaluate_aggregate(function, argument.as_deref(), *distinct, rows, context)
+            }
+            None => Err(SQLError::InvalidSyntax(format!(
+                "Aggregate function {} is not allowed here",
+                function
+            ))),
+        },
+        Expression::BinaryOp { left, op, right } if is_arithmetic_operator(op) => {
+            get_value(left, context)?.arithmetic(op, &get_value(right, context)?)
+        }
+        Expression::BinaryOp { left, op, right } if op == "||" => {
+            Ok(get_value(left, context)?.concat(&get_value(right, context)?))
+        }
+        Expression::Cast { operand, data_type } => get_value(operand, context)?.cast(*data_type),
+        // Only the branch that applies is evaluated, so that the others may fail
+        // for rows they do not apply to, as with a division by zero
+        Expression::Case {
+            operand,
+            branches,
+            else_result,
+        } => {
+            let operand = match operand {
+                Some(operand) => Some(get_value(operand, context)?),
+                None => None,
+            };
+            for (when, then) in branches {
+                let applies = match &operand {
+                    Some(value) => {
+                        value.compare(&get_value(when, context)?) == Some(Ordering::Equal)
+                    }
+                    None => evaluate_condition(when, context)? == Some(true),
+                };
+                if applies {
+                    return get_value(then, context);
+                }
+            }
+            match else_result {
+                Some(else_result) => get_value(else_result, context),
+                None => Ok(Value::Null),
+            }
+        }
+        // Like CASE, COALESCE and IIF evaluate only the arguments they need
+        Expression::Function { name, arguments } if name == "COALESCE" => {
+            for argument in arguments {
+                let value = get_value(argument, context)?;
+                if !value.is_null() {
+                    return Ok(value);
+                }
+            }
+            Ok(Value::Null)
+        }
+        Expression::Function { name, arguments } if name == "IIF" => {
+            let chosen = if functions::iif_condition(&get_value(&arguments[0], context)?)? {
+                &arguments[1]
+            } else {
+                &arguments[2]
+            };
+            get_value(chosen, context)
+        }
+        Expression::Function { name, arguments } => {
+            let values = arguments
+                .iter()
+                .map(|argument| get_value(argument, context))
+                .collect::<Result<Vec<Value>, SQLError>>()?;
+            functions::call(name, &values)
+        }
+        Expression::Subquery(query) => {
+            scalar_subquery(correlated(query, context), &context.scope.tables_path)
+        }
+        Expression::UnaryOp { op, operand } if op == "-" => get_value(operand, context)?.negate(),
+        // Conditions used as values evaluate to a boolean, or NULL when UNKNOWN
+        Expression::BinaryOp { .. }
+        | Expression::UnaryOp { .. }
+        | Expression::IsNull { .. }
+        | Expression::InList { .. }
+        | Expression::InSubquery { .. }
+        | Expression::Exists(_)
+        | Expression::Between { .. }
+        | Expression::Like { .. } => {
+            Ok(evaluate_condition(expr, context)?.map_or(Value::Null, Value::Boolean))
+        }
+    }
+}
+
+/// Whether `value` equals one of `items`. Without a match, a NULL on either side
+/// makes the result UNKNOWN.
+fn in_list(
+    value: &Value,
+    items: impl Iterator<Item = Result<Value, SQLError>>,
+) -> Result<Option<bool>, SQLError> {
+    let mut found = Some(false);
+    for item in items {
+        match value.compare(&item?) {
+            Some(Ordering::Equal) => return Ok(Some(true)),
+            Some(_) => {}
+            None => found = None,
+        }
+    }
+    Ok(found)
+}
+
+/// Runs the subqueries in `expr` that do not refer to the row they are evaluated
+/// against once, up front, and replaces each with its result: a scalar subquery
+/// with its value, `IN (SELECT ...)` with an IN list of its values and EXISTS
+/// with TRUE or FALSE. Correlated subqueries are left to run for every row, with
+/// their references to it resolved here.
+fn bind_subqueries(expr: &mut Expression, scope: &Scope) -> Result<(), SQLError> {
+    let uncorrelated = match expr {
+        Expression::Subquery(query)
+        | Expression::Exists(query)
+        | Expression::InSubquery { query, .. } => !correlate(query, &mut Vec::new(), scope)?,
+        _ => false,
+    };
+    if uncorrelated {
+        let tables_path = &scope.tables_path;
+        *expr = match std::mem::replace(expr, Expression::Literal(Value::Null)) {
+            Expression::Subquery(query) => {
+                Expression::Literal(scalar_subquery(*query, tables_path)?)
+            }
+            Expression::Exists(query) => {
+                Expression::Literal(Value::Boolean(subquery_exists(*query, tables_path)?))
+            }
+            Expression::InSubquery {
+                operand,
+                query,
+                negated,
+            } => Expression::InList {
+                operand,
+                list: subquery_values(*query, tables_path)?
+                    .into_iter()
+                    .map(Expression::Literal)
+                    .collect(),
+                negated,
+            },
+            other => other,
+        };
+    }
+    expr.children_mut()
+        .into_iter()
+        .try_for_each(|child| bind_subqueries(child, scope))
+}
+
+/// A correlated subquery with its references to the row it is evaluated against
+/// replaced by their values, ready to run on its own.
+fn correlated(query: &SelectQuery, context: &RowContext) -> SelectQuery {
+    let mut query = query.clone();
+    for expr in query.expressions_mut() {
+        substitute_outer_columns(expr, context.values);
+    }
+    query
+}
+
+/// Replaces every `OuterColumn` in `expr`, and in its subqueries, by its value in `row`.
+fn substitute_outer_columns(expr: &mut Expression, row: &[Value]) {
+    match expr {
+        Expression::OuterColumn { index, .. } => {
+            *expr = Expression::Literal(row[*index].clone());
+            return;
+        }
+        Expression::Subquery(query)
+        | Expression::Exists(query)
+        | Expression::InSubquery { query, .. } => {
+            for expr in query.expressions_mut() {
+                substitute_outer_columns(expr, row);
+            }
+        }
+        _ => {}
+    }
+    for child in expr.children_mut() {
+        substitute_outer_columns(child, row);
+    }
+}
+
+/// Resolves the column references in `query`, and in the subqueries nested in
+/// it, against the FROM clause of each and of the `inner` queries they are
+/// nested in. Those that are to none of them but to a column of the `outer`
+/// query are replaced by an `OuterColumn`; any other is an error. Returns
+/// whether there were references to `outer`.
+fn correlate(
+    query: &mut SelectQuery,
+    inner: &mut Vec<Scope>,
+    outer: &Scope,
+) -> Result<bool, SQLError> {
+    let scope = from_scope(query, &outer.tables_path)?;
+    let columns = output_columns(query, &scope);
+    let names_output = |expr: &Expression| match expr {
+        Expression::Column(name) => {
+            columns.iter().filter(|column| column.name == *name).count() == 1
+        }
+        _ => false,
+    };
+    // The ORDER BY and DISTINCT ON keys are resolved apart, as those that name
+    // an output column are read from the query's output instead
+    let mut order_by = std::mem::take(&mut query.order_by);
+    let mut distinct = std::mem::replace(&mut query.distinct, Distinct::All);
+    inner.push(scope);
+    let mut correlated = false;
+    for expr in query.expressions_mut() {
+        correlated |= correlate_expression(expr, inner, outer)?;
+    }
+    let keys = order_by.iter_mut().map(|key| &mut key.expression);
+    let keys = keys.chain(match distinct {
+        Distinct::On(ref mut on) => on.iter_mut().collect(),
+        _ => Vec::new(),
+    });
+    for expr in keys.filter(|expr| !names_output(expr)) {
+        correlated |= correlate_expression(expr, inner, outer)?;
+    }
+    inner.pop();
+    query.order_by = order_by;
+    query.distinct = distinct;
+    Ok(correlated)
+}
+
+fn correlate_expression(
+    expr: &mut Expression,
+    inner: &mut Vec<Scope>,
+    outer: &Scope,
+) -> Result<bool, SQLError> {
+    let reference = match expr {
+        Expression::Column(name) => Some((None, name.clone())),
+        Expression::QualifiedColumn { table, column } => {
+            Some((Some(table.clone()), column.clone()))
+        }
+        _ => None,
+    };
+    if let Some((table, name)) = reference {
+        let table = table.as_deref();
+        // The innermost query with such a column is the one it belongs to
+        if let Some(scope) = inner
+            .iter()
+            .rev()
+            .find(|scope| scope.contains(table, &name))
+        {
+            scope.resolve(table, &name)?;
+            return Ok(false);
+        }
+        if !outer.contains(table, &name) {
+            // Reported as missing from the FROM clause of the query it is in
+            return Err(inner[inner.len() - 1].resolve(table, &name).unwrap_err());
+        }
+        let index = outer.resolve(table, &name)?;
+        let reference = Box::new(std::mem::replace(expr, Expression::Literal(Value::Null)));
+        *expr = Expression::OuterColumn { reference, index };
+        return Ok(true);
+    }
+
+    let mut correlated = false;
+    if let Expression::Subquery(query)
+    | Expression::Exists(query)
+    | Expression::InSubquery { query, .. } = expr
+    {
+        correlated |= correlate(query, inner, outer)?;
+    }
+    for child in expr.children_mut() {
+        correlated |= correlate_expression(child, inner, outer)?;
+    }
+    Ok(correlated)
+}
+
+/// The columns the FROM clause of a query, with its joins, makes visible.
+fn from_scope(select_query: &SelectQuery, tables_path: &str) -> Result<Scope, SQLError> {
+    let label = table_label(&select_query.table, &select_query.alias);
+    let mut scope = match select_query.subquery {
+        Some(ref subquery) => {
+            let (columns, _) = select_stream(subquery.as_ref().clone(), tables_path)?;
+            Scope::from_columns(tables_path, label, &columns)
+        }
+        None => {
+            let table_file = format!("{}/{}.csv", tables_path, select_query.table);
+            Scope::from_table(tables_path, label, &read_table_schema(&table_file)?)
+        }
+    };
+    for join in &select_query.joins {
+        let join_file = format!("{}/{}.csv", tables_path, join.table);
+        let label = table_label(&join.table, &join.alias);
+        scope = scope.join(&Scope::from_table(
+            tables_path,
+            label,
+            &read_table_schema(&join_file)?,
+        ));
+    }
+    Ok(scope)
+}
+
+/// Runs a subquery, checking that it returns a single column, and returns the
+/// values of that column.
+fn subquery_column(
+    query: SelectQuery,
+    tables_path: &str,
+) -> Result<impl Iterator<Item = Result<Value, SQLError>>, SQLError> {
+    let (columns, rows) = select_stream(query, tables_path)?;
+    if columns.len() != 1 {
+        return Err(SQLError::InvalidSyntax(format!(
+            "Subquery must return one column, but returns {}",
+            columns.len()
+        )));
+    }
+    Ok(rows.map(|row| row.map(|mut values| values.swap_remove(0))))
+}
+
+fn subquery_values(query: SelectQuery, tables_path: &str) -> Result<Vec<Value>, SQLError> {
+    subquery_column(query, tables_path)?.collect()
+}
+
+/// The value of a subquery used as a value, NULL when it returns no rows.
+fn scalar_subquery(query: SelectQuery, tables_path: &str) -> Result<Value, SQLError> {
+    let mut values = subquery_column(query, tables_path)?;
+    let value = values.next().transpose()?.unwrap_or(Value::Null);
+    if values.next().transpose()?.is_some() {
+        return Err(SQLError::GenericError(
+            "Subquery used as a value returned more than one row".to_string(),
+        ));
+    }
+    Ok(value)
+}
+
+/// Whether a subquery returns any row, reading no further than the first.
+fn subquery_exists(query: SelectQuery, tables_path: &str) -> Result<bool, SQLError> {
+    let (_, mut rows) = select_stream(query, tables_path)?;
+    Ok(rows.next().transpose()?.is_some())
+}
+
+/// Computes an aggregate over the rows of a group. NULL arguments are ignored,
+/// and every function but COUNT returns NULL when there is nothing to aggregate.
+fn evaluate_aggregate(
+    function: &str,
+    argument: Option<&Expression>,
+    distinct: bool,
+    rows: &[Vec<Value>],
+    context: &RowContext,
+) -> Result<Value, SQLError> {
+    let Some(argument) = argument else {
+        // COUNT(*) counts rows, including those that are entirely NULL
+        return Ok(Value::Integer(rows.len() as i64));
+    };
+
+    let mut values = Vec::new();
+    let mut seen = HashSet::new();
+    for row in rows {
+        let value = get_value(argument, &RowContext::new(context.scope, row))?;
+        if value.is_null() || (distinct && !seen.insert(value.group_key())) {
+            continue;
+        }
+        values.push(value);
+    }
+
+    match function {
+        "COUNT" => Ok(Value::Integer(values.len() as i64)),
+        "SUM" => sum_values(function, &values),
+        "AVG" => match sum_values(function, &values)? {
+            Value::Integer(total) => Ok(Value::Float(total as f64 / values.len() as f64)),
+            Value::Float(total) => Ok(Value::Float(total / values.len() as f64)),
+            other => Ok(other),
+        },
+        "MIN" => Ok(values
+            .into_iter()
+            .min_by(|a, b| a.sort_cmp(b))
+            .unwrap_or(Value::Null)),
+        "MAX" => Ok(values
+            .into_iter()
+            .max_by(|a, b| a.sort_cmp(b))
+            .unwrap_or(Value::Null)),
+        _ => Err(SQLError::InvalidSyntax(format!(
+            "Unknown aggregate function '{}'",
+            function
+        ))),
+    }
+}
+
+/// Adds up numeric values, staying in integers until a float shows up. An
+/// integer total that overflows is an error, as with `+`.
+fn sum_values(function: &str, values: &[Value]) -> Result<Value, SQLError> {
+    if values.is_empty() {
+        return Ok(Value::Null);
+    }
+    let mut total = Value::Integer(0);
+    for value in values {
+        total = match (&total, value.inferred().as_ref()) {
+            (Value::Integer(a), Value::Integer(b)) => match a.checked_add(*b) {
+                Some(sum) => Value::Integer(sum),
+                None => {
+                    return Err(SQLError::GenericError(format!(
+                        "Integer overflow in {}",
+                        function
+                    )))
+                }
+            },
+            (Value::Integer(a), Value::Float(b)) => Value::Float(*a as f64 + b),
+            (Value::Float(a), Value::Integer(b)) => Value::Float(a + *b as f64),
+            (Value::Float(a), Value::Float(b)) => Value::Float(a + b),
+            _ => {
+                return Err(SQLError::TypeMismatch(format!(
+                    "{} expects numeric values, found '{}'",
+                    function, value
+                )))
+            }
+        };
+    }
+    Ok(total)
+}
+
+/// How to sort and deduplicate the output rows. Keys are positions in an output
+/// row; ORDER BY and DISTINCT ON expressions that are not in the select list are
+/// `hidden` expressions, evaluated into extra values after the selected ones.
+struct OutputPlan {
+    sort_keys: Vec<SortKey>,
+    /// Positions whose values identify duplicate rows, when only distinct rows
+    /// are returned
+    distinct_keys: Option<Vec<usize>>,
+    hidden: Vec<Expression>,
+}
+
+struct SortKey {
+    /// Position of the sorted value in an output row
+    position: usize,
+    ascending: bool,
+    nulls_first: bool,
+}
+
+impl OutputPlan {
+    /// Resolves a key to an output column: an integer literal is an output
+    /// position, an expression in the select list or a name heading a single
+    /// output column refers to that column, and anything else becomes hidden.
+    fn key_position(
+        &mut self,
+        expr: &Expression,
+        select_query: &SelectQuery,
+        columns: &[Column],
+        scope: &Scope,
+        clause: &str,
+    ) -> Result<usize, SQLError> {
+        if let Expression::Literal(Value::Integer(ordinal)) = expr {
+            if *ordinal < 1 || *ordinal as usize > columns.len() {
+                return Err(SQLError::InvalidColumn(format!(
+                    "{} position {} is not in the select list",
+                    clause, ordinal
+                )));
+            }
+            return Ok(*ordinal as usize - 1);
+        }
+        if let Some(position) = output_position(select_query, columns, scope, expr) {
+            return Ok(position);
+        }
+        if let Some(position) = self.hidden.iter().position(|hidden| hidden == expr) {
+            return Ok(columns.len() + position);
+        }
+        check_columns(expr, scope)?;
+        self.hidden.push(expr.clone());
+        Ok(columns.len() + self.hidden.len() - 1)
+    }
+}
+
+fn plan_output(
+    select_query: &SelectQuery,
+    columns: &[Column],
+    scope: &Scope,
+) -> Result<OutputPlan, SQLError> {
+    let mut plan = OutputPlan {
+        sort_keys: Vec::new(),
+        distinct_keys: None,
+        hidden: Vec::new(),
+    };
+    for order_by in &select_query.order_by {
+        let position = plan.key_position(
+            &order_by.expression,
+            select_query,
+            columns,
+            scope,
+            "ORDER BY",
+        )?;
+        plan.sort_keys.push(SortKey {
+            position,
+            ascending: order_by.ascending,
+            nulls_first: order_by.nulls_first,
+        });
+    }
+
+    match &select_query.distinct {
+        Distinct::All => {}
+        Distinct::Rows => {
+            // Rows that look the same could differ in a hidden value, which makes
+            // the order of the deduplicated rows meaningless
+            if !plan.hidden.is_empty() {
+                return Err(SQLError::InvalidSyntax(
+                    "For SELECT DISTINCT, ORDER BY expressions must appear in the select list"
+                        .to_string(),
+                ));
+            }
+            plan.distinct_keys = Some((0..columns.len()).collect());
+        }
+        Distinct::On(expressions) => {
+            let mut keys = Vec::new();
+            for expr in expressions {
+                keys.push(plan.key_position(expr, select_query, columns, scope, "DISTINCT ON")?);
+            }
+            // The row kept for each key is the first in ORDER BY order, which is
+            // only well defined when the rows are sorted by the key first
+            let mut leading = plan.sort_keys.iter().take(keys.len());
+            if leading.any(|key| !keys.contains(&key.position)) {
+                return Err(SQLError::InvalidSyntax(
+                    "SELECT DISTINCT ON expressions must match the leading ORDER BY expressions"
+                        .to_string(),
+                ));
+            }
+            plan.distinct_keys = Some(keys);
+        }
+    }
+
+    Ok(plan)
+}
+
+fn output_position(
+    select_query: &SelectQuery,
+    columns: &[Column],
+    scope: &Scope,
+    expr: &Expression,
+) -> Option<usize> {
+    let mut position = 0;
+    for item in &select_query.columns {
+        match item {
+            SelectItem::Wildcard => position += scope.columns.len(),
+            SelectItem::QualifiedWildcard(table) => position += scope.table_columns(table).count(),
+            SelectItem::Expression {
+                expr: item_expr, ..
+            } if item_expr == expr => return Some(position),
+            SelectItem::Expression { .. } => position += 1,
+        }
+    }
+    if let Expression::Column(name) = expr {
+        let mut matches = columns
+            .iter()
+            .enumerate()
+            .filter(|(_, column)| column.name == *name);
+        if let (Some((position, _)), None) = (matches.next(), matches.next()) {
+            return Some(position);
+        }
+    }
+    None
+}
+
+/// Sorts rows by each key in turn, keeping rows that compare equal in their
+/// original order.
+fn sort_results(results: &mut [Vec<Value>], keys: &[SortKey]) {
+    if keys.is_empty() {
+        return;
+    }
+    results.sort_by(|a, b| {
+        keys.iter()
+            .map(|key| compare_sort_values(&a[key.position], &b[key.position], key))
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+fn compare_sort_values(a: &Value, b: &Value, key: &SortKey) -> Ordering {
+    match (a.is_null(), b.is_null()) {
+        (true, true) => Ordering::Equal,
+        (true, false) if key.nulls_first => Ordering::Less,
+        (true, false) => Ordering::Greater,
+        (false, true) if key.nulls_first => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) if key.ascending => a.sort_cmp(b),
+        (false, false) => b.sort_cmp(a),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, tokenize};
+
+    fn ordenes_schema() -> TableSchema {
+        TableSchema::from_header(&["id", "client_id", "product", "quantity"])
+    }
+
+    fn ordenes_scope() -> Scope {
+        Scope::from_table("tables", "ordenes", &ordenes_schema())
+    }
+
+    fn where_clause(sql: &str) -> Expression {
+        let tokens = tokenize(sql).unwrap();
+        match parse(&tokens).unwrap() {
+            SQLQuery::Select(select_query) => select_query.where_clause.unwrap(),
+            _ => panic!("Expected SELECT query"),
+        }
+    }
+
+    /// Creates a scratch tables directory holding the given `(file name, contents)` pairs.
+    fn tables_dir(name: &str, files: &[(&str, &str)]) -> String {
+        let dir = std::env::temp_dir().join(format!("rustic_sql_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file_name, contents) in files {
+            std::fs::write(dir.join(file_name), contents).unwrap();
+        }
+        dir.to_string_lossy().into_owned()
+    }
+
+    /// Runs a statement, reading every row of a query.
+    fn run(sql: &str, tables_path: &str) -> Result<(), SQLError> {
+        let tokens = tokenize(sql)?;
+        execute_query(parse(&tokens)?, tables_path)?.try_for_each(|row| row.map(drop))
+    }
+
+    /// Runs a query, returning its column names and its values as text.
+    fn select_from(
+        sql: &str,
+        tables_path: &str,
+    ) -> Result<(Vec<String>, Vec<Vec<String>>), SQLError> {
+        let (columns, rows) = select_rows(select_query(sql), tables_path)?;
+        let headers = columns.into_iter().map(|column| column.name).collect();
+        let rows = rows
+            .iter()
+            .map(|row| row.iter().map(|value| value.to_string()).collect())
+            .collect();
+        Ok((headers, rows))
+    }
+
+    fn row(values: &[&str]) -> Vec<Value> {
+        let fields: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        ordenes_schema().parse_row(&fields).unwrap()
+    }
+
+    #[test]
+    fn test_evaluate_boolean_where_clause() {
+        let scope = ordenes_scope();
+        let expr = where_clause(
+            "SELECT * FROM ordenes WHERE (quantity >= 2 AND product = 'Mouse') OR NOT client_id = 6;",
+        );
+
+        let mouse = row(&["105", "4", "Mouse", "2"]);
+        let laptop = row(&["111", "6", "Laptop", "3"]);
+        let phone = row(&["102", "2", "Teléfono", "2"]);
+        assert!(evaluate_where_clause(&expr, &RowContext::new(&scope, &mouse)).unwrap());
+        assert!(!evaluate_where_clause(&expr, &RowContext::new(&scope, &laptop)).unwrap());
+        assert!(evaluate_where_clause(&expr, &RowContext::new(&scope, &phone)).unwrap());
+    }
+
+    #[test]
+    fn test_evaluate_short_circuits() {
+        let scope = ordenes_scope();
+        // The right-hand side references a missing column and would fail if evaluated
+        let expr = where_clause("SELECT * FROM ordenes WHERE product = 'Mouse' OR missing = 1;");
+        let mouse = row(&["105", "4", "Mouse", "2"]);
+        assert!(evaluate_where_clause(&expr, &RowContext::new(&scope, &mouse)).unwrap());
+
+        let laptop = row(&["111", "6", "Laptop", "3"]);
+        assert!(evaluate_where_clause(&expr, &RowContext::new(&scope, &laptop)).is_err());
+    }
+
+    #[test]
+    fn test_evaluate_compares_numbers_numerically() {
+        let scope = ordenes_scope();
+        let row = row(&["111", "6", "Laptop", "9"]);
+        let expr = where_clause("SELECT * FROM ordenes WHERE quantity < 10;");
+        assert!(evaluate_where_clause(&expr, &RowContext::new(&scope, &row)).unwrap());
+        let expr = where_clause("SELECT * FROM ordenes WHERE quantity > '10';");
+        assert!(!evaluate_where_clause(&expr, &RowContext::new(&scope, &row)).unwrap());
+    }
+
+    #[test]
+    fn test_sort_results_numerically() {
+        let mut results = vec![
+            vec![Value::Integer(10)],
+            vec![Value::Integer(9)],
+            vec![Value::Integer(100)],
+        ];
+        let key = SortKey {
+            position: 0,
+            ascending: true,
+            nulls_first: false,
+        };
+        sort_results(&mut results, &[key]);
+        assert_eq!(
+            results,
+            vec![
+                vec![Value::Integer(9)],
+                vec![Value::Integer(10)],
+                vec![Value::Integer(100)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_insert_and_update_validate_declared_types() {
+        let dir = tables_dir(
+            "typed_insert",
+            &[
+                ("ordenes.csv", "id,client_id,product,quantity\n"),
+                (
+                    "ordenes.schema",
+                    "id INTEGER NOT NULL\nclient_id INTEGER\nproduct TEXT NOT NULL\nquantity INTEGER DEFAULT 1\n",
+                ),
+            ],
+        );
+
+        run(
+            "INSERT INTO ordenes (id, product) VALUES (1, 'Mouse');",
+            &dir,
+        )
+        .unwrap();
+        let contents = std::fs::read_to_string(format!("{}/ordenes.csv", dir)).unwrap();
+        assert_eq!(contents, "id,client_id,product,quantity\n1,,Mouse,1\n");
+
+        let result = run("INSERT INTO ordenes (id, quantity) VALUES ('x', 2);", &dir);
+        assert!(matches!(result, Err(SQLError::TypeMismatch(_))));
+        let result = run("INSERT INTO ordenes (id, quantity) VALUES (2, 2);", &dir);
+        assert!(matches!(result, Err(SQLError::TypeMismatch(_))));
+        let result = run("UPDATE ordenes SET quantity = 'many' WHERE id = 1;", &dir);
+        assert!(matches!(result, Err(SQLError::TypeMismatch(_))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_dml_preserves_quoted_fields() {
+        let dir = tables_dir(
+            "quoted_fields",
+            &[(
+                "clientes.csv",
+                "id,name,email\r\n1,\"Pérez, Juan\",juan@email.com\r\n2,\"Ana \"\"La Jefa\"\"\",\"ana\nlopez\"\r\n",
+            )],
+        );
+
+        run(
+            "INSERT INTO clientes VALUES (3, 'Gómez, Carlos', 'a,b');",
+            &dir,
+        )
+        .unwrap();
+        run("UPDATE clientes SET email = 'x' WHERE id = 1;", &dir).unwrap();
+        run("DELETE FROM clientes WHERE name = 'Gómez, Carlos';", &dir).unwrap();
+
+        let contents = std::fs::read_to_string(format!("{}/clientes.csv", dir)).unwrap();
+        assert_eq!(
+            contents,
+            "id,name,email\n1,\"Pérez, Juan\",x\n2,\"Ana \"\"La Jefa\"\"\",\"ana\nlopez\"\n"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_untyped_fields_round_trip() {
+        let dir = tables_dir(
+            "untyped_fields",
+            &[
+                (
+                    "t.csv",
+                    "id,code,price,active\n1,007,1.50,TRUE\n2,01234,2.0,false\n3,10,0.5,True\n",
+                ),
+                ("u.csv", "id,code,price,active\n"),
+            ],
+        );
+
+        // Fields read exactly as written, and compare and sort as what they read as
+        let (_, rows) =
+            select_from("SELECT * FROM t WHERE active ORDER BY code DESC;", &dir).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec!["3", "10", "0.5", "True"],
+                vec!["1", "007", "1.50", "TRUE"]
+            ]
+        );
+        let (_, rows) = select_from(
+            "SELECT id FROM t WHERE code = 7 OR price = '2' ORDER BY price;",
+            &dir,
+        )
+        .unwrap();
+        assert_eq!(rows, vec![vec!["1"], vec!["2"]]);
+        let (_, rows) = select_from("SELECT MAX(code), SUM(price) FROM t;", &dir).unwrap();
+        assert_eq!(rows, vec![vec!["01234", "4.0"]]);
+
+        run("INSERT INTO u SELECT * FROM t;", &dir).unwrap();
+        run("UPDATE t SET id = id + 10;", &dir).unwrap();
+        let copied = std::fs::read_to_string(format!("{}/u.csv", dir)).unwrap();
+        assert_eq!(
+            copied,
+            "id,code,price,active\n1,007,1.50,TRUE\n2,01234,2.0,false\n3,10,0.5,True\n"
+        );
+        let updated = std::fs::read_to_string(format!("{}/t.csv", dir)).unwrap();
+        assert_eq!(
+            updated,
+            "id,code,price,active\n11,007,1.50,TRUE\n12,01234,2.0,false\n13,10,0.5,True\n"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_evaluate_null_three_valued_logic() {
+        let scope = ordenes_scope();
+        let row = row(&["111", "6", "", "3"]);
+        let context = RowContext::new(&scope, &row);
+        let matches = |sql: &str| evaluate_where_clause(&where_clause(sql), &context).unwrap();
+
+        assert!(matches("SELECT * FROM t WHERE product IS NULL;"));
+        assert!(!matches("SELECT * FROM t WHERE product IS NOT NULL;"));
+        // Comparisons with NULL are UNKNOWN, and so is their negation
+        assert!(!matches("SELECT * FROM t WHERE product = 'Mouse';"));
+        assert!(!matches("SELECT * FROM t WHERE NOT product = 'Mouse';"));
+        assert!(!matches("SELECT * FROM t WHERE quantity = NULL;"));
+        // UNKNOWN OR TRUE is TRUE, UNKNOWN AND FALSE is FALSE
+        assert!(matches(
+            "SELECT * FROM t WHERE product = 'Mouse' OR quantity = 3;"
+        ));
+        assert!(matches(
+            "SELECT * FROM t WHERE NOT (product = 'Mouse' AND quantity = 4);"
+        ));
+    }
+
+    #[test]
+    fn test_evaluate_in_between_and_like() {
+        let scope = ordenes_scope();
+        let row = row(&["111", "6", "Mouse_Pad", ""]);
+        let context = RowContext::new(&scope, &row);
+        let matches = |condition: &str| {
+            let sql = format!("SELECT * FROM t WHERE {};", condition);
+            evaluate_where_clause(&where_clause(&sql), &context).unwrap()
+        };
+
+        assert!(matches("id IN (110, 111.0)"));
+        assert!(matches("id NOT IN (1, 2)"));
+        assert!(matches("client_id BETWEEN 6 AND 10"));
+        assert!(!matches("client_id NOT BETWEEN '1' AND 6"));
+        assert!(matches("product LIKE 'M%'"));
+        assert!(!matches("product LIKE 'm%'"));
+        assert!(matches("product ILIKE 'm%PAD'"));
+        assert!(matches("product LIKE 'Mouse\\_%'"));
+        assert!(!matches("product LIKE '%#%' ESCAPE '#'"));
+        assert!(matches("id LIKE '1_1'"));
+
+        // A NULL without a match makes IN, BETWEEN and LIKE UNKNOWN, and so their
+        // negations too
+        assert!(!matches("id IN (1, NULL)"));
+        assert!(!matches("id NOT IN (1, NULL)"));
+        assert!(matches("id IN (111, NULL)"));
+        assert!(!matches("quantity NOT BETWEEN 1 AND 2"));
+        assert!(!matches("client_id BETWEEN 1 AND NULL"));
+        assert!(!matches("client_id NOT BETWEEN 1 AND NULL"));
+        assert!(matches("client_id NOT BETWEEN 7 AND NULL"));
+        assert!(!matches("quantity NOT LIKE '%'"));
+    }
+
+    #[test]
+    fn test_sort_results_nulls_first_and_last() {
+        let mut key = SortKey {
+            position: 0,
+            ascending: true,
+            nulls_first: true,
+        };
+        let mut results = vec![
+            vec![Value::Integer(2)],
+            vec![Value::Null],
+            vec![Value::Integer(1)],
+        ];
+        sort_results(&mut results, std::slice::from_ref(&key));
+        assert_eq!(
+            results,
+            vec![
+                vec![Value::Null],
+                vec![Value::Integer(1)],
+                vec![Value::Integer(2)],
+            ]
+        );
+
+        key.ascending = false;
+        key.nulls_first = false;
+        sort_results(&mut results, &[key]);
+        assert_eq!(
+            results,
+            vec![
+                vec![Value::Integer(2)],
+                vec![Value::Integer(1)],
+                vec![Value::Null],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_insert_null() {
+        let dir = tables_dir(
+            "insert_null",
+            &[
+                ("clientes.csv", "id,name,email\n"),
+                (
+                    "clientes.schema",
+                    "id INTEGER NOT NULL\n@null_marker 'NULL'\n",
+                ),
+            ],
+        );
+
+        run("INSERT INTO clientes VALUES (1, '', NULL);", &dir).unwrap();
+        let result = run("INSERT INTO clientes (id) VALUES (NULL);", &dir);
+        assert!(matches!(result, Err(SQLError::TypeMismatch(_))));
+
+        let contents = std::fs::read_to_string(format!("{}/clientes.csv", dir)).unwrap();
+        assert_eq!(contents, "id,name,email\n1,,NULL\n");
+        // The empty name reads back as empty text, not NULL
+        let (_, rows) = select_from(
+            "SELECT id, name IS NULL, email IS NULL FROM clientes;",
+            &dir,
+        )
+        .unwrap();
+        assert_eq!(rows, vec![vec!["1", "false", "true"]]);
+
+        // Where empty fields are NULL, empty text cannot be stored
+        std::fs::write(format!("{}/notas.csv", dir), "id,text\n").unwrap();
+        let result = run("INSERT INTO notas VALUES (2, '');", &dir);
+        assert!(matches!(result, Err(SQLError::TypeMismatch(_))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn select_query(sql: &str) -> SelectQuery {
+        let tokens = tokenize(sql).unwrap();
+        match parse(&tokens).unwrap() {
+            SQLQuery::Select(select_query) => select_query,
+            _ => panic!("Expected SELECT query"),
+        }
+    }
+
+    fn ordenes_rows() -> Vec<Vec<Value>> {
+        vec![
+            row(&["102", "2", "Teléfono", "2"]),
+            row(&["105", "4", "Mouse", "2"]),
+            row(&["110", "6", "Teléfono", "2"]),
+            row(&["111", "6", "Laptop", "3"]),
+            row(&["112", "6", "Mouse", ""]),
+        ]
+    }
+
+    #[test]
+    fn test_group_rows_aggregates_per_group() {
+        let scope = ordenes_scope();
+        let query = select_query(
+            "SELECT product, SUM(quantity), COUNT(*), COUNT(quantity), AVG(quantity), \
+             MAX(client_id), COUNT(DISTINCT client_id) FROM ordenes GROUP BY product;",
+        );
+        let results = group_rows(ordenes_rows(), &scope, &query, &[]).unwrap();
+        assert_eq!(
+            results,
+            vec![
+                vec![
+                    Value::Text("Teléfono".to_string()),
+                    Value::Integer(4),
+                    Value::Integer(2),
+                    Value::Integer(2),
+                    Value::Float(2.0),
+                    Value::Text("6".to_string()),
+                    Value::Integer(2),
+                ],
+                vec![
+                    Value::Text("Mouse".to_string()),
+                    Value::Integer(2),
+                    Value::Integer(2),
+                    Value::Integer(1),
+                    Value::Float(2.0),
+                    Value::Text("6".to_string()),
+                    Value::Integer(2),
+                ],
+                vec![
+                    Value::Text("Laptop".to_string()),
+                    Value::Integer(3),
+                    Value::Integer(1),
+                    Value::Integer(1),
+                    Value::Float(3.0),
+                    Value::Text("6".to_string()),
+                    Value::Integer(1),
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_group_rows_having_and_empty_input() {
+        let scope = ordenes_scope();
+        let query = select_query(
+            "SELECT client_id, COUNT(*) FROM ordenes GROUP BY client_id HAVING COUNT(*) > 1;",
+        );
+        let results = group_rows(ordenes_rows(), &scope, &query, &[]).unwrap();
+        assert_eq!(
+            results,
+            vec![vec![Value::Text("6".to_string()), Value::Integer(3)]]
+        );
+
+        // Aggregating without GROUP BY yields a single row even with no input
+        let query = select_query("SELECT COUNT(*), SUM(quantity) FROM ordenes;");
+        let results = group_rows(Vec::new(), &scope, &query, &[]).unwrap();
+        assert_eq!(results, vec![vec![Value::Integer(0), Value::Null]]);
+
+        // An integer total that does not fit fails rather than turning into a float
+        let big = || row(&["1", "2", "Mouse", "9223372036854775807"]);
+        let result = group_rows(vec![big(), big()], &scope, &query, &[]);
+        assert!(matches!(
+            result,
+            Err(SQLError::GenericError(message)) if message == "Integer overflow in SUM"
+        ));
+    }
+
+    #[test]
+    fn test_group_rows_rejects_ungrouped_columns() {
+        let scope = ordenes_scope();
+        let query = select_query("SELECT product, id FROM ordenes GROUP BY product;");
+        assert!(matches!(
+            check_grouped_query(&query, &scope, &[]),
+            Err(SQLError::InvalidColumn(_))
+        ));
+        let query = select_query("SELECT SUM(product) FROM ordenes;");
+        assert!(matches!(
+            group_rows(ordenes_rows(), &scope, &query, &[]),
+            Err(SQLError::TypeMismatch(_))
+        ));
+    }
+
+    fn join_results(sql: &str, right_rows: Vec<Vec<Value>>) -> Vec<Vec<Value>> {
+        let query = select_query(sql);
+        let clientes = TableSchema::from_header(&["id", "name"]);
+        let plan = JoinPlan {
+            kind: query.joins[0].kind,
+            condition: query.joins[0].condition.clone(),
+            left_scope: Scope::from_table("tables", "o", &ordenes_schema()),
+            right_scope: Scope::from_table("tables", "c", &clientes),
+        };
+        let left_rows = Box::new(ordenes_rows().into_iter().map(Ok));
+        let join = HashJoin::new(plan, left_rows, right_rows).unwrap();
+        join.collect::<Result<_, _>>().unwrap()
+    }
+
+    fn clientes_rows() -> Vec<Vec<Value>> {
+        vec![
+            vec![Value::Integer(2), Value::Text("Ana".to_string())],
+            vec![Value::Integer(3), Value::Text("Carlos".to_string())],
+            // Text keys still match numbers that they compare equal to
+            vec![
+                Value::Text("6".to_string()),
+                Value::Text("Laura".to_string()),
+            ],
+        ]
+    }
+
+    /// Pairs of (order id, client name) from joined rows.
+    fn join_pairs(rows: &[Vec<Value>]) -> Vec<(String, String)> {
+        rows.iter()
+            .map(|row| (row[0].to_string(), row[5].to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_join_kinds() {
+        let pair = |id: &str, name: &str| (id.to_string(), name.to_string());
+        let matched = vec![
+            pair("102", "Ana"),
+            pair("110", "Laura"),
+            pair("111", "Laura"),
+            pair("112", "Laura"),
+        ];
+
+        let sql = "SELECT * FROM ordenes o {} JOIN clientes c ON o.client_id = c.id;";
+        let inner = join_results(&sql.replace("{}", "INNER"), clientes_rows());
+        assert_eq!(join_pairs(&inner), matched);
+
+        let left = join_results(&sql.replace("{}", "LEFT"), clientes_rows());
+        let mut expected = matched.clone();
+        expected.insert(1, pair("105", ""));
+        assert_eq!(join_pairs(&left), expected);
+        assert!(left[1][4..].iter().all(Value::is_null));
+
+        let right = join_results(&sql.replace("{}", "RIGHT"), clientes_rows());
+        let mut expected = matched.clone();
+        expected.push(pair("", "Carlos"));
+        assert_eq!(join_pairs(&right), expected);
+
+        let full = join_results(&sql.replace("{}", "FULL OUTER"), clientes_rows());
+        assert_eq!(full.len(), 6);
+    }
+
+    #[test]
+    fn test_join_without_equality_uses_whole_condition() {
+        let rows = join_results(
+            "SELECT * FROM ordenes o JOIN clientes c ON c.id = 3 AND o.quantity > 2;",
+            clientes_rows(),
+        );
+        assert_eq!(
+            join_pairs(&rows),
+            vec![("111".to_string(), "Carlos".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_scope_resolves_qualified_columns() {
+        let scope = ordenes_scope().join(&Scope::from_table(
+            "tables",
+            "c",
+            &TableSchema::from_header(&["id", "name"]),
+        ));
+        assert_eq!(scope.resolve(None, "name").unwrap(), 5);
+        assert_eq!(scope.resolve(Some("ordenes"), "id").unwrap(), 0);
+        assert_eq!(scope.resolve(Some("c"), "id").unwrap(), 4);
+        assert!(matches!(
+            scope.resolve(None, "id"),
+            Err(SQLError::InvalidColumn(_))
+        ));
+        assert!(matches!(
+            scope.resolve(Some("x"), "id"),
+            Err(SQLError::InvalidTable(_))
+        ));
+    }
+
+    #[test]
+    fn test_join_validates_tables() {
+        let dir = tables_dir(
+            "join_tables",
+            &[
+                ("ordenes.csv", "id,client_id\n1,2\n"),
+                ("clientes.csv", "id,name\n2,Ana\n"),
+            ],
+        );
+
+        assert!(run(
+            "SELECT o.id, name FROM ordenes o JOIN clientes c ON o.client_id = c.id;",
+            &dir
+        )
+        .is_ok());
+        assert!(matches!(
+            run("SELECT * FROM ordenes JOIN ordenes ON id = id;", &dir),
+            Err(SQLError::InvalidTable(_))
+        ));
+        assert!(matches!(
+            run(
+                "SELECT x.* FROM ordenes o JOIN clientes c ON o.client_id = c.id;",
+                &dir
+            ),
+            Err(SQLError::InvalidTable(_))
+        ));
+        assert!(matches!(
+            run(
+                "SELECT id FROM ordenes o JOIN clientes c ON o.client_id = c.id;",
+                &dir
+            ),
+            Err(SQLError::InvalidColumn(_))
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_limit_stops_reading_without_order_by() {
+        // The last record is malformed, so reading it would fail the query
+        let dir = tables_dir(
+            "limit",
+            &[(
+                "ordenes.csv",
+                "id,product\n1,Mouse\n2,Laptop\n3,Monitor\n4\n",
+            )],
+        );
+
+        assert!(run("SELECT * FROM ordenes LIMIT 2;", &dir).is_ok());
+        assert!(run("SELECT * FROM ordenes LIMIT 2 OFFSET 1;", &dir).is_ok());
+        assert!(run("SELECT * FROM ordenes FETCH FIRST 0 ROWS ONLY;", &dir).is_ok());
+        assert!(run("SELECT * FROM ordenes LIMIT 2 OFFSET 2;", &dir).is_err());
+        assert!(run("SELECT * FROM ordenes ORDER BY id LIMIT 1;", &dir).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Runs the non-grouped path of a SELECT over `ordenes_rows()`, sorted and
+    /// with the hidden sort values removed.
+    fn sorted_ids(sql: &str) -> Result<Vec<String>, SQLError> {
+        let scope = ordenes_scope();
+        let query = select_query(sql);
+        let columns = output_columns(&query, &scope);
+        let plan = plan_output(&query, &columns, &scope)?;
+        let mut results = ordenes_rows()
+            .iter()
+            .map(|row| project_row(&query.columns, &plan.hidden, &RowContext::new(&scope, row)))
+            .collect::<Result<Vec<_>, SQLError>>()?;
+        sort_results(&mut results, &plan.sort_keys);
+        assert!(results.iter().all(|row| row.len() >= columns.len()));
+        Ok(results.iter().map(|row| row[0].to_string()).collect())
+    }
+
+    #[test]
+    fn test_sort_by_several_keys() {
+        assert_eq!(
+            sorted_ids("SELECT id FROM ordenes ORDER BY client_id DESC, product, quantity;")
+                .unwrap(),
+            vec!["111", "112", "110", "105", "102"]
+        );
+        // Ordinals and expressions outside the select list
+        assert_eq!(
+            sorted_ids("SELECT id, product FROM ordenes ORDER BY 2 DESC, quantity IS NULL DESC;")
+                .unwrap(),
+            vec!["102", "110", "112", "105", "111"]
+        );
+        // Ties keep the order the rows were read in
+        assert_eq!(
+            sorted_ids("SELECT id FROM ordenes ORDER BY quantity = 2 DESC NULLS LAST;").unwrap(),
+            vec!["102", "105", "110", "111", "112"]
+        );
+    }
+
+    #[test]
+    fn test_sort_key_errors() {
+        assert!(matches!(
+            sorted_ids("SELECT id FROM ordenes ORDER BY 2;"),
+            Err(SQLError::InvalidColumn(_))
+        ));
+        assert!(matches!(
+            sorted_ids("SELECT id FROM ordenes ORDER BY missing;"),
+            Err(SQLError::InvalidColumn(_))
+        ));
+
+        let scope = ordenes_scope();
+        let query = select_query(
+            "SELECT product, COUNT(*) FROM ordenes GROUP BY product ORDER BY client_id;",
+        );
+        let columns = output_columns(&query, &scope);
+        let plan = plan_output(&query, &columns, &scope).unwrap();
+        assert!(matches!(
+            check_grouped_query(&query, &scope, &plan.hidden),
+            Err(SQLError::InvalidColumn(_))
+        ));
+    }
+
+    /// Runs a SELECT over `ordenes_rows()`, returning each output row as text.
+    fn select_ordenes(sql: &str) -> Result<Vec<Vec<String>>, SQLError> {
+        let query = select_query(sql);
+        let rows = Box::new(ordenes_rows().into_iter().map(Ok));
+        let (_, results) = process_rows(rows, ordenes_scope(), query)?;
+        Ok(results
+            .collect::<Result<Vec<_>, _>>()?
+            .iter()
+            .map(|row| row.iter().map(|value| value.to_string()).collect())
+            .collect())
+    }
+
+    #[test]
+    fn test_select_distinct() {
+        assert_eq!(
+            select_ordenes("SELECT DISTINCT product FROM ordenes;").unwrap(),
+            vec![vec!["Teléfono"], vec!["Mouse"], vec!["Laptop"]]
+        );
+        assert_eq!(
+            select_ordenes("SELECT DISTINCT quantity FROM ordenes ORDER BY 1 LIMIT 2;").unwrap(),
+            vec![vec!["2"], vec!["3"]]
+        );
+        // Integers and whole floats are the same value
+        assert_eq!(
+            select_ordenes("SELECT DISTINCT client_id, quantity FROM ordenes LIMIT 2 OFFSET 1;")
+                .unwrap(),
+            vec![vec!["4", "2"], vec!["6", "2"]]
+        );
+        assert!(matches!(
+            select_ordenes("SELECT DISTINCT product FROM ordenes ORDER BY id;"),
+            Err(SQLError::InvalidSyntax(_))
+        ));
+    }
+
+    #[test]
+    fn test_select_distinct_on() {
+        // The first row of each client in ORDER BY order
+        assert_eq!(
+            select_ordenes(
+                "SELECT DISTINCT ON (client_id) client_id, id FROM ordenes \
+                 ORDER BY client_id, id DESC;"
+            )
+            .unwrap(),
+            vec![vec!["2", "102"], vec!["4", "105"], vec!["6", "112"]]
+        );
+        // Without ORDER BY, the first row read
+        assert_eq!(
+            select_ordenes("SELECT DISTINCT ON (product) id FROM ordenes;").unwrap(),
+            vec![vec!["102"], vec!["105"], vec!["111"]]
+        );
+        assert!(matches!(
+            select_ordenes("SELECT DISTINCT ON (client_id) id FROM ordenes ORDER BY id;"),
+            Err(SQLError::InvalidSyntax(_))
+        ));
+    }
+
+    #[test]
+    fn test_select_aliases_name_and_sort_output() {
+        let query = select_query(
+            "SELECT product AS item, SUM(quantity) total, o.client_id FROM ordenes o \
+             GROUP BY product, client_id ORDER BY total DESC, item;",
+        );
+        let scope = Scope::from_table("tables", "o", &ordenes_schema());
+        let rows = Box::new(ordenes_rows().into_iter().map(Ok));
+        let (columns, results) = process_rows(rows, scope, query).unwrap();
+        let results: Vec<Vec<Value>> = results.collect::<Result<_, _>>().unwrap();
+        let names: Vec<&str> = columns.iter().map(|column| column.name.as_str()).collect();
+        assert_eq!(names, vec!["item", "total", "client_id"]);
+        let items: Vec<String> = results.iter().map(|row| row[0].to_string()).collect();
+        // The NULL total of client 6's mouse order sorts first in descending order
+        assert_eq!(
+            items,
+            vec!["Mouse", "Laptop", "Mouse", "Teléfono", "Teléfono"]
+        );
+    }
+
+    #[test]
+    fn test_arithmetic_in_where_and_select() {
+        assert_eq!(
+            select_ordenes(
+                "SELECT id, quantity * 2 + 1 AS doubled, -quantity, quantity / 2.0 FROM ordenes \
+                 WHERE (client_id + 1) % 2 = 1 AND id - 100 > 3;"
+            )
+            .unwrap(),
+            vec![
+                vec!["105", "5", "-2", "1.0"],
+                vec!["110", "5", "-2", "1.0"],
+                vec!["111", "7", "-3", "1.5"],
+                vec!["112", "", "", ""],
+            ]
+        );
+        assert!(matches!(
+            select_ordenes("SELECT id / (quantity - 2) FROM ordenes;"),
+            Err(SQLError::GenericError(_))
+        ));
+        assert!(matches!(
+            select_ordenes("SELECT id FROM ordenes WHERE quantity + 1;"),
+            Err(SQLError::InvalidSyntax(_))
+        ));
+    }
+
+    #[test]
+    fn test_update_assigns_expressions_from_the_old_row() {
+        let dir = tables_dir(
+            "update_expressions",
+            &[
+                ("ordenes.csv", "id,client_id,quantity\n1,10,2\n2,20,5\n"),
+                ("ordenes.schema", "quantity INTEGER\n"),
+            ],
+        );
+
+        // Both assignments see the old quantity
+        run(
+            "UPDATE ordenes SET quantity = quantity + 1, client_id = quantity * 100 WHERE id = 2;",
+            &dir,
+        )
+        .unwrap();
+        let contents = std::fs::read_to_string(format!("{}/ordenes.csv", dir)).unwrap();
+        assert_eq!(contents, "id,client_id,quantity\n1,10,2\n2,500,6\n");
+
+        let result = run("UPDATE ordenes SET quantity = quantity / 4.0;", &dir);
+        assert!(matches!(result, Err(SQLError::TypeMismatch(_))));
+        let result = run("UPDATE ordenes SET quantity = missing;", &dir);
+        assert!(matches!(result, Err(SQLError::InvalidColumn(_))));
+        let result = run("UPDATE ordenes SET quantity = 1, quantity = 2;", &dir);
+        assert!(matches!(result, Err(SQLError::InvalidSyntax(_))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_insert_many_rows_and_select() {
+        let dir = tables_dir(
+            "insert_rows",
+            &[
+                ("ordenes.csv", "id,product,quantity\n1,Mouse,2\n"),
+                (
+                    "ordenes.schema",
+                    "id INTEGER NOT NULL\nquantity INTEGER DEFAULT 1\n",
+                ),
+                ("archivo.csv", "id,product\n"),
+            ],
+        );
+        let contents =
+            |table: &str| std::fs::read_to_string(format!("{}/{}.csv", dir, table)).unwrap();
+
+        run(
+            "INSERT INTO ordenes (id, product) VALUES (2, 'Laptop'), (3, 'Cable');",
+            &dir,
+        )
+        .unwrap();
+        assert_eq!(
+            contents("ordenes"),
+            "id,product,quantity\n1,Mouse,2\n2,Laptop,1\n3,Cable,1\n"
+        );
+
+        // A bad row rejects the whole statement
+        let result = run(
+            "INSERT INTO ordenes VALUES (4, 'Monitor', 1), (NULL, 'Mouse', 1);",
+            &dir,
+        );
+        assert!(matches!(result, Err(SQLError::TypeMismatch(_))));
+        let result = run(
+            "INSERT INTO ordenes VALUES (4, 'Monitor', 1), (5, 'Mouse');",
+            &dir,
+        );
+        assert!(matches!(result, Err(SQLError::InvalidSyntax(_))));
+        assert_eq!(contents("ordenes").lines().count(), 4);
+
+        run(
+            "INSERT INTO archivo SELECT id, product FROM ordenes WHERE quantity = 1 ORDER BY id DESC;",
+            &dir,
+        )
+        .unwrap();
+        assert_eq!(contents("archivo"), "id,product\n3,Cable\n2,Laptop\n");
+
+        // Reading and appending to the same table copies each row once
+        run(
+            "INSERT INTO ordenes SELECT id + 10, product, quantity FROM ordenes;",
+            &dir,
+        )
+        .unwrap();
+        assert_eq!(contents("ordenes").lines().count(), 7);
+        let result = run(
+            "INSERT INTO ordenes (id) SELECT id, product FROM archivo;",
+            &dir,
+        );
+        assert!(matches!(result, Err(SQLError::InvalidSyntax(_))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_multi_character_operators() {
+        assert_eq!(
+            select_ordenes(
+                "SELECT id FROM ordenes WHERE product <> 'Teléfono' AND client_id != 6;"
+            )
+            .unwrap(),
+            vec![vec!["105"]]
+        );
+        assert_eq!(
+            select_ordenes(
+                "SELECT product || ' x' || quantity, id::TEXT || '!', quantity::FLOAT / 4 \
+                 FROM ordenes WHERE client_id::TEXT = '6';"
+            )
+            .unwrap(),
+            vec![
+                vec!["Teléfono x2", "110!", "0.5"],
+                vec!["Laptop x3", "111!", "0.75"],
+                vec!["", "112!", ""],
+            ]
+        );
+        assert!(matches!(
+            select_ordenes("SELECT product::INTEGER FROM ordenes;"),
+            Err(SQLError::TypeMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_quoted_identifiers_and_escaped_literals() {
+        let dir = tables_dir(
+            "quoted_identifiers",
+            &[("pedidos.csv", "id,order,ship date\n1,A,2024-01-02\n2,B,\n")],
+        );
+        run(
+            "UPDATE pedidos SET \"order\" = 'O''Brien' -- the customer\nWHERE id = 2;",
+            &dir,
+        )
+        .unwrap();
+        let (headers, rows) = select_from(
+            "SELECT \"order\", p.\"ship date\" FROM pedidos p /* every row */ ORDER BY \"order\";",
+            &dir,
+        )
+        .unwrap();
+        assert_eq!(headers, vec!["order", "ship date"]);
+        assert_eq!(rows, vec![vec!["A", "2024-01-02"], vec!["O'Brien", ""]]);
+        assert!(matches!(
+            run("SELECT * FROM pedidos WHERE \"order\" = 'A;", &dir),
+            Err(SQLError::Located { error, .. }) if matches!(*error, SQLError::InvalidSyntax(_))
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_unknown_columns_suggest_close_names() {
+        fn error_message<T>(result: Result<T, SQLError>) -> String {
+            match result {
+                Err(SQLError::InvalidColumn(message)) => message,
+                _ => panic!("Expected an invalid column error"),
+            }
+        }
+        assert_eq!(
+            error_message(select_ordenes("SELECT prodcut FROM ordenes;")),
+            "Column 'prodcut' does not exist (did you mean 'product'?)"
+        );
+        assert_eq!(
+            error_message(select_ordenes("SELECT ordenes.quantty FROM ordenes;")),
+            "Column 'ordenes.quantty' does not exist (did you mean 'quantity'?)"
+        );
+        assert_eq!(
+            error_message(select_ordenes("SELECT price FROM ordenes;")),
+            "Column 'price' does not exist"
+        );
+
+        let dir = tables_dir(
+            "column_suggestions",
+            &[("ordenes.csv", "id,product\n1,Mouse\n")],
+        );
+        assert_eq!(
+            error_message(run("UPDATE ordenes SET prodct = 'Cable';", &dir)),
+            "Column 'prodct' does not exist (did you mean 'product'?)"
+        );
+        assert_eq!(
+            error_message(run(
+                "INSERT INTO ordenes (id, Product) VALUES (2, 'Cable');",
+                &dir
+            )),
+            "Column 'Product' does not exist (did you mean 'product'?)"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_conditional_functions_evaluate_lazily() {
+        // The arguments that are not returned would divide by zero
+        assert_eq!(
+            select_ordenes(
+                "SELECT IIF(quantity = 2, 0, 10 / (quantity - 2)), \
+                 COALESCE(quantity, 10 / (quantity - 2)) FROM ordenes;"
+            )
+            .unwrap(),
+            vec![
+                vec!["0", "2"],
+                vec!["0", "2"],
+                vec!["0", "2"],
+                vec!["10", "3"],
+                vec!["", ""],
+            ]
+        );
+        assert!(matches!(
+            select_ordenes("SELECT IIF(quantity, 1, 2) FROM ordenes;"),
+            Err(SQLError::TypeMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_string_functions_in_queries() {
+        let dir = tables_dir(
+            "string_functions",
+            &[
+                ("ordenes.csv", "id,product\n1,Mouse\n2,Laptop\n"),
+                ("ordenes.schema", "id INTEGER\n"),
+            ],
+        );
+        run(
+            "UPDATE ordenes SET product = CONCAT(UPPER(product), '-', LPAD(id::TEXT, 3, '0')) \
+             WHERE id = 1;",
+            &dir,
+        )
+        .unwrap();
+        let (headers, rows) = select_from(
+            "SELECT id, LOWER(SUBSTR(product, 1, 2)), LENGTH(product) FROM ordenes \
+             WHERE POSITION('-' IN product) > 0;",
+            &dir,
+        )
+        .unwrap();
+        assert_eq!(headers[1], "LOWER(SUBSTR(product, 1, 2))");
+        assert_eq!(rows, vec![vec!["1", "mo", "9"]]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_dates_in_queries() {
+        let dir = tables_dir(
+            "dates",
+            &[
+                (
+                    "envios.csv",
+                    "id,sent,delivered\n1,2024-01-30,2024-02-02 16:45:00\n2,2024-03-15,\n",
+                ),
+                (
+                    "envios.schema",
+                    "id INTEGER\nsent DATE\ndelivered TIMESTAMP\n",
+                ),
+            ],
+        );
+        run(
+            "INSERT INTO envios (id, sent) VALUES (3, DATE '2024-04-01');",
+            &dir,
+        )
+        .unwrap();
+        assert!(run(
+            "INSERT INTO envios (id, sent) VALUES (4, '2024-02-30');",
+            &dir
+        )
+        .is_err());
+
+        let (headers, rows) = select_from(
+            "SELECT id, sent + INTERVAL '1 month', EXTRACT(DOW FROM sent), \
+             DATE_DIFF('day', sent, delivered) FROM envios \
+             WHERE sent >= '2024-01-30' AND sent < DATE '2024-04-01' ORDER BY sent DESC;",
+            &dir,
+        )
+        .unwrap();
+        assert_eq!(headers[1], "sent + INTERVAL '1 mon'");
+        assert_eq!(headers[2], "EXTRACT(DOW FROM sent)");
+        assert_eq!(
+            rows,
+            vec![
+                vec!["2", "2024-04-15 00:00:00", "5", ""],
+                vec!["1", "2024-02-29 00:00:00", "2", "3"],
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_case_and_conditional_functions() {
+        assert_eq!(
+            select_ordenes(
+                "SELECT id, CASE WHEN quantity IS NULL THEN 'unknown' WHEN quantity > 2 THEN 'many' \
+                 ELSE 'few' END, COALESCE(quantity, 0), NULLIF(product, 'Mouse'), \
+                 IIF(client_id = 6, 'six', 'other'), \
+                 CASE WHEN quantity = 3 THEN 0 ELSE 10 / (quantity - 3) END \
+                 FROM ordenes WHERE id > 105;"
+            )
+            .unwrap(),
+            vec![
+                vec!["110", "few", "2", "Teléfono", "six", "-10"],
+                vec!["111", "many", "3", "Laptop", "six", "0"],
+                vec!["112", "unknown", "0", "", "six", ""],
+            ]
+        );
+        assert!(matches!(
+            select_ordenes("SELECT CAST(product AS INTEGER) FROM ordenes;"),
+            Err(SQLError::TypeMismatch(message)) if message == "Cannot convert 'Teléfono' to INTEGER"
+        ));
+    }
+
+    #[test]
+    fn test_subqueries() {
+        let dir = tables_dir(
+            "subqueries",
+            &[
+                ("ordenes.csv", "id,product,quantity\n1,Mouse,2\n2,Laptop,\n"),
+                ("ordenes.schema", "id INTEGER\nquantity INTEGER\n"),
+                ("clientes.csv", "id,name\n1,Ana\n2,Luis\n3,Eva\n"),
+                ("clientes.schema", "id INTEGER\n"),
+                ("compras.csv", "client,orden\n1,1\n1,2\n3,1\n"),
+                ("compras.schema", "client INTEGER\norden INTEGER\n"),
+            ],
+        );
+        let select = |sql: &str| select_from(sql, &dir).map(|(_, rows)| rows);
+
+        // Correlated, run for every client
+        assert_eq!(
+            select(
+                "SELECT name, (SELECT COUNT(*) FROM compras WHERE client = clientes.id) \
+                 FROM clientes WHERE EXISTS (SELECT 1 FROM compras WHERE compras.client = clientes.id);"
+            )
+            .unwrap(),
+            vec![vec!["Ana", "2"], vec!["Eva", "1"]]
+        );
+        assert_eq!(
+            select(
+                "SELECT name FROM clientes c \
+                 WHERE NOT EXISTS (SELECT * FROM compras WHERE client = c.id);"
+            )
+            .unwrap(),
+            vec![vec!["Luis"]]
+        );
+        assert_eq!(
+            select(
+                "SELECT name FROM clientes WHERE id IN (SELECT client FROM compras \
+                 WHERE orden IN (SELECT id FROM ordenes WHERE product = 'Laptop'));"
+            )
+            .unwrap(),
+            vec![vec!["Ana"]]
+        );
+        assert_eq!(
+            select(
+                "SELECT name, id NOT IN (SELECT client FROM compras) FROM clientes ORDER BY id;"
+            )
+            .unwrap(),
+            vec![
+                vec!["Ana", "false"],
+                vec!["Luis", "true"],
+                vec!["Eva", "false"]
+            ]
+        );
+        assert_eq!(
+            select(
+                "SELECT product, quantity - (SELECT MIN(quantity) FROM ordenes) FROM ordenes \
+                 WHERE id = (SELECT MAX(orden) FROM compras WHERE client = 3);"
+            )
+            .unwrap(),
+            vec![vec!["Mouse", "0"]]
+        );
+        assert_eq!(
+            select(
+                "SELECT t.client, t.total FROM (SELECT client, COUNT(*) AS total FROM compras \
+                 GROUP BY client) AS t WHERE t.total > 1;"
+            )
+            .unwrap(),
+            vec![vec!["1", "2"]]
+        );
+        // A scalar subquery without rows is NULL
+        assert_eq!(
+            select("SELECT (SELECT name FROM clientes WHERE id = 9) IS NULL FROM ordenes LIMIT 1;")
+                .unwrap(),
+            vec![vec!["true"]]
+        );
+        // Of an aggregate query, reading the columns of each group
+        assert_eq!(
+            select(
+                "SELECT client, (SELECT name FROM clientes WHERE id = client), \
+                 (SELECT orden AS o FROM compras c WHERE c.client = compras.client ORDER BY o DESC LIMIT 1) \
+                 FROM compras GROUP BY client ORDER BY client;"
+            )
+            .unwrap(),
+            vec![vec!["1", "Ana", "2"], vec!["3", "Eva", "1"]]
+        );
+        assert!(matches!(
+            select(
+                "SELECT COUNT(*), (SELECT name FROM clientes c WHERE c.id = o.orden) \
+                 FROM compras o WHERE client > 1000;"
+            ),
+            Err(SQLError::InvalidColumn(message))
+                if message == "Column 'o.orden' must appear in the GROUP BY clause or be used in an aggregate function"
+        ));
+        // Columns are resolved before any row is read
+        let query = select_query(
+            "SELECT name, (SELECT COUNT(*) FROM compras WHERE client = c.id AND total > 1) FROM clientes c;",
+        );
+        assert!(matches!(
+            select_stream(query, &dir),
+            Err(SQLError::InvalidColumn(message)) if message == "Column 'total' does not exist"
+        ));
+
+        run(
+            "DELETE FROM compras WHERE client IN (SELECT id FROM clientes WHERE name = 'Ana');",
+            &dir,
+        )
+        .unwrap();
+        let contents = std::fs::read_to_string(format!("{}/compras.csv", dir)).unwrap();
+        assert_eq!(contents, "client,orden\n3,1\n");
+
+        assert!(matches!(
+            select("SELECT (SELECT id FROM clientes) FROM ordenes;"),
+            Err(SQLError::GenericError(message)) if message == "Subquery used as a value returned more than one row"
+        ));
+        assert!(matches!(
+            select("SELECT * FROM ordenes WHERE id IN (SELECT id, name FROM clientes);"),
+            Err(SQLError::InvalidSyntax(message)) if message == "Subquery must return one column, but returns 2"
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_join_text_with_dates_and_times() {
+        let dir = tables_dir(
+            "join_dates",
+            &[
+                (
+                    "envios.csv",
+                    "id,sent,duration\n1,2024-01-01,01:30\n2,2024-03-15,02:00\n",
+                ),
+                ("envios.schema", "sent DATE\nduration INTERVAL\n"),
+                (
+                    "feriados.csv",
+                    "day,name\n2024-01-01,Año Nuevo\n2024-03-15 00:00,Marzo\n",
+                ),
+                ("turnos.csv", "start,length\n08:00,01:30\n"),
+                ("turnos.schema", "start TIME\n"),
+            ],
+        );
+        // The same rows the nested-loop join finds with a non-equality condition
+        for condition in ["e.sent = f.day", "e.sent >= f.day AND e.sent <= f.day"] {
+            let sql = format!(
+                "SELECT e.id, f.name FROM envios e JOIN feriados f ON {} ORDER BY e.id;",
+                condition
+            );
+            let (_, rows) = select_from(&sql, &dir).unwrap();
+            assert_eq!(rows, vec![vec!["1", "Año Nuevo"], vec!["2", "Marzo"]]);
+        }
+        let (_, rows) = select_from(
+            "SELECT e.id, t.start FROM envios e JOIN turnos t ON e.duration = t.length;",
+            &dir,
+        )
+        .unwrap();
+        assert_eq!(rows, vec![vec!["1", "08:00:00"]]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
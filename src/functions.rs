@@ -0,0 +1,477 @@
+use crate::datetime::{self, Timestamp};
+use crate::errors::SQLError;
+use crate::value::{DataType, Value};
+use std::cmp::Ordering;
+
+/// The scalar functions, with the fewest and the most arguments each takes;
+/// `None` for no limit. Text is handled as Unicode characters, not bytes.
+const FUNCTIONS: [(&str, usize, Option<usize>); 25] = [
+    ("UPPER", 1, Some(1)),
+    ("LOWER", 1, Some(1)),
+    ("LENGTH", 1, Some(1)),
+    ("TRIM", 1, Some(2)),
+    ("LTRIM", 1, Some(2)),
+    ("RTRIM", 1, Some(2)),
+    ("SUBSTR", 2, Some(3)),
+    ("REPLACE", 3, Some(3)),
+    ("CONCAT", 1, None),
+    ("POSITION", 2, Some(2)),
+    ("SPLIT_PART", 3, Some(3)),
+    ("LPAD", 2, Some(3)),
+    ("RPAD", 2, Some(3)),
+    ("CURRENT_DATE", 0, Some(0)),
+    ("CURRENT_TIME", 0, Some(0)),
+    ("CURRENT_TIMESTAMP", 0, Some(0)),
+    ("NOW", 0, Some(0)),
+    ("EXTRACT", 2, Some(2)),
+    ("DATE_PART", 2, Some(2)),
+    ("DATE_TRUNC", 2, Some(2)),
+    ("DATE_DIFF", 3, Some(3)),
+    ("STRFTIME", 2, Some(2)),
+    ("COALESCE", 1, None),
+    ("NULLIF", 2, Some(2)),
+    ("IIF", 3, Some(3)),
+];
+
+/// The longest text, in characters, that LPAD and RPAD pad to.
+const MAX_PAD_LENGTH: usize = 10_000_000;
+
+/// The functions that are called without parentheses, as in `CURRENT_DATE`.
+pub const NILADIC: [&str; 3] = ["CURRENT_DATE", "CURRENT_TIME", "CURRENT_TIMESTAMP"];
+
+/// The names of the scalar functions.
+pub fn names() -> impl Iterator<Item = &'static str> {
+    FUNCTIONS.iter().map(|(name, _, _)| *name)
+}
+
+pub fn is_function(name: &str) -> bool {
+    names().any(|function| function == name)
+}
+
+/// Checks that `function` can be called with `count` arguments.
+pub fn check_arity(function: &str, count: usize) -> Result<(), SQLError> {
+    let Some(&(_, min, max)) = FUNCTIONS.iter().find(|(name, _, _)| *name == function) else {
+        return Err(SQLError::InvalidSyntax(format!(
+            "Unknown function '{}'",
+            function
+        )));
+    };
+    if count >= min && max.is_none_or(|max| count <= max) {
+        return Ok(());
+    }
+    let plural = |n: usize| if n == 1 { "argument" } else { "arguments" };
+    let expected = match max {
+        Some(max) if max == min => format!("{} {}", min, plural(min)),
+        Some(max) => format!("{} to {} arguments", min, max),
+        None => format!("at least {} {}", min, plural(min)),
+    };
+    Err(SQLError::InvalidSyntax(format!(
+        "{} takes {}, but was given {}",
+        function, expected, count
+    )))
+}
+
+/// Whether IIF returns its second argument rather than its third, for which a
+/// NULL condition counts as false.
+pub fn iif_condition(condition: &Value) -> Result<bool, SQLError> {
+    match *condition.inferred() {
+        Value::Boolean(b) => Ok(b),
+        Value::Null => Ok(false),
+        _ => Err(SQLError::TypeMismatch(format!(
+            "IIF expects a boolean condition, got '{}'",
+            condition
+        ))),
+    }
+}
+
+/// Calls a scalar function. Every function but CONCAT and the conditional ones
+/// returns NULL when any argument is NULL; CONCAT skips them. The current date
+/// and time are read from the system clock, in UTC.
+pub fn call(function: &str, arguments: &[Value]) -> Result<Value, SQLError> {
+    check_arity(function, arguments.len())?;
+    match function {
+        // The first argument that is not NULL
+        "COALESCE" => {
+            let value = arguments.iter().find(|argument| !argument.is_null());
+            return Ok(value.cloned().unwrap_or(Value::Null));
+        }
+        // NULL when both arguments are equal, otherwise the first
+        "NULLIF" => {
+            let equal = arguments[0].compare(&arguments[1]) == Some(Ordering::Equal);
+            return Ok(if equal {
+                Value::Null
+            } else {
+                arguments[0].clone()
+            });
+        }
+        // The second argument when the condition is true, otherwise the third
+        "IIF" => {
+            let chosen = if iif_condition(&arguments[0])? { 1 } else { 2 };
+            return Ok(arguments[chosen].clone());
+        }
+        _ => {}
+    }
+    if function == "CONCAT" {
+        let text: String = arguments
+            .iter()
+            .filter(|argument| !argument.is_null())
+            .map(|argument| argument.to_string())
+            .collect();
+        return Ok(Value::Text(text));
+    }
+    if arguments.iter().any(Value::is_null) {
+        return Ok(Value::Null);
+    }
+
+    let text = |idx: usize| arguments[idx].to_string();
+    let integer = |idx: usize| integer_argument(function, &arguments[idx]);
+    let temporal = |idx: usize| temporal_argument(&arguments[idx]);
+    let value = match function {
+        "UPPER" => Value::Text(text(0).to_uppercase()),
+        "LOWER" => Value::Text(text(0).to_lowercase()),
+        "LENGTH" => Value::Integer(text(0).chars().count() as i64),
+        "TRIM" | "LTRIM" | "RTRIM" => {
+            let characters = if arguments.len() > 1 {
+                text(1)
+            } else {
+                " ".to_string()
+            };
+            let trimmed = |ch: char| characters.contains(ch);
+            let value = text(0);
+            Value::Text(match function {
+                "LTRIM" => value.trim_start_matches(trimmed).to_string(),
+                "RTRIM" => value.trim_end_matches(trimmed).to_string(),
+                _ => value.trim_matches(trimmed).to_string(),
+            })
+        }
+        "SUBSTR" => {
+            let length = if arguments.len() > 2 {
+                Some(integer(2)?)
+            } else {
+                None
+            };
+            Value::Text(substring(&text(0), integer(1)?, length)?)
+        }
+        "REPLACE" => {
+            let (value, from) = (text(0), text(1));
+            match from.is_empty() {
+                true => Value::Text(value),
+                false => Value::Text(value.replace(&from, &text(2))),
+            }
+        }
+        "POSITION" => {
+            let (substring, value) = (text(0), text(1));
+            let position = value
+                .find(&substring)
+                .map_or(0, |byte_idx| value[..byte_idx].chars().count() + 1);
+            Value::Integer(position as i64)
+        }
+        "SPLIT_PART" => Value::Text(split_part(&text(0), &text(1), integer(2)?)?),
+        "LPAD" | "RPAD" => {
+            let fill = if arguments.len() > 2 {
+                text(2)
+            } else {
+                " ".to_string()
+            };
+            Value::Text(pad(&text(0), integer(1)?, &fill, function == "LPAD")?)
+        }
+        "CURRENT_DATE" => Value::Date(Timestamp::now().date()),
+        "CURRENT_TIME" => Value::Time(Timestamp::now().time()),
+        "CURRENT_TIMESTAMP" | "NOW" => Value::Timestamp(Timestamp::now()),
+        "EXTRACT" | "DATE_PART" => datetime::extract(&text(0), &temporal(1))?,
+        "DATE_TRUNC" => datetime::truncate(&text(0), &temporal(1))?,
+        "DATE_DIFF" => datetime::difference(&text(0), &temporal(1), &temporal(2))?,
+        "STRFTIME" => Value::Text(datetime::format(&temporal(0), &text(1))?),
+        _ => {
+            return Err(SQLError::InvalidSyntax(format!(
+                "Unknown function '{}'",
+                function
+            )))
+        }
+    };
+    Ok(value)
+}
+
+/// Reads an argument that must be a whole number, such as a length.
+fn integer_argument(function: &str, argument: &Value) -> Result<i64, SQLError> {
+    match argument {
+        Value::Integer(n) => Some(*n),
+        Value::Text(text) => text.trim().parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        SQLError::TypeMismatch(format!(
+            "{} expects an integer argument, got '{}'",
+            function, argument
+        ))
+    })
+}
+
+/// Reads text passed where a date, time, timestamp or interval is expected as
+/// the first of those it can be read as.
+fn temporal_argument(argument: &Value) -> Value {
+    let Value::Text(text) = argument else {
+        return argument.clone();
+    };
+    [
+        DataType::Date,
+        DataType::Timestamp,
+        DataType::Time,
+        DataType::Interval,
+    ]
+    .iter()
+    .find_map(|data_type| data_type.parse_field(text.trim()))
+    .unwrap_or_else(|| argument.clone())
+}
+
+/// The characters of `text` from the 1-based position `start`, up to `length` of
+/// them counted from `start` even when it is before the first character.
+fn substring(text: &str, start: i64, length: Option<i64>) -> Result<String, SQLError> {
+    let end = match length {
+        Some(length) if length < 0 => {
+            return Err(SQLError::GenericError(
+                "SUBSTR length cannot be negative".to_string(),
+            ))
+        }
+        Some(length) => start.saturating_add(length),
+        None => i64::MAX,
+    };
+    let skip = start.max(1) - 1;
+    let take = end.saturating_sub(start.max(1)).max(0);
+    Ok(text
+        .chars()
+        .skip(skip as usize)
+        .take(usize::try_from(take).unwrap_or(usize::MAX))
+        .collect())
+}
+
+/// The `n`th field of `text` split on `delimiter`, counting from the end when `n`
+/// is negative, or empty text when there is no such field.
+fn split_part(text: &str, delimiter: &str, n: i64) -> Result<String, SQLError> {
+    if n == 0 {
+        return Err(SQLError::GenericError(
+            "SPLIT_PART field position must not be zero".to_string(),
+        ));
+    }
+    let fields: Vec<&str> = match delimiter.is_empty() {
+        true => vec![text],
+        false => text.split(delimiter).collect(),
+    };
+    let idx = match n > 0 {
+        true => usize::try_from(n - 1).ok(),
+        false => usize::try_from(n.unsigned_abs())
+            .ok()
+            .and_then(|from_end| fields.len().checked_sub(from_end)),
+    };
+    Ok(idx
+        .and_then(|idx| fields.get(idx))
+        .map_or(String::new(), |field| field.to_string()))
+}
+
+/// Pads `text` to `length` characters by repeating `fill` on its left or right,
+/// or cuts it down to `length` characters if it is longer.
+fn pad(text: &str, length: i64, fill: &str, left: bool) -> Result<String, SQLError> {
+    let length = usize::try_from(length).unwrap_or(0);
+    if length > MAX_PAD_LENGTH {
+        return Err(SQLError::GenericError(format!(
+            "{} length cannot exceed {}",
+            if left { "LPAD" } else { "RPAD" },
+            MAX_PAD_LENGTH
+        )));
+    }
+    let chars = text.chars().count();
+    if chars >= length || fill.is_empty() {
+        return Ok(text.chars().take(length).collect());
+    }
+    let padding: String = fill.chars().cycle().take(length - chars).collect();
+    Ok(match left {
+        true => padding + text,
+        false => text.to_string() + &padding,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(value: &str) -> Value {
+        Value::Text(value.to_string())
+    }
+
+    fn call_text(function: &str, arguments: &[Value]) -> String {
+        match call(function, arguments).unwrap() {
+            Value::Text(text) => text,
+            other => panic!("Expected text, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_string_functions_handle_unicode() {
+        assert_eq!(call_text("UPPER", &[text("Rodríguez")]), "RODRÍGUEZ");
+        assert_eq!(call_text("LOWER", &[text("FERNÁNDEZ")]), "fernández");
+        assert_eq!(
+            call("LENGTH", &[text("Fernández")]).unwrap(),
+            Value::Integer(9)
+        );
+        assert_eq!(
+            call_text("SUBSTR", &[text("Rodríguez"), Value::Integer(4)]),
+            "ríguez"
+        );
+        assert_eq!(
+            call_text(
+                "SUBSTR",
+                &[text("Rodríguez"), Value::Integer(0), Value::Integer(4)]
+            ),
+            "Rod"
+        );
+        assert_eq!(
+            call("POSITION", &[text("í"), text("Rodríguez")]).unwrap(),
+            Value::Integer(5)
+        );
+        assert_eq!(
+            call("POSITION", &[text("x"), text("Rodríguez")]).unwrap(),
+            Value::Integer(0)
+        );
+        assert_eq!(
+            call_text("LPAD", &[text("ñu"), Value::Integer(5), text("áb")]),
+            "ábáñu"
+        );
+        assert_eq!(
+            call_text("RPAD", &[text("Fernández"), Value::Integer(4)]),
+            "Fern"
+        );
+        assert_eq!(call_text("RPAD", &[text("7"), Value::Integer(3)]), "7  ");
+        assert!(matches!(
+            call("LPAD", &[text("a"), Value::Integer(99_999_999_999_999), text("*")]),
+            Err(SQLError::GenericError(message)) if message == "LPAD length cannot exceed 10000000"
+        ));
+    }
+
+    #[test]
+    fn test_trim_replace_split_and_concat() {
+        assert_eq!(call_text("TRIM", &[text("  a b  ")]), "a b");
+        assert_eq!(call_text("LTRIM", &[text("xxaxx"), text("x")]), "axx");
+        assert_eq!(call_text("RTRIM", &[text("xxaxx"), text("x")]), "xxa");
+        assert_eq!(
+            call_text("REPLACE", &[text("a-b-c"), text("-"), text("--")]),
+            "a--b--c"
+        );
+        assert_eq!(
+            call_text("REPLACE", &[text("abc"), text(""), text("x")]),
+            "abc"
+        );
+        let split = |n| call_text("SPLIT_PART", &[text("ana@mail.com"), text("@"), n]);
+        assert_eq!(split(Value::Integer(2)), "mail.com");
+        assert_eq!(split(Value::Integer(-2)), "ana");
+        assert_eq!(split(Value::Integer(3)), "");
+        // Argument values are checked as the query runs
+        assert!(matches!(
+            call("SPLIT_PART", &[text("a"), text("@"), Value::Integer(0)]),
+            Err(SQLError::GenericError(_))
+        ));
+        assert!(matches!(
+            call(
+                "SUBSTR",
+                &[text("abc"), Value::Integer(1), Value::Integer(-1)]
+            ),
+            Err(SQLError::GenericError(_))
+        ));
+        assert_eq!(
+            call_text("CONCAT", &[text("a"), Value::Null, Value::Integer(1)]),
+            "a1"
+        );
+    }
+
+    #[test]
+    fn test_nulls_and_argument_errors() {
+        assert_eq!(call("UPPER", &[Value::Null]).unwrap(), Value::Null);
+        assert_eq!(
+            call("SUBSTR", &[text("abc"), Value::Null]).unwrap(),
+            Value::Null
+        );
+        assert!(matches!(
+            call("SUBSTR", &[text("abc"), text("x")]),
+            Err(SQLError::TypeMismatch(_))
+        ));
+        assert!(call(
+            "SUBSTR",
+            &[text("abc"), Value::Integer(1), Value::Integer(-1)]
+        )
+        .is_err());
+        assert!(matches!(
+            check_arity("UPPER", 2),
+            Err(SQLError::InvalidSyntax(message)) if message == "UPPER takes 1 argument, but was given 2"
+        ));
+        assert!(check_arity("LPAD", 1).is_err());
+        assert!(check_arity("CONCAT", 0).is_err());
+        assert!(check_arity("CONCAT", 5).is_ok());
+    }
+
+    #[test]
+    fn test_date_functions_read_text_arguments() {
+        assert_eq!(
+            call("EXTRACT", &[text("YEAR"), text("2024-05-17")]).unwrap(),
+            Value::Integer(2024)
+        );
+        assert_eq!(
+            call("DATE_PART", &[text("hour"), text("2024-05-17 08:15")]).unwrap(),
+            Value::Integer(8)
+        );
+        assert_eq!(
+            call("DATE_TRUNC", &[text("year"), text("2024-05-17")])
+                .unwrap()
+                .to_string(),
+            "2024-01-01"
+        );
+        assert_eq!(
+            call(
+                "DATE_DIFF",
+                &[text("day"), text("2024-02-01"), text("2024-03-01")]
+            )
+            .unwrap(),
+            Value::Integer(29)
+        );
+        assert_eq!(
+            call_text("STRFTIME", &[text("2024-05-17"), text("%d/%m/%Y")]),
+            "17/05/2024"
+        );
+        assert!(matches!(
+            call("STRFTIME", &[text("soon"), text("%Y")]),
+            Err(SQLError::TypeMismatch(_))
+        ));
+        assert!(matches!(call("NOW", &[]).unwrap(), Value::Timestamp(_)));
+    }
+
+    #[test]
+    fn test_conditional_functions() {
+        assert_eq!(
+            call(
+                "COALESCE",
+                &[Value::Null, Value::Integer(2), Value::Integer(3)]
+            )
+            .unwrap(),
+            Value::Integer(2)
+        );
+        assert_eq!(call("COALESCE", &[Value::Null]).unwrap(), Value::Null);
+        assert_eq!(
+            call("NULLIF", &[Value::Integer(0), Value::Float(0.0)]).unwrap(),
+            Value::Null
+        );
+        assert_eq!(
+            call("NULLIF", &[Value::Integer(1), Value::Null]).unwrap(),
+            Value::Integer(1)
+        );
+        assert_eq!(
+            call("IIF", &[Value::Null, text("yes"), text("no")]).unwrap(),
+            text("no")
+        );
+        assert_eq!(
+            call("IIF", &[Value::Boolean(true), Value::Null, text("no")]).unwrap(),
+            Value::Null
+        );
+        assert!(matches!(
+            call("IIF", &[Value::Integer(1), text("yes"), text("no")]),
+            Err(SQLError::TypeMismatch(_))
+        ));
+    }
+}
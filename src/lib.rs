@@ -0,0 +1,32 @@
+//! A small SQL engine that runs queries against a directory of CSV files.
+//!
+//! ```no_run
+//! use rustic_sql::Database;
+//!
+//! let database = Database::open("tables")?;
+//! let results = database.query("SELECT id, product FROM ordenes WHERE quantity > 1;")?;
+//! for row in results.rows() {
+//!     println!("{} {}", row[0], row[1]);
+//! }
+//! let deleted = database.execute("DELETE FROM ordenes WHERE quantity = 0;")?;
+//! # Ok::<(), rustic_sql::SQLError>(())
+//! ```
+
+mod csv;
+mod data;
+mod database;
+mod datetime;
+mod errors;
+mod executor;
+mod functions;
+mod parser;
+mod result;
+mod utils;
+mod value;
+
+pub use database::Database;
+pub use datetime::{Date, Interval, Time, Timestamp};
+pub use errors::SQLError;
+pub use parser::Span;
+pub use result::{Column, ResultSet, Row, RowIter};
+pub use value::{DataType, Value};
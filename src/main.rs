@@ -1,15 +1,6 @@
+use rustic_sql::Database;
 use std::env;
 
-mod errors;
-use errors::SQLError;
-
-mod data;
-mod executor;
-mod parser;
-
-use crate::executor::execute_query;
-use crate::parser::{parse, tokenize};
-
 fn main() {
     // Collect command-line arguments
     let args: Vec<String> = env::args().collect();
@@ -23,30 +14,26 @@ fn main() {
     let tables_path = &args[1];
     let sql_query = &args[2];
 
-    // Proceed to parse and execute the SQL query
     println!("Tables path: {}", tables_path);
     println!("SQL query: {}", sql_query);
 
-    // Tokenize and parse the query
-    let tokens = match tokenize(sql_query) {
-        Ok(t) => t,
-        Err(e) => {
-            println!("{}", e);
-            std::process::exit(1);
-        }
-    };
-
-    let parsed_query = match parse(&tokens) {
-        Ok(q) => q,
-        Err(e) => {
-            println!("{}", e);
-            std::process::exit(1);
-        }
-    };
-
-    // Execute the query
-    if let Err(e) = execute_query(parsed_query, tables_path) {
-        println!("{}", e);
+    // Run the query and print any rows it returns as CSV, as they are read,
+    // followed by how many rows a statement changed
+    let result = Database::open(tables_path)
+        .and_then(|database| database.query_iter(sql_query))
+        .and_then(|rows| {
+            let affected = rows.rows_affected();
+            if !rows.columns().is_empty() {
+                rows.write_csv(&mut std::io::stdout().lock())?;
+            }
+            if let Some(count) = affected {
+                let noun = if count == 1 { "row" } else { "rows" };
+                println!("{} {} affected", count, noun);
+            }
+            Ok(())
+        });
+    if let Err(e) = result {
+        println!("{}", e.render(sql_query));
         std::process::exit(1);
     }
 }
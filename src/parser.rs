@@ -1,4 +1,8 @@
+use crate::functions;
+use crate::utils::{closest_match, did_you_mean};
+use crate::value::{parse_number, DataType, Value};
 use crate::SQLError;
+use std::fmt;
 
 #[derive(Debug)]
 pub enum SQLQuery {
@@ -8,19 +12,80 @@ pub enum SQLQuery {
     Delete(DeleteQuery),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SelectQuery {
-    pub columns: Vec<String>,
+    pub distinct: Distinct,
+    pub columns: Vec<SelectItem>,
     pub table: String,
+    pub alias: Option<String>,
+    /// `FROM (SELECT ...) AS alias`, a derived table whose rows are read instead
+    /// of a table file; `table` is then its alias
+    pub subquery: Option<Box<SelectQuery>>,
+    pub joins: Vec<Join>,
     pub where_clause: Option<Expression>,
-    pub order_by: Option<OrderBy>,
+    pub group_by: Vec<Expression>,
+    pub having: Option<Expression>,
+    pub order_by: Vec<OrderBy>,
+    /// Maximum number of rows to return, after skipping `offset` rows
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Distinct {
+    /// Every row is returned
+    All,
+    /// `DISTINCT`, rows with the same selected values are returned once
+    Rows,
+    /// `DISTINCT ON (...)`, only the first row for each value of the expressions
+    /// is returned
+    On(Vec<Expression>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectItem {
+    Wildcard,
+    /// `t.*`, every column of one table in the FROM clause
+    QualifiedWildcard(String),
+    /// An expression, headed by its alias or else a name derived from it
+    Expression {
+        expr: Expression,
+        alias: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Join {
+    pub kind: JoinKind,
+    pub table: String,
+    pub alias: Option<String>,
+    pub condition: Expression,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+    Right,
+    Full,
 }
 
 #[derive(Debug)]
 pub struct InsertQuery {
     pub table: String,
     pub columns: Vec<String>,
-    pub values: Vec<String>,
+    pub source: InsertSource,
+    /// The `RETURNING` list, evaluated against each inserted row; empty without one
+    pub returning: Vec<SelectItem>,
+}
+
+/// Where the rows of an INSERT come from.
+#[derive(Debug)]
+pub enum InsertSource {
+    /// `VALUES (...), (...)`, one list of literals per row
+    Values(Vec<Vec<Value>>),
+    /// `SELECT ...`, whose result rows are inserted
+    Select(Box<SelectQuery>),
 }
 
 #[derive(Debug)]
@@ -28,168 +93,905 @@ pub struct UpdateQuery {
     pub table: String,
     pub assignments: Vec<Assignment>,
     pub where_clause: Option<Expression>,
+    /// The `RETURNING` list, evaluated against each row after it is updated
+    pub returning: Vec<SelectItem>,
 }
 
 #[derive(Debug)]
 pub struct DeleteQuery {
     pub table: String,
     pub where_clause: Option<Expression>,
+    /// The `RETURNING` list, evaluated against each deleted row
+    pub returning: Vec<SelectItem>,
 }
 
 #[derive(Debug)]
 pub struct Assignment {
     pub column: String,
-    pub value: String,
+    /// Evaluated against the row as it was before the update
+    pub value: Expression,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
     BinaryOp {
         left: Box<Expression>,
         op: String,
         right: Box<Expression>,
     },
-    Literal(String),
+    UnaryOp {
+        op: String,
+        operand: Box<Expression>,
+    },
+    IsNull {
+        operand: Box<Expression>,
+        negated: bool,
+    },
+    /// `operand [NOT] IN (item, ...)`
+    InList {
+        operand: Box<Expression>,
+        list: Vec<Expression>,
+        negated: bool,
+    },
+    /// `operand [NOT] IN (SELECT ...)`, against the values of the subquery's
+    /// single column
+    InSubquery {
+        operand: Box<Expression>,
+        query: Box<SelectQuery>,
+        negated: bool,
+    },
+    /// `EXISTS (SELECT ...)`, whether the subquery returns any row
+    Exists(Box<SelectQuery>),
+    /// `(SELECT ...)` used as a value: that of the single column of its single
+    /// row, or NULL when it returns no rows
+    Subquery(Box<SelectQuery>),
+    /// `operand [NOT] BETWEEN low AND high`, which includes both bounds
+    Between {
+        operand: Box<Expression>,
+        low: Box<Expression>,
+        high: Box<Expression>,
+        negated: bool,
+    },
+    /// `operand [NOT] LIKE pattern [ESCAPE 'c']`, where `%` in the pattern matches
+    /// any text and `_` any one character. ILIKE ignores case.
+    Like {
+        operand: Box<Expression>,
+        pattern: Box<Expression>,
+        /// Makes the next pattern character match itself; `\` when not given
+        escape: Option<char>,
+        negated: bool,
+        case_insensitive: bool,
+    },
+    /// `operand::type` or `CAST(operand AS type)`, converting a value to another
+    /// type
+    Cast {
+        operand: Box<Expression>,
+        data_type: DataType,
+    },
+    /// `CASE [operand] WHEN ... THEN ... [ELSE ...] END`, giving the result of the
+    /// first branch that applies, or the ELSE result (NULL without one). With an
+    /// operand, a branch applies when its WHEN value equals the operand; without
+    /// one, when its WHEN condition is true.
+    Case {
+        operand: Option<Box<Expression>>,
+        branches: Vec<(Expression, Expression)>,
+        else_result: Option<Box<Expression>>,
+    },
+    Literal(Value),
     Column(String),
+    /// A column reference prefixed by its table name or alias, as in `o.client_id`
+    QualifiedColumn {
+        table: String,
+        column: String,
+    },
+    /// A column of the row an enclosing query is evaluating, referenced from
+    /// within a correlated subquery: its position in that row, along with the
+    /// reference as written. Only the executor creates these, when it binds the
+    /// subquery, and replaces them with the row's values before running it.
+    OuterColumn {
+        reference: Box<Expression>,
+        index: usize,
+    },
+    /// A call to a scalar function such as `UPPER(name)`, named in upper case
+    Function {
+        name: String,
+        arguments: Vec<Expression>,
+    },
+    /// An aggregate call such as `COUNT(*)` or `SUM(DISTINCT quantity)`; a missing
+    /// argument stands for `*`.
+    Aggregate {
+        function: String,
+        argument: Option<Box<Expression>>,
+        distinct: bool,
+    },
 }
 
-#[derive(Debug)]
+pub const AGGREGATE_FUNCTIONS: [&str; 5] = ["COUNT", "SUM", "AVG", "MIN", "MAX"];
+
+impl Expression {
+    /// Returns the direct subexpressions of this expression.
+    pub fn children(&self) -> Vec<&Expression> {
+        match self {
+            Expression::BinaryOp { left, right, .. } => vec![left, right],
+            Expression::UnaryOp { operand, .. }
+            | Expression::IsNull { operand, .. }
+            | Expression::InSubquery { operand, .. }
+            | Expression::Cast { operand, .. } => vec![operand],
+            Expression::InList { operand, list, .. } => {
+                std::iter::once(&**operand).chain(list).collect()
+            }
+            Expression::Between {
+                operand, low, high, ..
+            } => vec![operand, low, high],
+            Expression::Like {
+                operand, pattern, ..
+            } => vec![operand, pattern],
+            Expression::Case {
+                operand,
+                branches,
+                else_result,
+            } => operand
+                .iter()
+                .map(|operand| &**operand)
+                .chain(branches.iter().flat_map(|(when, then)| [when, then]))
+                .chain(else_result.iter().map(|result| &**result))
+                .collect(),
+            Expression::Function { arguments, .. } => arguments.iter().collect(),
+            Expression::Aggregate { argument, .. } => argument.iter().map(|arg| &**arg).collect(),
+            // The expressions of a subquery belong to its own query
+            Expression::Exists(_)
+            | Expression::Subquery(_)
+            | Expression::Literal(_)
+            | Expression::Column(_)
+            | Expression::QualifiedColumn { .. }
+            | Expression::OuterColumn { .. } => Vec::new(),
+        }
+    }
+
+    /// Returns the direct subexpressions of this expression, for rewriting them
+    /// in place.
+    pub fn children_mut(&mut self) -> Vec<&mut Expression> {
+        match self {
+            Expression::BinaryOp { left, right, .. } => vec![left, right],
+            Expression::UnaryOp { operand, .. }
+            | Expression::IsNull { operand, .. }
+            | Expression::InSubquery { operand, .. }
+            | Expression::Cast { operand, .. } => vec![operand],
+            Expression::InList { operand, list, .. } => {
+                std::iter::once(&mut **operand).chain(list).collect()
+            }
+            Expression::Between {
+                operand, low, high, ..
+            } => vec![operand, low, high],
+            Expression::Like {
+                operand, pattern, ..
+            } => vec![operand, pattern],
+            Expression::Case {
+                operand,
+                branches,
+                else_result,
+            } => operand
+                .iter_mut()
+                .map(|operand| &mut **operand)
+                .chain(branches.iter_mut().flat_map(|(when, then)| [when, then]))
+                .chain(else_result.iter_mut().map(|result| &mut **result))
+                .collect(),
+            Expression::Function { arguments, .. } => arguments.iter_mut().collect(),
+            Expression::Aggregate { argument, .. } => {
+                argument.iter_mut().map(|arg| &mut **arg).collect()
+            }
+            Expression::Exists(_)
+            | Expression::Subquery(_)
+            | Expression::Literal(_)
+            | Expression::Column(_)
+            | Expression::QualifiedColumn { .. }
+            | Expression::OuterColumn { .. } => Vec::new(),
+        }
+    }
+
+    pub fn contains_aggregate(&self) -> bool {
+        matches!(self, Expression::Aggregate { .. })
+            || self
+                .children()
+                .iter()
+                .any(|child| child.contains_aggregate())
+    }
+}
+
+impl SelectQuery {
+    /// The expressions of every clause. Those of a derived table in FROM belong
+    /// to its own query.
+    pub fn expressions(&self) -> Vec<&Expression> {
+        let mut expressions = Vec::new();
+        if let Distinct::On(ref on) = self.distinct {
+            expressions.extend(on.iter());
+        }
+        for item in &self.columns {
+            if let SelectItem::Expression { expr, .. } = item {
+                expressions.push(expr);
+            }
+        }
+        expressions.extend(self.joins.iter().map(|join| &join.condition));
+        expressions.extend(self.where_clause.iter());
+        expressions.extend(self.group_by.iter());
+        expressions.extend(self.having.iter());
+        expressions.extend(self.order_by.iter().map(|key| &key.expression));
+        expressions
+    }
+
+    /// The expressions of every clause, for rewriting them in place.
+    pub fn expressions_mut(&mut self) -> Vec<&mut Expression> {
+        let mut expressions = Vec::new();
+        if let Distinct::On(ref mut on) = self.distinct {
+            expressions.extend(on.iter_mut());
+        }
+        for item in &mut self.columns {
+            if let SelectItem::Expression { expr, .. } = item {
+                expressions.push(expr);
+            }
+        }
+        expressions.extend(self.joins.iter_mut().map(|join| &mut join.condition));
+        expressions.extend(self.where_clause.iter_mut());
+        expressions.extend(self.group_by.iter_mut());
+        expressions.extend(self.having.iter_mut());
+        expressions.extend(self.order_by.iter_mut().map(|key| &mut key.expression));
+        expressions
+    }
+}
+
+impl Expression {
+    /// How tightly the expression binds when printed; operands that bind less
+    /// tightly than their operator need parentheses.
+    fn precedence(&self) -> u8 {
+        match self {
+            Expression::BinaryOp { op, .. } => binary_precedence(op),
+            Expression::UnaryOp { op, .. } if op == "-" => PRECEDENCE_UNARY,
+            Expression::UnaryOp { .. } => PRECEDENCE_NOT,
+            Expression::IsNull { .. }
+            | Expression::InList { .. }
+            | Expression::InSubquery { .. }
+            | Expression::Between { .. }
+            | Expression::Like { .. } => PRECEDENCE_COMPARISON,
+            Expression::Cast { .. } => PRECEDENCE_CAST,
+            _ => u8::MAX,
+        }
+    }
+}
+
+fn write_operand(
+    f: &mut fmt::Formatter<'_>,
+    operand: &Expression,
+    min_precedence: u8,
+) -> fmt::Result {
+    if operand.precedence() < min_precedence {
+        write!(f, "({})", operand)
+    } else {
+        write!(f, "{}", operand)
+    }
+}
+
+/// Writes a column or table name, double-quoted when it would not read back as
+/// the same identifier (a keyword, or a name with spaces or symbols).
+fn write_identifier(f: &mut fmt::Formatter<'_>, name: &str) -> fmt::Result {
+    let plain = name.starts_with(|ch: char| ch.is_alphabetic())
+        && name.chars().all(|ch| ch.is_alphanumeric() || ch == '_')
+        && !is_keyword(name);
+    if plain {
+        write!(f, "{}", name)
+    } else {
+        write!(f, "\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+/// Renders an expression back as SQL, which is also used as the header of a
+/// computed output column.
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::BinaryOp { left, op, right } => {
+                let precedence = binary_precedence(op);
+                write_operand(f, left, precedence)?;
+                write!(f, " {} ", op)?;
+                // Operators are left-associative, so an equal one on the right
+                // was parenthesized
+                write_operand(f, right, precedence + 1)
+            }
+            Expression::UnaryOp { op, operand } if op == "-" => {
+                write!(f, "-")?;
+                write_operand(f, operand, PRECEDENCE_UNARY)
+            }
+            Expression::UnaryOp { op, operand } => {
+                write!(f, "{} ", op)?;
+                write_operand(f, operand, PRECEDENCE_NOT)
+            }
+            Expression::IsNull { operand, negated } => {
+                write_operand(f, operand, PRECEDENCE_COMPARISON)?;
+                match negated {
+                    true => write!(f, " IS NOT NULL"),
+                    false => write!(f, " IS NULL"),
+                }
+            }
+            Expression::InList {
+                operand,
+                list,
+                negated,
+            } => {
+                write_operand(f, operand, PRECEDENCE_COMPARISON)?;
+                write!(f, " {}IN (", if *negated { "NOT " } else { "" })?;
+                for (i, item) in list.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, ")")
+            }
+            Expression::InSubquery {
+                operand,
+                query,
+                negated,
+            } => {
+                write_operand(f, operand, PRECEDENCE_COMPARISON)?;
+                write!(f, " {}IN ({})", if *negated { "NOT " } else { "" }, query)
+            }
+            Expression::Exists(query) => write!(f, "EXISTS ({})", query),
+            Expression::Subquery(query) => write!(f, "({})", query),
+            Expression::Between {
+                operand,
+                low,
+                high,
+                negated,
+            } => {
+                write_operand(f, operand, PRECEDENCE_COMPARISON)?;
+                write!(f, " {}BETWEEN ", if *negated { "NOT " } else { "" })?;
+                write_operand(f, low, PRECEDENCE_COMPARISON + 1)?;
+                write!(f, " AND ")?;
+                write_operand(f, high, PRECEDENCE_COMPARISON + 1)
+            }
+            Expression::Like {
+                operand,
+                pattern,
+                escape,
+                negated,
+                case_insensitive,
+            } => {
+                write_operand(f, operand, PRECEDENCE_COMPARISON)?;
+                write!(f, " {}", if *negated { "NOT " } else { "" })?;
+                write!(f, "{} ", if *case_insensitive { "ILIKE" } else { "LIKE" })?;
+                write_operand(f, pattern, PRECEDENCE_COMPARISON + 1)?;
+                match escape {
+                    Some('\'') => write!(f, " ESCAPE ''''"),
+                    Some(escape) => write!(f, " ESCAPE '{}'", escape),
+                    None => Ok(()),
+                }
+            }
+            Expression::Cast { operand, data_type } => {
+                write_operand(f, operand, PRECEDENCE_CAST)?;
+                write!(f, "::{}", data_type)
+            }
+            Expression::Case {
+                operand,
+                branches,
+                else_result,
+            } => {
+                write!(f, "CASE")?;
+                if let Some(operand) = operand {
+                    write!(f, " {}", operand)?;
+                }
+                for (when, then) in branches {
+                    write!(f, " WHEN {} THEN {}", when, then)?;
+                }
+                if let Some(else_result) = else_result {
+                    write!(f, " ELSE {}", else_result)?;
+                }
+                write!(f, " END")
+            }
+            Expression::Literal(Value::Text(text)) => write!(f, "'{}'", text.replace('\'', "''")),
+            Expression::Literal(Value::Null) => write!(f, "NULL"),
+            Expression::Literal(
+                value
+                @ (Value::Date(_) | Value::Time(_) | Value::Timestamp(_) | Value::Interval(_)),
+            ) => match value.data_type() {
+                Some(data_type) => write!(f, "{} '{}'", data_type, value),
+                None => write!(f, "NULL"),
+            },
+            Expression::Literal(value) => write!(f, "{}", value),
+            Expression::Column(name) => write_identifier(f, name),
+            Expression::QualifiedColumn { table, column } => {
+                write_identifier(f, table)?;
+                write!(f, ".")?;
+                write_identifier(f, column)
+            }
+            Expression::OuterColumn { reference, .. } => write!(f, "{}", reference),
+            Expression::Function { name, arguments } => match arguments.as_slice() {
+                [] if functions::NILADIC.contains(&name.as_str()) => write!(f, "{}", name),
+                [Expression::Literal(Value::Text(field)), value] if name == "EXTRACT" => {
+                    write!(f, "EXTRACT({} FROM {})", field, value)
+                }
+                _ => {
+                    write!(f, "{}(", name)?;
+                    for (i, argument) in arguments.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{}", argument)?;
+                    }
+                    write!(f, ")")
+                }
+            },
+            Expression::Aggregate {
+                function,
+                argument,
+                distinct,
+            } => {
+                write!(f, "{}(", function)?;
+                if *distinct {
+                    write!(f, "DISTINCT ")?;
+                }
+                match argument {
+                    Some(argument) => write!(f, "{})", argument),
+                    None => write!(f, "*)"),
+                }
+            }
+        }
+    }
+}
+
+/// Renders a query back as SQL, as it is printed inside an expression that
+/// uses it as a subquery.
+impl fmt::Display for SelectQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SELECT ")?;
+        match &self.distinct {
+            Distinct::All => {}
+            Distinct::Rows => write!(f, "DISTINCT ")?,
+            Distinct::On(expressions) => {
+                write!(f, "DISTINCT ON (")?;
+                write_list(f, expressions)?;
+                write!(f, ") ")?;
+            }
+        }
+        write_list(f, &self.columns)?;
+        write!(f, " FROM ")?;
+        if let Some(subquery) = &self.subquery {
+            write!(f, "({}) AS ", subquery)?;
+        }
+        write_table(f, &self.table, &self.alias)?;
+        for join in &self.joins {
+            write!(f, " {}", join)?;
+        }
+        if let Some(where_clause) = &self.where_clause {
+            write!(f, " WHERE {}", where_clause)?;
+        }
+        if !self.group_by.is_empty() {
+            write!(f, " GROUP BY ")?;
+            write_list(f, &self.group_by)?;
+        }
+        if let Some(having) = &self.having {
+            write!(f, " HAVING {}", having)?;
+        }
+        if !self.order_by.is_empty() {
+            write!(f, " ORDER BY ")?;
+            write_list(f, &self.order_by)?;
+        }
+        if let Some(limit) = self.limit {
+            write!(f, " LIMIT {}", limit)?;
+        }
+        if self.offset > 0 {
+            write!(f, " OFFSET {}", self.offset)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SelectItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelectItem::Wildcard => write!(f, "*"),
+            SelectItem::QualifiedWildcard(table) => {
+                write_identifier(f, table)?;
+                write!(f, ".*")
+            }
+            SelectItem::Expression { expr, alias } => {
+                write!(f, "{}", expr)?;
+                match alias {
+                    Some(alias) => {
+                        write!(f, " AS ")?;
+                        write_identifier(f, alias)
+                    }
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Join {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            JoinKind::Inner => write!(f, "JOIN ")?,
+            JoinKind::Left => write!(f, "LEFT JOIN ")?,
+            JoinKind::Right => write!(f, "RIGHT JOIN ")?,
+            JoinKind::Full => write!(f, "FULL JOIN ")?,
+        }
+        write_table(f, &self.table, &self.alias)?;
+        write!(f, " ON {}", self.condition)
+    }
+}
+
+impl fmt::Display for OrderBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expression)?;
+        if !self.ascending {
+            write!(f, " DESC")?;
+        }
+        // NULLs come last in ascending order unless told otherwise
+        match (self.nulls_first, self.ascending) {
+            (true, true) => write!(f, " NULLS FIRST"),
+            (false, false) => write!(f, " NULLS LAST"),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn write_table(f: &mut fmt::Formatter<'_>, table: &str, alias: &Option<String>) -> fmt::Result {
+    write_identifier(f, table)?;
+    match alias {
+        Some(alias) => {
+            write!(f, " AS ")?;
+            write_identifier(f, alias)
+        }
+        None => Ok(()),
+    }
+}
+
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter<'_>, items: &[T]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+/// One ORDER BY key: an expression, or the 1-based position of an output column
+/// when it is an integer literal.
+#[derive(Debug, Clone, PartialEq)]
 pub struct OrderBy {
-    pub column: String,
+    pub expression: Expression,
     pub ascending: bool,
+    pub nulls_first: bool,
+}
+
+/// A byte range of the query text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
 }
 
+/// A token along with the span of the query text it was read from, so that
+/// errors can point at it.
 #[derive(Debug, PartialEq)]
-pub enum Token {
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TokenKind {
     Keyword(String),
     Identifier(String),
     Operator(String),
     Literal(String),
+    Number(String),
     Comma,
+    Dot,
     Semicolon,
     Asterisk,
     OpenParen,
     CloseParen,
-    EOF,
+    Eof,
+}
+
+/// Reserved words; anything else made of letters, digits and `_` is an identifier.
+#[rustfmt::skip]
+const KEYWORDS: [&str; 47] = [
+    "SELECT", "FROM", "WHERE", "ORDER", "BY", "ASC", "DESC", "INSERT", "INTO", "VALUES", "UPDATE",
+    "SET", "DELETE", "AND", "OR", "NOT", "TRUE", "FALSE", "NULL", "IS", "NULLS", "GROUP", "HAVING",
+    "DISTINCT", "JOIN", "INNER", "LEFT", "RIGHT", "FULL", "OUTER", "ON", "AS", "LIMIT", "OFFSET",
+    "FETCH", "RETURNING", "IN", "BETWEEN", "LIKE", "ILIKE", "ESCAPE", "CASE", "WHEN", "THEN", "ELSE",
+    "END", "EXISTS",
+];
+
+fn is_keyword(word: &str) -> bool {
+    KEYWORDS.contains(&word.to_uppercase().as_str())
+}
+
+/// Every operator the tokenizer recognizes, longest first so that the first one
+/// the input starts with is the longest match (`<>` rather than `<` then `>`).
+/// `*` is not listed since it is its own token.
+const OPERATORS: [&str; 13] = [
+    "<>", "<=", ">=", "!=", "||", "::", "=", "<", ">", "+", "-", "/", "%",
+];
+
+/// The longest operator at the start of the remaining input, if any.
+fn match_operator(rest: &str) -> Option<&'static str> {
+    OPERATORS.iter().copied().find(|op| rest.starts_with(op))
+}
+
+/// Moves past the next `len` bytes of the input.
+fn advance(chars: &mut std::str::Chars, len: usize) {
+    *chars = chars.as_str()[len..].chars();
 }
 
 pub fn tokenize(input: &str) -> Result<Vec<Token>, crate::errors::SQLError> {
     let mut tokens = Vec::new();
-    let mut chars = input.chars().peekable();
+    let mut chars = input.chars();
+    let offset = |chars: &std::str::Chars| input.len() - chars.as_str().len();
 
-    while let Some(&ch) = chars.peek() {
-        match ch {
+    while let Some(ch) = chars.clone().next() {
+        let start = offset(&chars);
+        let error = |message: String, end: usize| {
+            crate::errors::SQLError::InvalidSyntax(message).at(Span { start, end })
+        };
+        let kind = match ch {
             ' ' | '\t' | '\n' | '\r' => {
                 chars.next();
+                continue;
+            }
+            '-' if chars.as_str().starts_with("--") => {
+                // Line comment, up to the end of the line
+                let rest = chars.as_str();
+                advance(&mut chars, rest.find('\n').unwrap_or(rest.len()));
+                continue;
+            }
+            '/' if chars.as_str().starts_with("/*") => {
+                // Block comment; comments do not nest
+                match chars.as_str()[2..].find("*/") {
+                    Some(end) => advance(&mut chars, end + 4),
+                    None => {
+                        return Err(error("Unterminated block comment".to_string(), input.len()))
+                    }
+                }
+                continue;
             }
             ',' => {
                 chars.next();
-                tokens.push(Token::Comma);
+                TokenKind::Comma
+            }
+            '.' if chars
+                .clone()
+                .nth(1)
+                .is_some_and(|next| next.is_ascii_digit()) =>
+            {
+                TokenKind::Number(read_number(&mut chars))
+            }
+            '.' => {
+                chars.next();
+                TokenKind::Dot
             }
             ';' => {
                 chars.next();
-                tokens.push(Token::Semicolon);
+                TokenKind::Semicolon
             }
             '*' => {
                 chars.next();
-                tokens.push(Token::Asterisk);
+                TokenKind::Asterisk
             }
             '(' => {
                 chars.next();
-                tokens.push(Token::OpenParen);
+                TokenKind::OpenParen
             }
             ')' => {
                 chars.next();
-                tokens.push(Token::CloseParen);
-            }
-            '=' | '>' | '<' => {
-                let mut op = ch.to_string();
-                chars.next();
-                if let Some(&'=') = chars.peek() {
-                    op.push('=');
-                    chars.next();
-                }
-                tokens.push(Token::Operator(op));
+                TokenKind::CloseParen
             }
-            '\'' => {
-                chars.next();
-                let mut literal = String::new();
-                while let Some(&ch) = chars.peek() {
-                    if ch == '\'' {
-                        chars.next();
-                        break;
-                    } else {
-                        literal.push(ch);
-                        chars.next();
-                    }
-                }
-                tokens.push(Token::Literal(literal));
+            _ if OPERATORS.iter().any(|op| op.starts_with(ch)) => {
+                // Take the longest operator the input starts with
+                let Some(op) = match_operator(chars.as_str()) else {
+                    return Err(error(
+                        format!("Unexpected character: '{}'", ch),
+                        start + ch.len_utf8(),
+                    ));
+                };
+                advance(&mut chars, op.len());
+                TokenKind::Operator(op.to_string())
             }
-            _ if ch.is_alphabetic() => {
-                let mut ident = String::new();
-                while let Some(&ch) = chars.peek() {
-                    if ch.is_alphanumeric() || ch == '_' {
-                        ident.push(ch);
-                        chars.next();
-                    } else {
-                        break;
-                    }
+            '\'' => match read_quoted(&mut chars) {
+                Some(literal) => TokenKind::Literal(literal),
+                None => {
+                    return Err(error(
+                        "Unterminated string literal".to_string(),
+                        input.len(),
+                    ))
                 }
-                let upper_ident = ident.to_uppercase();
-                match upper_ident.as_str() {
-                    "SELECT" | "FROM" | "WHERE" | "ORDER" | "BY" | "ASC" | "DESC" | "INSERT"
-                    | "INTO" | "VALUES" | "UPDATE" | "SET" | "DELETE" | "AND" | "OR" | "NOT" => {
-                        tokens.push(Token::Keyword(upper_ident))
-                    }
-                    _ => tokens.push(Token::Identifier(ident)),
+            },
+            '"' => {
+                // A quoted identifier is taken as written, even if it is a keyword
+                let Some(ident) = read_quoted(&mut chars) else {
+                    return Err(error(
+                        "Unterminated quoted identifier".to_string(),
+                        input.len(),
+                    ));
+                };
+                if ident.is_empty() {
+                    return Err(error(
+                        "Quoted identifiers cannot be empty".to_string(),
+                        offset(&chars),
+                    ));
                 }
+                TokenKind::Identifier(ident)
             }
-            _ if ch.is_digit(10) => {
-                let mut number = String::new();
-                while let Some(&ch) = chars.peek() {
-                    if ch.is_digit(10) {
-                        number.push(ch);
-                        chars.next();
-                    } else {
-                        break;
-                    }
+            _ if ch.is_alphabetic() => {
+                let rest = chars.as_str();
+                let len = rest
+                    .find(|ch: char| !(ch.is_alphanumeric() || ch == '_'))
+                    .unwrap_or(rest.len());
+                let ident = &rest[..len];
+                advance(&mut chars, len);
+                if is_keyword(ident) {
+                    TokenKind::Keyword(ident.to_uppercase())
+                } else {
+                    TokenKind::Identifier(ident.to_string())
                 }
-                tokens.push(Token::Literal(number));
             }
+            _ if ch.is_ascii_digit() => TokenKind::Number(read_number(&mut chars)),
             _ => {
-                return Err(crate::errors::SQLError::InvalidSyntax(format!(
-                    "Unexpected character: '{}'",
-                    ch
-                )));
+                return Err(error(
+                    format!("Unexpected character: '{}'", ch),
+                    start + ch.len_utf8(),
+                ));
             }
-        }
+        };
+        let span = Span {
+            start,
+            end: offset(&chars),
+        };
+        tokens.push(Token { kind, span });
     }
 
-    tokens.push(Token::EOF);
+    let end = Span {
+        start: input.len(),
+        end: input.len(),
+    };
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        span: end,
+    });
     Ok(tokens)
 }
 
+/// Reads text enclosed in the quote character at the start of the input, where a
+/// doubled quote stands for one quote (`'O''Brien'`). `None` if the closing
+/// quote is missing.
+fn read_quoted(chars: &mut std::str::Chars) -> Option<String> {
+    let quote = chars.next()?;
+    let mut text = String::new();
+    loop {
+        let ch = chars.next()?;
+        if ch != quote {
+            text.push(ch);
+        } else if chars.as_str().starts_with(quote) {
+            text.push(quote);
+            chars.next();
+        } else {
+            return Some(text);
+        }
+    }
+}
+
+fn read_digits(chars: &mut std::str::Chars, number: &mut String) {
+    while let Some(ch) = chars.clone().next().filter(char::is_ascii_digit) {
+        number.push(ch);
+        chars.next();
+    }
+}
+
+/// Reads an unsigned numeric literal: digits with an optional fractional part
+/// and an optional exponent, as in `42`, `3.14`, `.5` or `1.5e-3`. A sign is a
+/// separate operator token.
+fn read_number(chars: &mut std::str::Chars) -> String {
+    let mut number = String::new();
+    read_digits(chars, &mut number);
+    let mut lookahead = chars.clone();
+    if lookahead.next() == Some('.') && lookahead.next().is_some_and(|ch| ch.is_ascii_digit()) {
+        number.push('.');
+        chars.next();
+        read_digits(chars, &mut number);
+    }
+
+    // Only take an exponent marker that is followed by digits, so `2e` stays a
+    // number followed by an identifier
+    let mut lookahead = chars.clone();
+    if let Some(marker @ ('e' | 'E')) = lookahead.next() {
+        let sign = lookahead
+            .clone()
+            .next()
+            .filter(|ch| matches!(ch, '+' | '-'));
+        if sign.is_some() {
+            lookahead.next();
+        }
+        if lookahead.next().is_some_and(|ch| ch.is_ascii_digit()) {
+            number.push(marker);
+            chars.next();
+            if let Some(sign) = sign {
+                number.push(sign);
+                chars.next();
+            }
+            read_digits(chars, &mut number);
+        }
+    }
+    number
+}
+
+/// The kind of the token at `index`, if there is one.
+fn peek(tokens: &[Token], index: usize) -> Option<&TokenKind> {
+    tokens.get(index).map(|token| &token.kind)
+}
+
 pub fn parse(tokens: &[Token]) -> Result<SQLQuery, SQLError> {
     let mut index = 0;
-    match tokens.get(index) {
-        Some(Token::Keyword(k)) if k == "SELECT" => parse_select(tokens, &mut index),
-        Some(Token::Keyword(k)) if k == "INSERT" => parse_insert(tokens, &mut index),
-        Some(Token::Keyword(k)) if k == "UPDATE" => parse_update(tokens, &mut index),
-        Some(Token::Keyword(k)) if k == "DELETE" => parse_delete(tokens, &mut index),
+    let query = match peek(tokens, index) {
+        Some(TokenKind::Keyword(k)) if k == "SELECT" => {
+            parse_select(tokens, &mut index).and_then(|select_query| match peek(tokens, index) {
+                // Expect semicolon or EOF
+                Some(TokenKind::Semicolon) | Some(TokenKind::Eof) => {
+                    Ok(SQLQuery::Select(select_query))
+                }
+                _ => Err(SQLError::InvalidSyntax(
+                    "Expected ';' at the end of query".to_string(),
+                )),
+            })
+        }
+        Some(TokenKind::Keyword(k)) if k == "INSERT" => parse_insert(tokens, &mut index),
+        Some(TokenKind::Keyword(k)) if k == "UPDATE" => parse_update(tokens, &mut index),
+        Some(TokenKind::Keyword(k)) if k == "DELETE" => parse_delete(tokens, &mut index),
         _ => Err(SQLError::InvalidSyntax(
             "Expected a SQL command".to_string(),
         )),
+    };
+    query.map_err(|error| locate(error, tokens, index))
+}
+
+/// Points a parse error at the token where parsing stopped. When that token
+/// looks like a misspelled keyword (`SELEC`), the error suggests the keyword,
+/// unless the message already names it.
+fn locate(error: SQLError, tokens: &[Token], index: usize) -> SQLError {
+    let Some(last) = tokens.len().checked_sub(1) else {
+        return error;
+    };
+    let token = &tokens[index.min(last)];
+    let misspelled = match &token.kind {
+        TokenKind::Identifier(word) => closest_match(&word.to_uppercase(), KEYWORDS),
+        _ => None,
+    };
+    match (misspelled, error) {
+        (Some(keyword), SQLError::InvalidSyntax(message))
+            if !message.contains(&format!("'{}'", keyword)) =>
+        {
+            SQLError::InvalidSyntax(format!("{} (did you mean '{}'?)", message, keyword))
+                .at(token.span)
+        }
+        (_, error) => error.at(token.span),
     }
 }
 
-fn parse_select(tokens: &[Token], index: &mut usize) -> Result<SQLQuery, crate::errors::SQLError> {
+/// Parses a SELECT query up to the end of its last clause, leaving what follows
+/// it to the caller.
+fn parse_select(
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<SelectQuery, crate::errors::SQLError> {
     *index += 1; // Skip 'SELECT'
 
+    let distinct = parse_distinct(tokens, index)?;
     let columns = parse_select_list(tokens, index)?;
 
     // Expect 'FROM'
-    match tokens.get(*index) {
-        Some(Token::Keyword(k)) if k == "FROM" => *index += 1,
+    match peek(tokens, *index) {
+        Some(TokenKind::Keyword(k)) if k == "FROM" => *index += 1,
         _ => {
             return Err(crate::errors::SQLError::InvalidSyntax(
                 "Expected 'FROM' keyword".to_string(),
@@ -197,21 +999,40 @@ fn parse_select(tokens: &[Token], index: &mut usize) -> Result<SQLQuery, crate::
         }
     }
 
-    // Expect table name
-    let table = match tokens.get(*index) {
-        Some(Token::Identifier(name)) => {
-            *index += 1;
-            name.clone()
-        }
-        _ => {
-            return Err(crate::errors::SQLError::InvalidSyntax(
-                "Expected table name".to_string(),
-            ))
+    // Expect table name, or a subquery named by its alias
+    let (table, alias, subquery) = if starts_subquery(tokens, *index) {
+        let subquery = parse_subquery(tokens, index)?;
+        match parse_alias(tokens, index)? {
+            Some(alias) => (alias, None, Some(Box::new(subquery))),
+            None => {
+                return Err(crate::errors::SQLError::InvalidSyntax(
+                    "Expected an alias for the subquery in FROM".to_string(),
+                ))
+            }
         }
+    } else {
+        let table = match peek(tokens, *index) {
+            Some(TokenKind::Identifier(name)) => {
+                *index += 1;
+                name.clone()
+            }
+            _ => {
+                return Err(crate::errors::SQLError::InvalidSyntax(
+                    "Expected table name".to_string(),
+                ))
+            }
+        };
+        (table, parse_alias(tokens, index)?, None)
     };
 
+    // Parse optional JOIN clauses
+    let mut joins = Vec::new();
+    while let Some(kind) = parse_join_kind(tokens, index)? {
+        joins.push(parse_join(kind, tokens, index)?);
+    }
+
     // Parse optional WHERE clause
-    let where_clause = if let Some(Token::Keyword(k)) = tokens.get(*index) {
+    let where_clause = if let Some(TokenKind::Keyword(k)) = peek(tokens, *index) {
         if k == "WHERE" {
             *index += 1;
             Some(parse_expression(tokens, index)?)
@@ -222,19 +1043,31 @@ fn parse_select(tokens: &[Token], index: &mut usize) -> Result<SQLQuery, crate::
         None
     };
 
-    // Parse optional ORDER BY clause
-    let order_by = if let Some(Token::Keyword(k)) = tokens.get(*index) {
-        if k == "ORDER" {
+    // Parse optional GROUP BY clause
+    let group_by = if let Some(TokenKind::Keyword(k)) = peek(tokens, *index) {
+        if k == "GROUP" {
             *index += 1;
-            match tokens.get(*index) {
-                Some(Token::Keyword(k)) if k == "BY" => *index += 1,
+            match peek(tokens, *index) {
+                Some(TokenKind::Keyword(k)) if k == "BY" => *index += 1,
                 _ => {
                     return Err(crate::errors::SQLError::InvalidSyntax(
-                        "Expected 'BY' after 'ORDER'".to_string(),
+                        "Expected 'BY' after 'GROUP'".to_string(),
                     ))
                 }
             }
-            Some(parse_order_by(tokens, index)?)
+            parse_expression_list(tokens, index)?
+        } else {
+            Vec::new()
+        }
+    } else {
+        Vec::new()
+    };
+
+    // Parse optional HAVING clause
+    let having = if let Some(TokenKind::Keyword(k)) = peek(tokens, *index) {
+        if k == "HAVING" {
+            *index += 1;
+            Some(parse_expression(tokens, index)?)
         } else {
             None
         }
@@ -242,49 +1075,112 @@ fn parse_select(tokens: &[Token], index: &mut usize) -> Result<SQLQuery, crate::
         None
     };
 
-    // Expect semicolon or EOF
-    match tokens.get(*index) {
-        Some(Token::Semicolon) | Some(Token::EOF) => {}
-        _ => {
-            return Err(crate::errors::SQLError::InvalidSyntax(
-                "Expected ';' at the end of query".to_string(),
-            ))
+    // Parse optional ORDER BY clause
+    let order_by = if let Some(TokenKind::Keyword(k)) = peek(tokens, *index) {
+        if k == "ORDER" {
+            *index += 1;
+            match peek(tokens, *index) {
+                Some(TokenKind::Keyword(k)) if k == "BY" => *index += 1,
+                _ => {
+                    return Err(crate::errors::SQLError::InvalidSyntax(
+                        "Expected 'BY' after 'ORDER'".to_string(),
+                    ))
+                }
+            }
+            parse_order_by(tokens, index)?
+        } else {
+            Vec::new()
         }
-    }
+    } else {
+        Vec::new()
+    };
+
+    // Parse optional LIMIT, OFFSET and FETCH FIRST clauses
+    let (limit, offset) = parse_limit(tokens, index)?;
 
-    Ok(SQLQuery::Select(SelectQuery {
+    Ok(SelectQuery {
+        distinct,
         columns,
         table,
+        alias,
+        subquery,
+        joins,
         where_clause,
+        group_by,
+        having,
         order_by,
-    }))
+        limit,
+        offset,
+    })
 }
 
-fn parse_select_list(
+/// Parses an optional `DISTINCT` or `DISTINCT ON (<expr>, ...)` after SELECT.
+fn parse_distinct(
     tokens: &[Token],
     index: &mut usize,
-) -> Result<Vec<String>, crate::errors::SQLError> {
-    let mut columns = Vec::new();
+) -> Result<Distinct, crate::errors::SQLError> {
+    match peek(tokens, *index) {
+        Some(TokenKind::Keyword(k)) if k == "DISTINCT" => *index += 1,
+        _ => return Ok(Distinct::All),
+    }
+    match peek(tokens, *index) {
+        Some(TokenKind::Keyword(k)) if k == "ON" => *index += 1,
+        _ => return Ok(Distinct::Rows),
+    }
+
+    match peek(tokens, *index) {
+        Some(TokenKind::OpenParen) => *index += 1,
+        _ => {
+            return Err(crate::errors::SQLError::InvalidSyntax(
+                "Expected '(' after 'DISTINCT ON'".to_string(),
+            ))
+        }
+    }
+    let expressions = parse_expression_list(tokens, index)?;
+    match peek(tokens, *index) {
+        Some(TokenKind::CloseParen) => *index += 1,
+        _ => {
+            return Err(crate::errors::SQLError::InvalidSyntax(
+                "Expected ')' after DISTINCT ON expressions".to_string(),
+            ))
+        }
+    }
+    Ok(Distinct::On(expressions))
+}
+
+fn parse_select_list(
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<Vec<SelectItem>, crate::errors::SQLError> {
+    let mut columns = Vec::new();
 
     loop {
-        match tokens.get(*index) {
-            Some(Token::Asterisk) => {
+        match peek(tokens, *index) {
+            Some(TokenKind::Asterisk) => {
                 *index += 1;
-                columns.push("*".to_string());
+                columns.push(SelectItem::Wildcard);
             }
-            Some(Token::Identifier(name)) => {
-                *index += 1;
-                columns.push(name.clone());
+            Some(TokenKind::Identifier(table))
+                if peek(tokens, *index + 1) == Some(&TokenKind::Dot)
+                    && peek(tokens, *index + 2) == Some(&TokenKind::Asterisk) =>
+            {
+                *index += 3;
+                columns.push(SelectItem::QualifiedWildcard(table.clone()));
             }
-            _ => {
+            Some(TokenKind::Keyword(k)) if k == "FROM" => {
                 return Err(crate::errors::SQLError::InvalidSyntax(
                     "Expected column name or '*'".to_string(),
                 ))
             }
+            _ => {
+                let expr = parse_expression(tokens, index)?;
+                let alias = parse_alias(tokens, index)?;
+                columns.push(SelectItem::Expression { expr, alias });
+            }
         }
 
-        match tokens.get(*index) {
-            Some(Token::Comma) => *index += 1,
+        match peek(tokens, *index) {
+            Some(TokenKind::Comma) => *index += 1,
             _ => break,
         }
     }
@@ -292,12 +1188,139 @@ fn parse_select_list(
     Ok(columns)
 }
 
+/// Parses an optional table or column alias, written either `AS alias` or just
+/// `alias`.
+fn parse_alias(
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<Option<String>, crate::errors::SQLError> {
+    let has_as = matches!(peek(tokens, *index), Some(TokenKind::Keyword(k)) if k == "AS");
+    if has_as {
+        *index += 1;
+    }
+    match peek(tokens, *index) {
+        Some(TokenKind::Identifier(alias)) => {
+            *index += 1;
+            Ok(Some(alias.clone()))
+        }
+        _ if has_as => Err(crate::errors::SQLError::InvalidSyntax(
+            "Expected alias after 'AS'".to_string(),
+        )),
+        _ => Ok(None),
+    }
+}
+
+/// Parses the keywords introducing a join, `[INNER] JOIN` or
+/// `{LEFT | RIGHT | FULL} [OUTER] JOIN`, if there is one.
+fn parse_join_kind(
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<Option<JoinKind>, crate::errors::SQLError> {
+    let kind = match peek(tokens, *index) {
+        Some(TokenKind::Keyword(k)) if k == "JOIN" => return Ok(Some(JoinKind::Inner)),
+        Some(TokenKind::Keyword(k)) if k == "INNER" => JoinKind::Inner,
+        Some(TokenKind::Keyword(k)) if k == "LEFT" => JoinKind::Left,
+        Some(TokenKind::Keyword(k)) if k == "RIGHT" => JoinKind::Right,
+        Some(TokenKind::Keyword(k)) if k == "FULL" => JoinKind::Full,
+        _ => return Ok(None),
+    };
+    *index += 1;
+
+    if kind != JoinKind::Inner {
+        if let Some(TokenKind::Keyword(k)) = peek(tokens, *index) {
+            if k == "OUTER" {
+                *index += 1;
+            }
+        }
+    }
+
+    match peek(tokens, *index) {
+        Some(TokenKind::Keyword(k)) if k == "JOIN" => Ok(Some(kind)),
+        _ => Err(crate::errors::SQLError::InvalidSyntax(
+            "Expected 'JOIN' keyword".to_string(),
+        )),
+    }
+}
+
+fn parse_join(
+    kind: JoinKind,
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<Join, crate::errors::SQLError> {
+    *index += 1; // Skip 'JOIN'
+
+    let table = match peek(tokens, *index) {
+        Some(TokenKind::Identifier(name)) => {
+            *index += 1;
+            name.clone()
+        }
+        _ => {
+            return Err(crate::errors::SQLError::InvalidSyntax(
+                "Expected table name after 'JOIN'".to_string(),
+            ))
+        }
+    };
+    let alias = parse_alias(tokens, index)?;
+
+    match peek(tokens, *index) {
+        Some(TokenKind::Keyword(k)) if k == "ON" => *index += 1,
+        _ => {
+            return Err(crate::errors::SQLError::InvalidSyntax(
+                "Expected 'ON' after joined table".to_string(),
+            ))
+        }
+    }
+    let condition = parse_expression(tokens, index)?;
+
+    Ok(Join {
+        kind,
+        table,
+        alias,
+        condition,
+    })
+}
+
+fn starts_subquery(tokens: &[Token], index: usize) -> bool {
+    peek(tokens, index) == Some(&TokenKind::OpenParen)
+        && matches!(peek(tokens, index + 1), Some(TokenKind::Keyword(k)) if k == "SELECT")
+}
+
+/// Parses a SELECT query in parentheses, starting at the opening one.
+fn parse_subquery(
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<SelectQuery, crate::errors::SQLError> {
+    *index += 1; // Skip '('
+    let query = parse_select(tokens, index)?;
+    match peek(tokens, *index) {
+        Some(TokenKind::CloseParen) => *index += 1, // Skip ')'
+        _ => {
+            return Err(crate::errors::SQLError::InvalidSyntax(
+                "Expected ')' after subquery".to_string(),
+            ))
+        }
+    }
+    Ok(query)
+}
+
+fn parse_expression_list(
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<Vec<Expression>, crate::errors::SQLError> {
+    let mut expressions = vec![parse_expression(tokens, index)?];
+    while let Some(TokenKind::Comma) = peek(tokens, *index) {
+        *index += 1;
+        expressions.push(parse_expression(tokens, index)?);
+    }
+    Ok(expressions)
+}
+
 fn parse_insert(tokens: &[Token], index: &mut usize) -> Result<SQLQuery, SQLError> {
     *index += 1; // Skip 'INSERT'
 
     // Expect 'INTO'
-    match tokens.get(*index) {
-        Some(Token::Keyword(k)) if k == "INTO" => *index += 1,
+    match peek(tokens, *index) {
+        Some(TokenKind::Keyword(k)) if k == "INTO" => *index += 1,
         _ => {
             return Err(SQLError::InvalidSyntax(
                 "Expected 'INTO' keyword".to_string(),
@@ -306,8 +1329,8 @@ fn parse_insert(tokens: &[Token], index: &mut usize) -> Result<SQLQuery, SQLErro
     }
 
     // Expect table name
-    let table = match tokens.get(*index) {
-        Some(Token::Identifier(name)) => {
+    let table = match peek(tokens, *index) {
+        Some(TokenKind::Identifier(name)) => {
             *index += 1;
             name.clone()
         }
@@ -319,11 +1342,11 @@ fn parse_insert(tokens: &[Token], index: &mut usize) -> Result<SQLQuery, SQLErro
     };
 
     // Parse optional column list
-    let columns = if let Some(Token::OpenParen) = tokens.get(*index) {
+    let columns = if let Some(TokenKind::OpenParen) = peek(tokens, *index) {
         *index += 1; // Skip '('
         let cols = parse_column_list(tokens, index)?;
-        match tokens.get(*index) {
-            Some(Token::CloseParen) => *index += 1, // Skip ')'
+        match peek(tokens, *index) {
+            Some(TokenKind::CloseParen) => *index += 1, // Skip ')'
             _ => {
                 return Err(SQLError::InvalidSyntax(
                     "Expected ')' after column list".to_string(),
@@ -335,40 +1358,27 @@ fn parse_insert(tokens: &[Token], index: &mut usize) -> Result<SQLQuery, SQLErro
         Vec::new()
     };
 
-    // Expect 'VALUES' keyword
-    match tokens.get(*index) {
-        Some(Token::Keyword(k)) if k == "VALUES" => *index += 1,
-        _ => {
-            return Err(SQLError::InvalidSyntax(
-                "Expected 'VALUES' keyword".to_string(),
-            ))
+    // Expect 'VALUES' or a SELECT query
+    let source = match peek(tokens, *index) {
+        Some(TokenKind::Keyword(k)) if k == "VALUES" => {
+            *index += 1;
+            InsertSource::Values(parse_values_rows(tokens, index)?)
         }
-    }
-
-    // Parse values list
-    match tokens.get(*index) {
-        Some(Token::OpenParen) => *index += 1, // Skip '('
-        _ => {
-            return Err(SQLError::InvalidSyntax(
-                "Expected '(' before values list".to_string(),
-            ))
+        Some(TokenKind::Keyword(k)) if k == "SELECT" => {
+            InsertSource::Select(Box::new(parse_select(tokens, index)?))
         }
-    }
-
-    let values = parse_values_list(tokens, index)?;
-
-    match tokens.get(*index) {
-        Some(Token::CloseParen) => *index += 1, // Skip ')'
         _ => {
             return Err(SQLError::InvalidSyntax(
-                "Expected ')' after values list".to_string(),
+                "Expected 'VALUES' keyword or a SELECT query".to_string(),
             ))
         }
-    }
+    };
+
+    let returning = parse_returning(tokens, index)?;
 
     // Expect semicolon or EOF
-    match tokens.get(*index) {
-        Some(Token::Semicolon) | Some(Token::EOF) => {}
+    match peek(tokens, *index) {
+        Some(TokenKind::Semicolon) | Some(TokenKind::Eof) => {}
         _ => {
             return Err(SQLError::InvalidSyntax(
                 "Expected ';' at the end of the query".to_string(),
@@ -379,15 +1389,16 @@ fn parse_insert(tokens: &[Token], index: &mut usize) -> Result<SQLQuery, SQLErro
     Ok(SQLQuery::Insert(InsertQuery {
         table,
         columns,
-        values,
+        source,
+        returning,
     }))
 }
 
 fn parse_column_list(tokens: &[Token], index: &mut usize) -> Result<Vec<String>, SQLError> {
     let mut columns = Vec::new();
     loop {
-        match tokens.get(*index) {
-            Some(Token::Identifier(name)) => {
+        match peek(tokens, *index) {
+            Some(TokenKind::Identifier(name)) => {
                 columns.push(name.clone());
                 *index += 1;
             }
@@ -398,9 +1409,9 @@ fn parse_column_list(tokens: &[Token], index: &mut usize) -> Result<Vec<String>,
             }
         }
 
-        match tokens.get(*index) {
-            Some(Token::Comma) => *index += 1,
-            Some(Token::CloseParen) => break,
+        match peek(tokens, *index) {
+            Some(TokenKind::Comma) => *index += 1,
+            Some(TokenKind::CloseParen) => break,
             _ => {
                 return Err(SQLError::InvalidSyntax(
                     "Expected ',' or ')' in column list".to_string(),
@@ -411,14 +1422,43 @@ fn parse_column_list(tokens: &[Token], index: &mut usize) -> Result<Vec<String>,
     Ok(columns)
 }
 
-fn parse_values_list(tokens: &[Token], index: &mut usize) -> Result<Vec<String>, SQLError> {
-    let mut values = Vec::new();
+/// Parses one or more comma-separated parenthesized values lists.
+fn parse_values_rows(tokens: &[Token], index: &mut usize) -> Result<Vec<Vec<Value>>, SQLError> {
+    let mut rows = Vec::new();
     loop {
-        match tokens.get(*index) {
-            Some(Token::Literal(value)) => {
-                values.push(value.clone());
-                *index += 1;
+        match peek(tokens, *index) {
+            Some(TokenKind::OpenParen) => *index += 1, // Skip '('
+            _ => {
+                return Err(SQLError::InvalidSyntax(
+                    "Expected '(' before values list".to_string(),
+                ))
             }
+        }
+
+        rows.push(parse_values_list(tokens, index)?);
+
+        match peek(tokens, *index) {
+            Some(TokenKind::CloseParen) => *index += 1, // Skip ')'
+            _ => {
+                return Err(SQLError::InvalidSyntax(
+                    "Expected ')' after values list".to_string(),
+                ))
+            }
+        }
+
+        match peek(tokens, *index) {
+            Some(TokenKind::Comma) => *index += 1,
+            _ => break,
+        }
+    }
+    Ok(rows)
+}
+
+fn parse_values_list(tokens: &[Token], index: &mut usize) -> Result<Vec<Value>, SQLError> {
+    let mut values = Vec::new();
+    loop {
+        match parse_literal(tokens, index) {
+            Some(value) => values.push(value?),
             _ => {
                 return Err(SQLError::InvalidSyntax(
                     "Expected literal value in values list".to_string(),
@@ -426,9 +1466,9 @@ fn parse_values_list(tokens: &[Token], index: &mut usize) -> Result<Vec<String>,
             }
         }
 
-        match tokens.get(*index) {
-            Some(Token::Comma) => *index += 1,
-            Some(Token::CloseParen) => break,
+        match peek(tokens, *index) {
+            Some(TokenKind::Comma) => *index += 1,
+            Some(TokenKind::CloseParen) => break,
             _ => {
                 return Err(SQLError::InvalidSyntax(
                     "Expected ',' or ')' in values list".to_string(),
@@ -443,8 +1483,8 @@ fn parse_update(tokens: &[Token], index: &mut usize) -> Result<SQLQuery, SQLErro
     *index += 1; // Skip 'UPDATE'
 
     // Expect table name
-    let table = match tokens.get(*index) {
-        Some(Token::Identifier(name)) => {
+    let table = match peek(tokens, *index) {
+        Some(TokenKind::Identifier(name)) => {
             *index += 1;
             name.clone()
         }
@@ -456,8 +1496,8 @@ fn parse_update(tokens: &[Token], index: &mut usize) -> Result<SQLQuery, SQLErro
     };
 
     // Expect 'SET' keyword
-    match tokens.get(*index) {
-        Some(Token::Keyword(k)) if k == "SET" => *index += 1,
+    match peek(tokens, *index) {
+        Some(TokenKind::Keyword(k)) if k == "SET" => *index += 1,
         _ => {
             return Err(SQLError::InvalidSyntax(
                 "Expected 'SET' keyword".to_string(),
@@ -469,7 +1509,7 @@ fn parse_update(tokens: &[Token], index: &mut usize) -> Result<SQLQuery, SQLErro
     let assignments = parse_assignments(tokens, index)?;
 
     // Parse optional WHERE clause
-    let where_clause = if let Some(Token::Keyword(k)) = tokens.get(*index) {
+    let where_clause = if let Some(TokenKind::Keyword(k)) = peek(tokens, *index) {
         if k == "WHERE" {
             *index += 1;
             Some(parse_expression(tokens, index)?)
@@ -480,9 +1520,11 @@ fn parse_update(tokens: &[Token], index: &mut usize) -> Result<SQLQuery, SQLErro
         None
     };
 
+    let returning = parse_returning(tokens, index)?;
+
     // Expect semicolon or EOF
-    match tokens.get(*index) {
-        Some(Token::Semicolon) | Some(Token::EOF) => {}
+    match peek(tokens, *index) {
+        Some(TokenKind::Semicolon) | Some(TokenKind::Eof) => {}
         _ => {
             return Err(SQLError::InvalidSyntax(
                 "Expected ';' at the end of the query".to_string(),
@@ -494,6 +1536,7 @@ fn parse_update(tokens: &[Token], index: &mut usize) -> Result<SQLQuery, SQLErro
         table,
         assignments,
         where_clause,
+        returning,
     }))
 }
 
@@ -501,8 +1544,8 @@ fn parse_assignments(tokens: &[Token], index: &mut usize) -> Result<Vec<Assignme
     let mut assignments = Vec::new();
     loop {
         // Expect column name
-        let column = match tokens.get(*index) {
-            Some(Token::Identifier(name)) => {
+        let column = match peek(tokens, *index) {
+            Some(TokenKind::Identifier(name)) => {
                 *index += 1;
                 name.clone()
             }
@@ -514,8 +1557,8 @@ fn parse_assignments(tokens: &[Token], index: &mut usize) -> Result<Vec<Assignme
         };
 
         // Expect '=' operator
-        match tokens.get(*index) {
-            Some(Token::Operator(op)) if op == "=" => *index += 1,
+        match peek(tokens, *index) {
+            Some(TokenKind::Operator(op)) if op == "=" => *index += 1,
             _ => {
                 return Err(SQLError::InvalidSyntax(
                     "Expected '=' in assignment".to_string(),
@@ -523,24 +1566,13 @@ fn parse_assignments(tokens: &[Token], index: &mut usize) -> Result<Vec<Assignme
             }
         }
 
-        // Expect literal value
-        let value = match tokens.get(*index) {
-            Some(Token::Literal(val)) => {
-                *index += 1;
-                val.clone()
-            }
-            _ => {
-                return Err(SQLError::InvalidSyntax(
-                    "Expected literal value in assignment".to_string(),
-                ))
-            }
-        };
+        let value = parse_expression(tokens, index)?;
 
         assignments.push(Assignment { column, value });
 
         // Check for comma or end
-        match tokens.get(*index) {
-            Some(Token::Comma) => *index += 1,
+        match peek(tokens, *index) {
+            Some(TokenKind::Comma) => *index += 1,
             _ => break,
         }
     }
@@ -551,8 +1583,8 @@ fn parse_delete(tokens: &[Token], index: &mut usize) -> Result<SQLQuery, SQLErro
     *index += 1; // Skip 'DELETE'
 
     // Expect 'FROM'
-    match tokens.get(*index) {
-        Some(Token::Keyword(k)) if k == "FROM" => *index += 1,
+    match peek(tokens, *index) {
+        Some(TokenKind::Keyword(k)) if k == "FROM" => *index += 1,
         _ => {
             return Err(SQLError::InvalidSyntax(
                 "Expected 'FROM' keyword after 'DELETE'".to_string(),
@@ -561,8 +1593,8 @@ fn parse_delete(tokens: &[Token], index: &mut usize) -> Result<SQLQuery, SQLErro
     }
 
     // Expect table name
-    let table = match tokens.get(*index) {
-        Some(Token::Identifier(name)) => {
+    let table = match peek(tokens, *index) {
+        Some(TokenKind::Identifier(name)) => {
             *index += 1;
             name.clone()
         }
@@ -574,7 +1606,7 @@ fn parse_delete(tokens: &[Token], index: &mut usize) -> Result<SQLQuery, SQLErro
     };
 
     // Parse optional WHERE clause
-    let where_clause = if let Some(Token::Keyword(k)) = tokens.get(*index) {
+    let where_clause = if let Some(TokenKind::Keyword(k)) = peek(tokens, *index) {
         if k == "WHERE" {
             *index += 1;
             Some(parse_expression(tokens, index)?)
@@ -585,9 +1617,11 @@ fn parse_delete(tokens: &[Token], index: &mut usize) -> Result<SQLQuery, SQLErro
         None
     };
 
+    let returning = parse_returning(tokens, index)?;
+
     // Expect semicolon or EOF
-    match tokens.get(*index) {
-        Some(Token::Semicolon) | Some(Token::EOF) => {}
+    match peek(tokens, *index) {
+        Some(TokenKind::Semicolon) | Some(TokenKind::Eof) => {}
         _ => {
             return Err(SQLError::InvalidSyntax(
                 "Expected ';' at the end of the query".to_string(),
@@ -598,121 +1632,1728 @@ fn parse_delete(tokens: &[Token], index: &mut usize) -> Result<SQLQuery, SQLErro
     Ok(SQLQuery::Delete(DeleteQuery {
         table,
         where_clause,
+        returning,
     }))
 }
 
-fn parse_order_by(tokens: &[Token], index: &mut usize) -> Result<OrderBy, crate::errors::SQLError> {
-    // Expect column name
-    let column = match tokens.get(*index) {
-        Some(Token::Identifier(name)) => {
+/// Parses an optional `RETURNING` clause, returning an empty list without one.
+fn parse_returning(tokens: &[Token], index: &mut usize) -> Result<Vec<SelectItem>, SQLError> {
+    match peek(tokens, *index) {
+        Some(TokenKind::Keyword(k)) if k == "RETURNING" => {
             *index += 1;
-            name.clone()
+            parse_select_list(tokens, index)
         }
-        _ => {
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Parses a comma-separated list of sort keys.
+fn parse_order_by(
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<Vec<OrderBy>, crate::errors::SQLError> {
+    let mut keys = Vec::new();
+    loop {
+        keys.push(parse_sort_key(tokens, index)?);
+        match peek(tokens, *index) {
+            Some(TokenKind::Comma) => *index += 1,
+            _ => break,
+        }
+    }
+    Ok(keys)
+}
+
+fn parse_sort_key(tokens: &[Token], index: &mut usize) -> Result<OrderBy, crate::errors::SQLError> {
+    let expression = match peek(tokens, *index) {
+        Some(TokenKind::Semicolon) | Some(TokenKind::Eof) | Some(TokenKind::Comma) => {
             return Err(crate::errors::SQLError::InvalidSyntax(
-                "Expected column name in ORDER BY".to_string(),
+                "Expected expression in ORDER BY".to_string(),
             ))
         }
+        _ => parse_expression(tokens, index)?,
     };
 
     // Optional ASC/DESC
-    let ascending = match tokens.get(*index) {
-        Some(Token::Keyword(k)) if k == "ASC" => {
+    let ascending = match peek(tokens, *index) {
+        Some(TokenKind::Keyword(k)) if k == "ASC" => {
             *index += 1;
             true
         }
-        Some(Token::Keyword(k)) if k == "DESC" => {
+        Some(TokenKind::Keyword(k)) if k == "DESC" => {
             *index += 1;
             false
         }
         _ => true, // Default to ASC
     };
 
-    Ok(OrderBy { column, ascending })
+    // Optional NULLS FIRST/LAST. NULL sorts as the largest value by default, so it
+    // comes last in ascending order and first in descending order.
+    let nulls_first = match peek(tokens, *index) {
+        Some(TokenKind::Keyword(k)) if k == "NULLS" => {
+            *index += 1;
+            match peek(tokens, *index) {
+                Some(TokenKind::Identifier(word)) if word.eq_ignore_ascii_case("FIRST") => {
+                    *index += 1;
+                    true
+                }
+                Some(TokenKind::Identifier(word)) if word.eq_ignore_ascii_case("LAST") => {
+                    *index += 1;
+                    false
+                }
+                _ => {
+                    return Err(crate::errors::SQLError::InvalidSyntax(
+                        "Expected 'FIRST' or 'LAST' after 'NULLS'".to_string(),
+                    ))
+                }
+            }
+        }
+        _ => !ascending,
+    };
+
+    Ok(OrderBy {
+        expression,
+        ascending,
+        nulls_first,
+    })
+}
+
+/// Parses the clauses that restrict the rows returned, in any order:
+///
+/// ```text
+/// LIMIT <n>
+/// OFFSET <m> [ROW | ROWS]
+/// FETCH {FIRST | NEXT} [<n>] {ROW | ROWS} ONLY
+/// ```
+///
+/// LIMIT and FETCH are two spellings of the same limit, so only one may be used.
+fn parse_limit(
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<(Option<usize>, usize), crate::errors::SQLError> {
+    let mut limit = None;
+    let mut offset = None;
+    while let Some(TokenKind::Keyword(k)) = peek(tokens, *index) {
+        let clause = k.clone();
+        match clause.as_str() {
+            "LIMIT" | "FETCH" if limit.is_some() => {
+                return Err(crate::errors::SQLError::InvalidSyntax(format!(
+                    "Unexpected '{}': the query already has a row limit",
+                    clause
+                )))
+            }
+            "OFFSET" if offset.is_some() => {
+                return Err(crate::errors::SQLError::InvalidSyntax(
+                    "Unexpected second 'OFFSET'".to_string(),
+                ))
+            }
+            "LIMIT" => {
+                *index += 1;
+                limit = Some(parse_row_count(tokens, index, &clause)?);
+            }
+            "OFFSET" => {
+                *index += 1;
+                offset = Some(parse_row_count(tokens, index, &clause)?);
+                if next_word_is(tokens, index, &["ROW", "ROWS"]) {
+                    *index += 1;
+                }
+            }
+            "FETCH" => {
+                *index += 1;
+                if !next_word_is(tokens, index, &["FIRST", "NEXT"]) {
+                    return Err(crate::errors::SQLError::InvalidSyntax(
+                        "Expected 'FIRST' or 'NEXT' after 'FETCH'".to_string(),
+                    ));
+                }
+                *index += 1;
+                // The row count may be left out, meaning a single row
+                let count = match peek(tokens, *index) {
+                    Some(TokenKind::Number(_)) => parse_row_count(tokens, index, &clause)?,
+                    _ => 1,
+                };
+                for expected in [&["ROW", "ROWS"][..], &["ONLY"][..]] {
+                    if !next_word_is(tokens, index, expected) {
+                        return Err(crate::errors::SQLError::InvalidSyntax(format!(
+                            "Expected '{}' in FETCH clause",
+                            expected.join("' or '")
+                        )));
+                    }
+                    *index += 1;
+                }
+                limit = Some(count);
+            }
+            _ => break,
+        }
+    }
+    Ok((limit, offset.unwrap_or(0)))
+}
+
+fn parse_row_count(
+    tokens: &[Token],
+    index: &mut usize,
+    clause: &str,
+) -> Result<usize, crate::errors::SQLError> {
+    match peek(tokens, *index) {
+        Some(TokenKind::Number(number)) => {
+            let count = number.parse::<usize>().map_err(|_| {
+                crate::errors::SQLError::InvalidSyntax(format!(
+                    "Invalid row count '{}' in {}",
+                    number, clause
+                ))
+            })?;
+            *index += 1;
+            Ok(count)
+        }
+        _ => Err(crate::errors::SQLError::InvalidSyntax(format!(
+            "Expected a row count after '{}'",
+            clause
+        ))),
+    }
+}
+
+/// Whether the next token is one of the given words, which are not keywords of
+/// their own, in any case.
+fn next_word_is(tokens: &[Token], index: &usize, words: &[&str]) -> bool {
+    matches!(
+        peek(tokens, *index),
+        Some(TokenKind::Identifier(word)) if words.iter().any(|w| word.eq_ignore_ascii_case(w))
+    )
+}
+
+// Binding powers for the precedence-climbing expression parser. Higher binds tighter.
+const PRECEDENCE_OR: u8 = 1;
+const PRECEDENCE_AND: u8 = 2;
+const PRECEDENCE_NOT: u8 = 3;
+const PRECEDENCE_COMPARISON: u8 = 4;
+const PRECEDENCE_CONCAT: u8 = 5;
+const PRECEDENCE_ADDITIVE: u8 = 6;
+const PRECEDENCE_MULTIPLICATIVE: u8 = 7;
+const PRECEDENCE_UNARY: u8 = 8;
+const PRECEDENCE_CAST: u8 = 9;
+
+/// Binding power of a binary operator, used both to parse it and to decide
+/// where an expression needs parentheses when it is printed.
+fn binary_precedence(op: &str) -> u8 {
+    match op {
+        "OR" => PRECEDENCE_OR,
+        "AND" => PRECEDENCE_AND,
+        "||" => PRECEDENCE_CONCAT,
+        "+" | "-" => PRECEDENCE_ADDITIVE,
+        "*" | "/" | "%" => PRECEDENCE_MULTIPLICATIVE,
+        _ => PRECEDENCE_COMPARISON,
+    }
+}
+
+/// Whether `op` compares its operands, giving a boolean.
+pub fn is_comparison_operator(op: &str) -> bool {
+    matches!(op, "=" | "<" | ">" | "<=" | ">=" | "<>" | "!=")
+}
+
+/// Whether `op` is an arithmetic rather than a comparison or logical operator.
+pub fn is_arithmetic_operator(op: &str) -> bool {
+    matches!(op, "+" | "-" | "*" | "/" | "%")
 }
 
 fn parse_expression(
     tokens: &[Token],
     index: &mut usize,
 ) -> Result<Expression, crate::errors::SQLError> {
-    // For simplicity, parse expressions of the form: column operator literal
-    let left = match tokens.get(*index) {
-        Some(Token::Identifier(name)) => {
+    parse_expression_with_precedence(tokens, index, 0)
+}
+
+fn parse_expression_with_precedence(
+    tokens: &[Token],
+    index: &mut usize,
+    min_precedence: u8,
+) -> Result<Expression, crate::errors::SQLError> {
+    let mut left = parse_prefix(tokens, index)?;
+
+    // Fold operators into the left operand while they bind at least as tightly as
+    // the caller allows; the right operand only takes strictly tighter operators,
+    // which makes every binary operator left-associative.
+    loop {
+        // `::type` is a postfix operator binding tighter than any other
+        if matches!(peek(tokens, *index), Some(TokenKind::Operator(op)) if op == "::") {
+            if PRECEDENCE_CAST < min_precedence {
+                break;
+            }
             *index += 1;
-            Expression::Column(name.clone())
+            left = Expression::Cast {
+                operand: Box::new(left),
+                data_type: parse_data_type(tokens, index)?,
+            };
+            continue;
         }
-        _ => {
-            return Err(crate::errors::SQLError::InvalidSyntax(
-                "Expected column name in expression".to_string(),
-            ))
+
+        // `IS [NOT] NULL` is a postfix operator at comparison precedence
+        if matches!(peek(tokens, *index), Some(TokenKind::Keyword(k)) if k == "IS") {
+            if PRECEDENCE_COMPARISON < min_precedence {
+                break;
+            }
+            left = parse_is_null(left, tokens, index)?;
+            continue;
         }
-    };
 
-    // Expect operator
-    let op = match tokens.get(*index) {
-        Some(Token::Operator(op)) => {
-            *index += 1;
-            op.clone()
+        // So are `[NOT] IN`, `[NOT] BETWEEN` and `[NOT] [I]LIKE`
+        if starts_predicate(tokens, *index) {
+            if PRECEDENCE_COMPARISON < min_precedence {
+                break;
+            }
+            left = parse_predicate(left, tokens, index)?;
+            continue;
         }
-        _ => {
-            return Err(crate::errors::SQLError::InvalidSyntax(
-                "Expected operator in expression".to_string(),
-            ))
+
+        let Some((op, precedence)) = infix_operator(peek(tokens, *index)) else {
+            break;
+        };
+        if precedence < min_precedence {
+            break;
         }
-    };
+        *index += 1;
+        let right = parse_expression_with_precedence(tokens, index, precedence + 1)?;
+        left = Expression::BinaryOp {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        };
+    }
+
+    Ok(left)
+}
 
-    // Expect literal
-    let right = match tokens.get(*index) {
-        Some(Token::Literal(value)) => {
+fn parse_data_type(
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<DataType, crate::errors::SQLError> {
+    match peek(tokens, *index) {
+        Some(TokenKind::Identifier(name)) => {
+            let data_type = DataType::from_name(name).ok_or_else(|| {
+                crate::errors::SQLError::InvalidSyntax(format!("Unknown type '{}'", name))
+            })?;
             *index += 1;
-            Expression::Literal(value.clone())
+            Ok(data_type)
         }
+        _ => Err(crate::errors::SQLError::InvalidSyntax(
+            "Expected a type name".to_string(),
+        )),
+    }
+}
+
+fn parse_is_null(
+    operand: Expression,
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<Expression, crate::errors::SQLError> {
+    *index += 1; // Skip 'IS'
+
+    let negated = matches!(peek(tokens, *index), Some(TokenKind::Keyword(k)) if k == "NOT");
+    if negated {
+        *index += 1;
+    }
+
+    match peek(tokens, *index) {
+        Some(TokenKind::Keyword(k)) if k == "NULL" => *index += 1,
         _ => {
             return Err(crate::errors::SQLError::InvalidSyntax(
-                "Expected literal value in expression".to_string(),
+                "Expected 'NULL' after 'IS'".to_string(),
             ))
         }
-    };
+    }
 
-    Ok(Expression::BinaryOp {
-        left: Box::new(left),
-        op,
-        right: Box::new(right),
+    Ok(Expression::IsNull {
+        operand: Box::new(operand),
+        negated,
     })
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_parse_select_simple() {
-        let query = "SELECT id, name FROM users;";
-        let tokens = tokenize(query).unwrap();
-        let parsed_query = parse(&tokens).unwrap();
-
-        if let SQLQuery::Select(select_query) = parsed_query {
-            assert_eq!(select_query.columns, vec!["id", "name"]);
-            assert_eq!(select_query.table, "users");
-            assert!(select_query.where_clause.is_none());
-            assert!(select_query.order_by.is_none());
-        } else {
-            panic!("Expected SELECT query");
-        }
+/// Whether the tokens at `index` start an IN, BETWEEN, LIKE or ILIKE predicate,
+/// possibly negated with NOT.
+fn starts_predicate(tokens: &[Token], index: usize) -> bool {
+    let is_predicate = |index| {
+        matches!(peek(tokens, index), Some(TokenKind::Keyword(k))
+            if ["IN", "BETWEEN", "LIKE", "ILIKE"].contains(&k.as_str()))
+    };
+    match peek(tokens, index) {
+        Some(TokenKind::Keyword(k)) if k == "NOT" => is_predicate(index + 1),
+        _ => is_predicate(index),
     }
+}
 
-    #[test]
-    fn test_parse_select_with_where() {
-        let query = "SELECT * FROM customers WHERE age > 30;";
+fn parse_predicate(
+    operand: Expression,
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<Expression, crate::errors::SQLError> {
+    let negated = matches!(peek(tokens, *index), Some(TokenKind::Keyword(k)) if k == "NOT");
+    if negated {
+        *index += 1;
+    }
+    let keyword = match peek(tokens, *index) {
+        Some(TokenKind::Keyword(k)) => k.clone(),
+        _ => String::new(),
+    };
+    *index += 1;
+    let operand = Box::new(operand);
+
+    // Bounds and patterns take no comparisons, so the AND of a BETWEEN ends the
+    // lower bound
+    match keyword.as_str() {
+        "IN" if starts_subquery(tokens, *index) => Ok(Expression::InSubquery {
+            operand,
+            query: Box::new(parse_subquery(tokens, index)?),
+            negated,
+        }),
+        "IN" => Ok(Expression::InList {
+            operand,
+            list: parse_in_list(tokens, index)?,
+            negated,
+        }),
+        "BETWEEN" => {
+            let low = parse_expression_with_precedence(tokens, index, PRECEDENCE_COMPARISON + 1)?;
+            match peek(tokens, *index) {
+                Some(TokenKind::Keyword(k)) if k == "AND" => *index += 1,
+                _ => {
+                    return Err(crate::errors::SQLError::InvalidSyntax(
+                        "Expected 'AND' in BETWEEN".to_string(),
+                    ))
+                }
+            }
+            let high = parse_expression_with_precedence(tokens, index, PRECEDENCE_COMPARISON + 1)?;
+            Ok(Expression::Between {
+                operand,
+                low: Box::new(low),
+                high: Box::new(high),
+                negated,
+            })
+        }
+        _ => {
+            let pattern =
+                parse_expression_with_precedence(tokens, index, PRECEDENCE_COMPARISON + 1)?;
+            Ok(Expression::Like {
+                operand,
+                pattern: Box::new(pattern),
+                escape: parse_escape(tokens, index)?,
+                negated,
+                case_insensitive: keyword == "ILIKE",
+            })
+        }
+    }
+}
+
+fn parse_in_list(
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<Vec<Expression>, crate::errors::SQLError> {
+    match peek(tokens, *index) {
+        Some(TokenKind::OpenParen) => *index += 1, // Skip '('
+        _ => {
+            return Err(crate::errors::SQLError::InvalidSyntax(
+                "Expected '(' after 'IN'".to_string(),
+            ))
+        }
+    }
+    let list = parse_expression_list(tokens, index)?;
+    match peek(tokens, *index) {
+        Some(TokenKind::CloseParen) => *index += 1, // Skip ')'
+        _ => {
+            return Err(crate::errors::SQLError::InvalidSyntax(
+                "Expected ')' after IN list".to_string(),
+            ))
+        }
+    }
+    Ok(list)
+}
+
+/// Parses an optional `ESCAPE 'c'` after a LIKE pattern.
+fn parse_escape(
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<Option<char>, crate::errors::SQLError> {
+    match peek(tokens, *index) {
+        Some(TokenKind::Keyword(k)) if k == "ESCAPE" => *index += 1,
+        _ => return Ok(None),
+    }
+    let escape = match peek(tokens, *index) {
+        Some(TokenKind::Literal(text)) => {
+            let mut chars = text.chars();
+            match (chars.next(), chars.next()) {
+                (Some(escape), None) => Some(escape),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    match escape {
+        Some(escape) => {
+            *index += 1;
+            Ok(Some(escape))
+        }
+        None => Err(crate::errors::SQLError::InvalidSyntax(
+            "Expected a single character in quotes after 'ESCAPE'".to_string(),
+        )),
+    }
+}
+
+fn infix_operator(token: Option<&TokenKind>) -> Option<(String, u8)> {
+    match token {
+        Some(TokenKind::Keyword(k)) if k == "OR" || k == "AND" => {
+            Some((k.clone(), binary_precedence(k)))
+        }
+        Some(TokenKind::Operator(op)) if op != "::" => Some((op.clone(), binary_precedence(op))),
+        // `*` is only multiplication after an operand
+        Some(TokenKind::Asterisk) => Some(("*".to_string(), PRECEDENCE_MULTIPLICATIVE)),
+        _ => None,
+    }
+}
+
+fn parse_prefix(
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<Expression, crate::errors::SQLError> {
+    if let Some(value) = parse_typed_literal(tokens, index) {
+        return value.map(Expression::Literal);
+    }
+    match peek(tokens, *index) {
+        Some(TokenKind::Keyword(k)) if k == "NOT" => {
+            *index += 1;
+            let operand = parse_expression_with_precedence(tokens, index, PRECEDENCE_NOT)?;
+            Ok(Expression::UnaryOp {
+                op: "NOT".to_string(),
+                operand: Box::new(operand),
+            })
+        }
+        Some(TokenKind::Operator(op)) if op == "-" => {
+            // A minus sign in front of a number is part of the literal
+            if let Some(value) = parse_literal(tokens, index) {
+                return value.map(Expression::Literal);
+            }
+            *index += 1;
+            let operand = parse_expression_with_precedence(tokens, index, PRECEDENCE_UNARY)?;
+            Ok(Expression::UnaryOp {
+                op: "-".to_string(),
+                operand: Box::new(operand),
+            })
+        }
+        Some(TokenKind::Keyword(k)) if k == "CASE" => parse_case(tokens, index),
+        Some(TokenKind::Keyword(k)) if k == "EXISTS" => {
+            *index += 1;
+            if !starts_subquery(tokens, *index) {
+                return Err(crate::errors::SQLError::InvalidSyntax(
+                    "Expected a subquery in parentheses after 'EXISTS'".to_string(),
+                ));
+            }
+            Ok(Expression::Exists(Box::new(parse_subquery(tokens, index)?)))
+        }
+        Some(TokenKind::OpenParen) if starts_subquery(tokens, *index) => Ok(Expression::Subquery(
+            Box::new(parse_subquery(tokens, index)?),
+        )),
+        Some(TokenKind::OpenParen) => {
+            *index += 1; // Skip '('
+            let expr = parse_expression(tokens, index)?;
+            match peek(tokens, *index) {
+                Some(TokenKind::CloseParen) => *index += 1, // Skip ')'
+                _ => {
+                    return Err(crate::errors::SQLError::InvalidSyntax(
+                        "Expected ')' to close expression".to_string(),
+                    ))
+                }
+            }
+            Ok(expr)
+        }
+        Some(TokenKind::Identifier(name))
+            if peek(tokens, *index + 1) == Some(&TokenKind::OpenParen) =>
+        {
+            parse_function_call(name, tokens, index)
+        }
+        Some(TokenKind::Identifier(table)) if peek(tokens, *index + 1) == Some(&TokenKind::Dot) => {
+            match peek(tokens, *index + 2) {
+                Some(TokenKind::Identifier(column)) => {
+                    *index += 3;
+                    Ok(Expression::QualifiedColumn {
+                        table: table.clone(),
+                        column: column.clone(),
+                    })
+                }
+                _ => Err(crate::errors::SQLError::InvalidSyntax(format!(
+                    "Expected column name after '{}.'",
+                    table
+                ))),
+            }
+        }
+        Some(TokenKind::Identifier(name)) => {
+            *index += 1;
+            let function = name.to_uppercase();
+            if functions::NILADIC.contains(&function.as_str()) {
+                return Ok(Expression::Function {
+                    name: function,
+                    arguments: Vec::new(),
+                });
+            }
+            Ok(Expression::Column(name.clone()))
+        }
+        _ => match parse_literal(tokens, index) {
+            Some(value) => value.map(Expression::Literal),
+            None => Err(crate::errors::SQLError::InvalidSyntax(
+                "Expected column name, literal or '(' in expression".to_string(),
+            )),
+        },
+    }
+}
+
+fn parse_function_call(
+    name: &str,
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<Expression, crate::errors::SQLError> {
+    let function = name.to_uppercase();
+    if function == "CAST" {
+        return parse_cast(tokens, index);
+    }
+    if functions::is_function(&function) {
+        return parse_scalar_call(function, tokens, index);
+    }
+    if !AGGREGATE_FUNCTIONS.contains(&function.as_str()) {
+        let known = AGGREGATE_FUNCTIONS.into_iter().chain(functions::names());
+        return Err(crate::errors::SQLError::InvalidSyntax(format!(
+            "Unknown function '{}'{}",
+            name,
+            did_you_mean(&function, known)
+        )));
+    }
+    *index += 2; // Skip the function name and '('
+
+    let distinct = matches!(peek(tokens, *index), Some(TokenKind::Keyword(k)) if k == "DISTINCT");
+    if distinct {
+        *index += 1;
+    }
+
+    let argument = match peek(tokens, *index) {
+        Some(TokenKind::Asterisk) if function == "COUNT" && !distinct => {
+            *index += 1;
+            None
+        }
+        Some(TokenKind::Asterisk) => {
+            return Err(crate::errors::SQLError::InvalidSyntax(format!(
+                "'*' is not a valid argument for {}",
+                function
+            )))
+        }
+        _ => Some(Box::new(parse_expression(tokens, index)?)),
+    };
+
+    match peek(tokens, *index) {
+        Some(TokenKind::CloseParen) => *index += 1, // Skip ')'
+        _ => {
+            return Err(crate::errors::SQLError::InvalidSyntax(format!(
+                "Expected ')' after {} argument",
+                function
+            )))
+        }
+    }
+
+    Ok(Expression::Aggregate {
+        function,
+        argument,
+        distinct,
+    })
+}
+
+/// Parses the arguments of a scalar function call, starting at its name.
+fn parse_scalar_call(
+    function: String,
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<Expression, crate::errors::SQLError> {
+    if function == "EXTRACT" {
+        return parse_extract(tokens, index);
+    }
+    *index += 2; // Skip the function name and '('
+
+    let mut arguments = Vec::new();
+    if peek(tokens, *index) == Some(&TokenKind::CloseParen) {
+        *index += 1;
+    } else {
+        loop {
+            // In the standard `POSITION(substring IN text)` the substring stops
+            // before IN rather than starting an IN predicate
+            let in_separator = function == "POSITION" && arguments.is_empty();
+            let min_precedence = if in_separator {
+                PRECEDENCE_COMPARISON + 1
+            } else {
+                0
+            };
+            arguments.push(parse_expression_with_precedence(
+                tokens,
+                index,
+                min_precedence,
+            )?);
+
+            match peek(tokens, *index) {
+                Some(TokenKind::Comma) => *index += 1,
+                Some(TokenKind::Keyword(k)) if k == "IN" && in_separator => *index += 1,
+                Some(TokenKind::CloseParen) => {
+                    *index += 1;
+                    break;
+                }
+                _ => {
+                    return Err(crate::errors::SQLError::InvalidSyntax(format!(
+                        "Expected ',' or ')' after {} argument",
+                        function
+                    )))
+                }
+            }
+        }
+    }
+
+    functions::check_arity(&function, arguments.len())?;
+    Ok(Expression::Function {
+        name: function,
+        arguments,
+    })
+}
+
+/// Parses `CAST(operand AS type)`, starting at `CAST`.
+fn parse_cast(tokens: &[Token], index: &mut usize) -> Result<Expression, crate::errors::SQLError> {
+    *index += 2; // Skip 'CAST' and '('
+    let operand = parse_expression(tokens, index)?;
+
+    match peek(tokens, *index) {
+        Some(TokenKind::Keyword(k)) if k == "AS" => *index += 1,
+        _ => {
+            return Err(crate::errors::SQLError::InvalidSyntax(
+                "Expected 'AS' followed by a type in CAST".to_string(),
+            ))
+        }
+    }
+    let data_type = parse_data_type(tokens, index)?;
+
+    match peek(tokens, *index) {
+        Some(TokenKind::CloseParen) => *index += 1, // Skip ')'
+        _ => {
+            return Err(crate::errors::SQLError::InvalidSyntax(
+                "Expected ')' after CAST type".to_string(),
+            ))
+        }
+    }
+
+    Ok(Expression::Cast {
+        operand: Box::new(operand),
+        data_type,
+    })
+}
+
+/// Parses a CASE expression, starting at `CASE`.
+fn parse_case(tokens: &[Token], index: &mut usize) -> Result<Expression, crate::errors::SQLError> {
+    *index += 1; // Skip 'CASE'
+    let is_keyword = |index: usize, keyword: &str| matches!(peek(tokens, index), Some(TokenKind::Keyword(k)) if k == keyword);
+
+    let operand = match ["WHEN", "ELSE", "END"]
+        .iter()
+        .any(|keyword| is_keyword(*index, keyword))
+    {
+        true => None,
+        false => Some(Box::new(parse_expression(tokens, index)?)),
+    };
+
+    let mut branches = Vec::new();
+    while is_keyword(*index, "WHEN") {
+        *index += 1;
+        let when = parse_expression(tokens, index)?;
+        if !is_keyword(*index, "THEN") {
+            return Err(crate::errors::SQLError::InvalidSyntax(format!(
+                "Expected 'THEN' after 'WHEN {}'",
+                when
+            )));
+        }
+        *index += 1;
+        let then = parse_expression(tokens, index)?;
+        branches.push((when, then));
+    }
+    if branches.is_empty() {
+        return Err(crate::errors::SQLError::InvalidSyntax(
+            "Expected 'WHEN' in CASE".to_string(),
+        ));
+    }
+
+    let else_result = match is_keyword(*index, "ELSE") {
+        true => {
+            *index += 1;
+            Some(Box::new(parse_expression(tokens, index)?))
+        }
+        false => None,
+    };
+
+    if !is_keyword(*index, "END") {
+        return Err(crate::errors::SQLError::InvalidSyntax(
+            "Expected 'END' to close CASE".to_string(),
+        ));
+    }
+    *index += 1;
+
+    Ok(Expression::Case {
+        operand,
+        branches,
+        else_result,
+    })
+}
+
+/// Parses `EXTRACT(field FROM value)`, starting at its name, into a call with the
+/// field's name as its first argument.
+fn parse_extract(
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<Expression, crate::errors::SQLError> {
+    *index += 2; // Skip 'EXTRACT' and '('
+
+    let field = match peek(tokens, *index) {
+        Some(TokenKind::Identifier(field)) => field.to_uppercase(),
+        _ => {
+            return Err(crate::errors::SQLError::InvalidSyntax(
+                "Expected a field name in EXTRACT".to_string(),
+            ))
+        }
+    };
+    *index += 1;
+
+    match peek(tokens, *index) {
+        Some(TokenKind::Keyword(k)) if k == "FROM" => *index += 1,
+        _ => {
+            return Err(crate::errors::SQLError::InvalidSyntax(format!(
+                "Expected 'FROM' after EXTRACT field '{}'",
+                field
+            )))
+        }
+    }
+    let value = parse_expression(tokens, index)?;
+
+    match peek(tokens, *index) {
+        Some(TokenKind::CloseParen) => *index += 1, // Skip ')'
+        _ => {
+            return Err(crate::errors::SQLError::InvalidSyntax(
+                "Expected ')' after EXTRACT argument".to_string(),
+            ))
+        }
+    }
+
+    Ok(Expression::Function {
+        name: "EXTRACT".to_string(),
+        arguments: vec![Expression::Literal(Value::Text(field)), value],
+    })
+}
+
+/// Reads a literal of a named type, such as `DATE '2024-01-01'`, if there is
+/// one at `index`, and moves past it.
+fn parse_typed_literal(
+    tokens: &[Token],
+    index: &mut usize,
+) -> Option<Result<Value, crate::errors::SQLError>> {
+    let (Some(TokenKind::Identifier(name)), Some(TokenKind::Literal(text))) =
+        (peek(tokens, *index), peek(tokens, *index + 1))
+    else {
+        return None;
+    };
+    let Some(data_type) = DataType::from_name(name) else {
+        return Some(Err(crate::errors::SQLError::InvalidSyntax(format!(
+            "Unknown type '{}'",
+            name
+        ))));
+    };
+    // An invalid literal is reported at the text, and a valid one moved past
+    *index += 1;
+    let value = data_type.parse_field(text).ok_or_else(|| {
+        crate::errors::SQLError::InvalidSyntax(format!("Invalid {} literal '{}'", data_type, text))
+    });
+    if value.is_ok() {
+        *index += 1;
+    }
+    Some(value)
+}
+
+/// Reads the literal at `index`, if there is one, including a number with a
+/// leading minus sign, and moves past it.
+fn parse_literal(
+    tokens: &[Token],
+    index: &mut usize,
+) -> Option<Result<Value, crate::errors::SQLError>> {
+    if let Some(value) = parse_typed_literal(tokens, index) {
+        return Some(value);
+    }
+    if let (Some(TokenKind::Operator(op)), Some(TokenKind::Number(number))) =
+        (peek(tokens, *index), peek(tokens, *index + 1))
+    {
+        if op == "-" {
+            *index += 2;
+            let negative = format!("-{}", number);
+            return Some(parse_number(&negative).ok_or_else(|| {
+                crate::errors::SQLError::InvalidSyntax(format!("Invalid number '{}'", negative))
+            }));
+        }
+    }
+    let value = peek(tokens, *index).and_then(literal_value)?;
+    *index += 1;
+    Some(value)
+}
+
+/// Converts a literal token (string, number, boolean or NULL) to its value, or
+/// returns `None` if the token is not a literal.
+fn literal_value(token: &TokenKind) -> Option<Result<Value, crate::errors::SQLError>> {
+    match token {
+        TokenKind::Literal(value) => Some(Ok(Value::Text(value.clone()))),
+        TokenKind::Number(number) => Some(parse_number(number).ok_or_else(|| {
+            crate::errors::SQLError::InvalidSyntax(format!("Invalid number '{}'", number))
+        })),
+        TokenKind::Keyword(k) if k == "TRUE" || k == "FALSE" => {
+            Some(Ok(Value::Boolean(k == "TRUE")))
+        }
+        TokenKind::Keyword(k) if k == "NULL" => Some(Ok(Value::Null)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_select_simple() {
+        let query = "SELECT id, name FROM users;";
+        let tokens = tokenize(query).unwrap();
+        let parsed_query = parse(&tokens).unwrap();
+
+        if let SQLQuery::Select(select_query) = parsed_query {
+            assert_eq!(
+                select_query.columns,
+                vec![
+                    SelectItem::Expression {
+                        expr: Expression::Column("id".to_string()),
+                        alias: None,
+                    },
+                    SelectItem::Expression {
+                        expr: Expression::Column("name".to_string()),
+                        alias: None,
+                    },
+                ]
+            );
+            assert_eq!(select_query.table, "users");
+            assert!(select_query.where_clause.is_none());
+            assert!(select_query.order_by.is_empty());
+        } else {
+            panic!("Expected SELECT query");
+        }
+    }
+
+    #[test]
+    fn test_parse_select_with_where() {
+        let query = "SELECT * FROM customers WHERE age > 30;";
         let tokens = tokenize(query).unwrap();
         let parsed_query = parse(&tokens).unwrap();
 
         if let SQLQuery::Select(select_query) = parsed_query {
-            assert_eq!(select_query.columns, vec!["*"]);
+            assert_eq!(select_query.columns, vec![SelectItem::Wildcard]);
             assert_eq!(select_query.table, "customers");
             assert!(select_query.where_clause.is_some());
         } else {
             panic!("Expected SELECT query");
         }
     }
+
+    #[test]
+    fn test_parse_where_precedence() {
+        let query =
+            "SELECT * FROM ordenes WHERE (quantity >= 2 AND product = 'Mouse') OR NOT client_id = 6;";
+        let tokens = tokenize(query).unwrap();
+        let parsed_query = parse(&tokens).unwrap();
+
+        let SQLQuery::Select(select_query) = parsed_query else {
+            panic!("Expected SELECT query");
+        };
+        let Some(Expression::BinaryOp { left, op, right }) = select_query.where_clause else {
+            panic!("Expected binary WHERE clause");
+        };
+        assert_eq!(op, "OR");
+        assert!(matches!(*left, Expression::BinaryOp { ref op, .. } if op == "AND"));
+        assert!(matches!(*right, Expression::UnaryOp { ref op, .. } if op == "NOT"));
+    }
+
+    #[test]
+    fn test_parse_and_binds_tighter_than_or() {
+        let query = "DELETE FROM t WHERE a = 1 OR b = 2 AND c = 3;";
+        let tokens = tokenize(query).unwrap();
+
+        let Ok(SQLQuery::Delete(delete_query)) = parse(&tokens) else {
+            panic!("Expected DELETE query");
+        };
+        let Some(Expression::BinaryOp { op, right, .. }) = delete_query.where_clause else {
+            panic!("Expected binary WHERE clause");
+        };
+        assert_eq!(op, "OR");
+        assert!(matches!(*right, Expression::BinaryOp { ref op, .. } if op == "AND"));
+    }
+
+    #[test]
+    fn test_parse_unbalanced_parentheses() {
+        let tokens = tokenize("SELECT * FROM t WHERE (a = 1 OR b = 2;").unwrap();
+        assert!(parse(&tokens).is_err());
+    }
+
+    #[test]
+    fn test_parse_is_null_and_nulls_ordering() {
+        let query = "SELECT * FROM t WHERE a IS NOT NULL AND b IS NULL ORDER BY a DESC NULLS LAST;";
+        let tokens = tokenize(query).unwrap();
+
+        let Ok(SQLQuery::Select(select_query)) = parse(&tokens) else {
+            panic!("Expected SELECT query");
+        };
+        let Some(Expression::BinaryOp { left, op, right }) = select_query.where_clause else {
+            panic!("Expected binary WHERE clause");
+        };
+        assert_eq!(op, "AND");
+        assert!(matches!(*left, Expression::IsNull { negated: true, .. }));
+        assert!(matches!(*right, Expression::IsNull { negated: false, .. }));
+
+        let order_by = &select_query.order_by[0];
+        assert!(!order_by.ascending);
+        assert!(!order_by.nulls_first);
+    }
+
+    #[test]
+    fn test_parse_group_by_having() {
+        let query = "SELECT product, SUM(quantity), COUNT(*), COUNT(DISTINCT client_id) \
+                     FROM ordenes GROUP BY product HAVING SUM(quantity) > 2;";
+        let tokens = tokenize(query).unwrap();
+
+        let Ok(SQLQuery::Select(select_query)) = parse(&tokens) else {
+            panic!("Expected SELECT query");
+        };
+        let names: Vec<String> = select_query
+            .columns
+            .iter()
+            .map(|item| match item {
+                SelectItem::Expression { expr, .. } => expr.to_string(),
+                _ => panic!("Expected an expression"),
+            })
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "product",
+                "SUM(quantity)",
+                "COUNT(*)",
+                "COUNT(DISTINCT client_id)"
+            ]
+        );
+        assert_eq!(
+            select_query.group_by,
+            vec![Expression::Column("product".to_string())]
+        );
+        assert!(select_query.having.unwrap().contains_aggregate());
+    }
+
+    #[test]
+    fn test_parse_invalid_aggregates() {
+        for query in [
+            "SELECT SUM(*) FROM t;",
+            "SELECT COUNT(DISTINCT *) FROM t;",
+            "SELECT COUNT(id FROM t;",
+            "SELECT FOO(id) FROM t;",
+        ] {
+            let tokens = tokenize(query).unwrap();
+            assert!(parse(&tokens).is_err(), "{} should not parse", query);
+        }
+    }
+
+    #[test]
+    fn test_parse_joins() {
+        let query = "SELECT o.*, c.name FROM ordenes AS o \
+                     JOIN clientes c ON o.client_id = c.id \
+                     LEFT OUTER JOIN productos ON productos.name = o.product \
+                     WHERE c.id > 1;";
+        let tokens = tokenize(query).unwrap();
+
+        let Ok(SQLQuery::Select(select_query)) = parse(&tokens) else {
+            panic!("Expected SELECT query");
+        };
+        assert_eq!(
+            select_query.columns,
+            vec![
+                SelectItem::QualifiedWildcard("o".to_string()),
+                SelectItem::Expression {
+                    expr: Expression::QualifiedColumn {
+                        table: "c".to_string(),
+                        column: "name".to_string(),
+                    },
+                    alias: None,
+                },
+            ]
+        );
+        assert_eq!(select_query.table, "ordenes");
+        assert_eq!(select_query.alias, Some("o".to_string()));
+        assert_eq!(select_query.joins.len(), 2);
+        assert_eq!(select_query.joins[0].kind, JoinKind::Inner);
+        assert_eq!(select_query.joins[0].alias, Some("c".to_string()));
+        assert_eq!(
+            select_query.joins[0].condition.to_string(),
+            "o.client_id = c.id"
+        );
+        assert_eq!(select_query.joins[1].kind, JoinKind::Left);
+        assert_eq!(select_query.joins[1].alias, None);
+    }
+
+    #[test]
+    fn test_parse_join_errors() {
+        for query in [
+            "SELECT * FROM a JOIN b;",
+            "SELECT * FROM a LEFT b ON a.id = b.id;",
+            "SELECT * FROM a AS JOIN b ON a.id = b.id;",
+            "SELECT a. FROM a;",
+        ] {
+            let tokens = tokenize(query).unwrap();
+            assert!(parse(&tokens).is_err(), "{} should not parse", query);
+        }
+    }
+
+    fn limit_of(query: &str) -> Result<(Option<usize>, usize), SQLError> {
+        match parse(&tokenize(query)?)? {
+            SQLQuery::Select(select_query) => Ok((select_query.limit, select_query.offset)),
+            _ => panic!("Expected SELECT query"),
+        }
+    }
+
+    #[test]
+    fn test_parse_limit_and_offset() {
+        assert_eq!(limit_of("SELECT * FROM t;").unwrap(), (None, 0));
+        assert_eq!(limit_of("SELECT * FROM t LIMIT 5;").unwrap(), (Some(5), 0));
+        assert_eq!(
+            limit_of("SELECT * FROM t ORDER BY id LIMIT 5 OFFSET 10;").unwrap(),
+            (Some(5), 10)
+        );
+        assert_eq!(
+            limit_of("SELECT * FROM t OFFSET 2 ROWS FETCH FIRST 3 ROWS ONLY;").unwrap(),
+            (Some(3), 2)
+        );
+        assert_eq!(
+            limit_of("SELECT * FROM t fetch next row only;").unwrap(),
+            (Some(1), 0)
+        );
+    }
+
+    #[test]
+    fn test_parse_limit_errors() {
+        assert!(limit_of("SELECT * FROM t LIMIT;").is_err());
+        assert!(limit_of("SELECT * FROM t LIMIT -1;").is_err());
+        assert!(limit_of("SELECT * FROM t LIMIT 1.5;").is_err());
+        assert!(limit_of("SELECT * FROM t LIMIT 1 FETCH FIRST 2 ROWS ONLY;").is_err());
+        assert!(limit_of("SELECT * FROM t FETCH FIRST 2 ROWS;").is_err());
+        assert!(limit_of("SELECT * FROM t OFFSET 1 OFFSET 2;").is_err());
+    }
+
+    #[test]
+    fn test_parse_order_by_list() {
+        let query = "SELECT a FROM t ORDER BY b DESC, 1, quantity < 3 NULLS FIRST LIMIT 3;";
+        let Ok(SQLQuery::Select(select_query)) = parse(&tokenize(query).unwrap()) else {
+            panic!("Expected SELECT query");
+        };
+        let keys: Vec<(String, bool, bool)> = select_query
+            .order_by
+            .iter()
+            .map(|key| (key.expression.to_string(), key.ascending, key.nulls_first))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                ("b".to_string(), false, true),
+                ("1".to_string(), true, false),
+                ("quantity < 3".to_string(), true, true),
+            ]
+        );
+        assert_eq!(select_query.limit, Some(3));
+
+        assert!(parse(&tokenize("SELECT a FROM t ORDER BY;").unwrap()).is_err());
+        assert!(parse(&tokenize("SELECT a FROM t ORDER BY a,;").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_parse_distinct() {
+        let distinct_of = |query: &str| match parse(&tokenize(query)?)? {
+            SQLQuery::Select(select_query) => Ok::<_, SQLError>(select_query.distinct),
+            _ => panic!("Expected SELECT query"),
+        };
+        assert_eq!(distinct_of("SELECT a FROM t;").unwrap(), Distinct::All);
+        assert_eq!(
+            distinct_of("SELECT DISTINCT a, b FROM t;").unwrap(),
+            Distinct::Rows
+        );
+        assert_eq!(
+            distinct_of("SELECT DISTINCT ON (a, b) a, c FROM t;").unwrap(),
+            Distinct::On(vec![
+                Expression::Column("a".to_string()),
+                Expression::Column("b".to_string())
+            ])
+        );
+        assert!(distinct_of("SELECT DISTINCT ON a FROM t;").is_err());
+        assert!(distinct_of("SELECT DISTINCT ON (a FROM t;").is_err());
+        assert!(distinct_of("SELECT DISTINCT FROM t;").is_err());
+    }
+
+    #[test]
+    fn test_parse_select_aliases() {
+        let query = "SELECT id AS order_id, 'x' label, COUNT(*) AS total FROM t;";
+        let Ok(SQLQuery::Select(select_query)) = parse(&tokenize(query).unwrap()) else {
+            panic!("Expected SELECT query");
+        };
+        let items: Vec<(String, Option<String>)> = select_query
+            .columns
+            .into_iter()
+            .map(|item| match item {
+                SelectItem::Expression { expr, alias } => (expr.to_string(), alias),
+                _ => panic!("Expected an expression"),
+            })
+            .collect();
+        assert_eq!(
+            items,
+            vec![
+                ("id".to_string(), Some("order_id".to_string())),
+                ("'x'".to_string(), Some("label".to_string())),
+                ("COUNT(*)".to_string(), Some("total".to_string())),
+            ]
+        );
+
+        assert!(parse(&tokenize("SELECT id AS FROM t;").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_tokenize_numbers_and_arithmetic() {
+        let tokens = tokenize("SELECT 1.5e3, .5, 2E-2, 7%2 FROM t WHERE a-1/-2 >= 0;").unwrap();
+        let numbers: Vec<&TokenKind> = tokens
+            .iter()
+            .map(|token| &token.kind)
+            .filter(|kind| matches!(kind, TokenKind::Number(_) | TokenKind::Operator(_)))
+            .collect();
+        assert_eq!(
+            numbers,
+            vec![
+                &TokenKind::Number("1.5e3".to_string()),
+                &TokenKind::Number(".5".to_string()),
+                &TokenKind::Number("2E-2".to_string()),
+                &TokenKind::Number("7".to_string()),
+                &TokenKind::Operator("%".to_string()),
+                &TokenKind::Number("2".to_string()),
+                &TokenKind::Operator("-".to_string()),
+                &TokenKind::Number("1".to_string()),
+                &TokenKind::Operator("/".to_string()),
+                &TokenKind::Operator("-".to_string()),
+                &TokenKind::Number("2".to_string()),
+                &TokenKind::Operator(">=".to_string()),
+                &TokenKind::Number("0".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_arithmetic_precedence() {
+        let expr = |sql: &str| {
+            let query = format!("SELECT * FROM t WHERE {};", sql);
+            match parse(&tokenize(&query).unwrap()).unwrap() {
+                SQLQuery::Select(select_query) => select_query.where_clause.unwrap(),
+                _ => panic!("Expected SELECT query"),
+            }
+        };
+        // Printing parenthesizes exactly where the parse differs from left to right
+        assert_eq!(expr("a + b * c > -2").to_string(), "a + b * c > -2");
+        assert_eq!(expr("(a + b) * c").to_string(), "(a + b) * c");
+        assert_eq!(expr("a - (b - c)").to_string(), "a - (b - c)");
+        assert_eq!(expr("a - b - c").to_string(), "a - b - c");
+        assert_eq!(
+            expr("-(a + 1) = 2 OR NOT b").to_string(),
+            "-(a + 1) = 2 OR NOT b"
+        );
+        assert_eq!(
+            expr("a * 2 = 3"),
+            Expression::BinaryOp {
+                left: Box::new(Expression::BinaryOp {
+                    left: Box::new(Expression::Column("a".to_string())),
+                    op: "*".to_string(),
+                    right: Box::new(Expression::Literal(Value::Integer(2))),
+                }),
+                op: "=".to_string(),
+                right: Box::new(Expression::Literal(Value::Integer(3))),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_update_expressions() {
+        let query = "UPDATE t SET quantity = quantity + 1, price = -2 WHERE id = 1;";
+        let Ok(SQLQuery::Update(update_query)) = parse(&tokenize(query).unwrap()) else {
+            panic!("Expected UPDATE query");
+        };
+        let assignments: Vec<(String, String)> = update_query
+            .assignments
+            .iter()
+            .map(|assignment| (assignment.column.clone(), assignment.value.to_string()))
+            .collect();
+        assert_eq!(
+            assignments,
+            vec![
+                ("quantity".to_string(), "quantity + 1".to_string()),
+                ("price".to_string(), "-2".to_string()),
+            ]
+        );
+
+        assert!(parse(&tokenize("UPDATE t SET quantity = WHERE id = 1;").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_parse_insert_sources() {
+        let query = "INSERT INTO t (a, b) VALUES (1, 'x'), (-2, NULL);";
+        let Ok(SQLQuery::Insert(insert_query)) = parse(&tokenize(query).unwrap()) else {
+            panic!("Expected INSERT query");
+        };
+        let InsertSource::Values(rows) = insert_query.source else {
+            panic!("Expected VALUES");
+        };
+        assert_eq!(
+            rows,
+            vec![
+                vec![Value::Integer(1), Value::Text("x".to_string())],
+                vec![Value::Integer(-2), Value::Null],
+            ]
+        );
+
+        let query = "INSERT INTO t (a) SELECT id FROM s WHERE id > 1;";
+        let Ok(SQLQuery::Insert(insert_query)) = parse(&tokenize(query).unwrap()) else {
+            panic!("Expected INSERT query");
+        };
+        let InsertSource::Select(select_query) = insert_query.source else {
+            panic!("Expected SELECT");
+        };
+        assert_eq!(select_query.table, "s");
+
+        assert!(parse(&tokenize("INSERT INTO t VALUES (1),;").unwrap()).is_err());
+        assert!(parse(&tokenize("INSERT INTO t VALUES (1) (2);").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_tokenize_operators_longest_match() {
+        let operators = |sql: &str| -> Vec<String> {
+            tokenize(sql)
+                .unwrap()
+                .into_iter()
+                .filter_map(|token| match token.kind {
+                    TokenKind::Operator(op) => Some(op),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(
+            operators("a<>b a!=b a||b a::INT a<=b a>=b a<b a>b a=b"),
+            vec!["<>", "!=", "||", "::", "<=", ">=", "<", ">", "="]
+        );
+        // Without spaces the longest operator wins, then lexing resumes after it
+        assert_eq!(operators("a<>=b"), vec!["<>", "="]);
+        assert_eq!(operators("a<=>b"), vec!["<=", ">"]);
+        assert!(tokenize("a|||b").is_err());
+        assert!(tokenize("a ! b").is_err());
+        assert!(tokenize("a : b").is_err());
+    }
+
+    #[test]
+    fn test_parse_concat_and_cast_precedence() {
+        let query = "SELECT * FROM t WHERE name || ' ' || id + 1 = 'x' AND -q::TEXT <> '1';";
+        let Ok(SQLQuery::Select(select_query)) = parse(&tokenize(query).unwrap()) else {
+            panic!("Expected SELECT query");
+        };
+        let Some(Expression::BinaryOp { left, .. }) = select_query.where_clause else {
+            panic!("Expected binary WHERE clause");
+        };
+        let Expression::BinaryOp { left: concat, .. } = *left else {
+            panic!("Expected comparison");
+        };
+        assert_eq!(concat.to_string(), "name || ' ' || id + 1");
+        assert!(matches!(*concat, Expression::BinaryOp { ref op, .. } if op == "||"));
+
+        let expr = parse(&tokenize("SELECT (a + 1)::FLOAT FROM t;").unwrap()).unwrap();
+        let SQLQuery::Select(select_query) = expr else {
+            panic!("Expected SELECT query");
+        };
+        let SelectItem::Expression { expr, .. } = &select_query.columns[0] else {
+            panic!("Expected an expression");
+        };
+        assert_eq!(expr.to_string(), "(a + 1)::FLOAT");
+        assert!(parse(&tokenize("SELECT a::MONEY FROM t;").unwrap()).is_err());
+    }
+
+    fn kinds(sql: &str) -> Vec<TokenKind> {
+        let tokens = tokenize(sql).unwrap();
+        tokens.into_iter().map(|token| token.kind).collect()
+    }
+
+    #[test]
+    fn test_tokenize_quotes_and_comments() {
+        assert_eq!(
+            kinds("'O''Brien' ''''"),
+            vec![
+                TokenKind::Literal("O'Brien".to_string()),
+                TokenKind::Literal("'".to_string()),
+                TokenKind::Eof
+            ]
+        );
+        assert_eq!(
+            kinds("\"order\" \"ship date\" \"a\"\"b\""),
+            vec![
+                TokenKind::Identifier("order".to_string()),
+                TokenKind::Identifier("ship date".to_string()),
+                TokenKind::Identifier("a\"b".to_string()),
+                TokenKind::Eof
+            ]
+        );
+        assert_eq!(
+            kinds("a -- trailing 'comment\n- /* block\n * / */ b /**/"),
+            vec![
+                TokenKind::Identifier("a".to_string()),
+                TokenKind::Operator("-".to_string()),
+                TokenKind::Identifier("b".to_string()),
+                TokenKind::Eof
+            ]
+        );
+        for sql in ["'open", "'it''s", "\"open", "\"\"", "a /* open *", "/*/"] {
+            assert!(
+                matches!(
+                    tokenize(sql),
+                    Err(SQLError::Located { error, .. }) if matches!(*error, SQLError::InvalidSyntax(_))
+                ),
+                "{}",
+                sql
+            );
+        }
+    }
+
+    #[test]
+    fn test_display_quotes_identifiers_and_literals() {
+        let query = "SELECT \"order\" + 1, t.\"ship date\", \"Name\" || 'O''Brien' FROM t;";
+        let SQLQuery::Select(select_query) = parse(&tokenize(query).unwrap()).unwrap() else {
+            panic!("Expected SELECT query");
+        };
+        let printed: Vec<String> = select_query
+            .columns
+            .iter()
+            .map(|item| match item {
+                SelectItem::Expression { expr, .. } => expr.to_string(),
+                _ => panic!("Expected an expression"),
+            })
+            .collect();
+        assert_eq!(
+            printed,
+            vec!["\"order\" + 1", "t.\"ship date\"", "Name || 'O''Brien'"]
+        );
+    }
+
+    #[test]
+    fn test_tokens_carry_spans() {
+        let tokens = tokenize("SELECT ñ, 'a''b'\n  FROM t;").unwrap();
+        let spans: Vec<(usize, usize)> = tokens
+            .iter()
+            .map(|token| (token.span.start, token.span.end))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (0, 6),
+                (7, 9),
+                (9, 10),
+                (11, 17),
+                (20, 24),
+                (25, 26),
+                (26, 27),
+                (27, 27)
+            ]
+        );
+
+        let error = tokenize("SELECT 'abc").unwrap_err();
+        assert!(
+            matches!(error, SQLError::Located { span, .. } if span == Span { start: 7, end: 11 })
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_point_at_tokens() {
+        fn error_at(sql: &str) -> (String, &str) {
+            match parse(&tokenize(sql).unwrap()) {
+                Err(SQLError::Located { error, span }) => {
+                    (error.to_string(), &sql[span.start..span.end])
+                }
+                _ => panic!("Expected a located error"),
+            }
+        }
+        assert_eq!(
+            error_at("SELECT id FROM ordenes WHERE;"),
+            (
+                "INVALID_SYNTAX: Expected column name, literal or '(' in expression".to_string(),
+                ";"
+            )
+        );
+        assert_eq!(
+            error_at("SELECT * FROM"),
+            ("INVALID_SYNTAX: Expected table name".to_string(), "")
+        );
+        // Misspelled keywords are suggested where parsing stops
+        assert_eq!(
+            error_at("SELEC * FROM ordenes;"),
+            (
+                "INVALID_SYNTAX: Expected a SQL command (did you mean 'SELECT'?)".to_string(),
+                "SELEC"
+            )
+        );
+        assert_eq!(
+            error_at("SELECT id FORM ordenes;"),
+            (
+                "INVALID_SYNTAX: Expected 'FROM' keyword".to_string(),
+                "ordenes"
+            )
+        );
+        // No hint repeats the keyword the message already asks for
+        assert_eq!(
+            error_at("SELECT id FROM ordenes ORDER BYY id;"),
+            (
+                "INVALID_SYNTAX: Expected 'BY' after 'ORDER'".to_string(),
+                "BYY"
+            )
+        );
+        assert_eq!(
+            error_at("SELECT id FROM ordenes ORDER id;"),
+            (
+                "INVALID_SYNTAX: Expected 'BY' after 'ORDER'".to_string(),
+                "id"
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_returning() {
+        let query = "DELETE FROM t WHERE id = 1 RETURNING *, id + 1 AS next;";
+        let Ok(SQLQuery::Delete(delete_query)) = parse(&tokenize(query).unwrap()) else {
+            panic!("Expected DELETE query");
+        };
+        assert_eq!(delete_query.returning[0], SelectItem::Wildcard);
+        let SelectItem::Expression { expr, alias } = &delete_query.returning[1] else {
+            panic!("Expected expression");
+        };
+        assert_eq!(expr.to_string(), "id + 1");
+        assert_eq!(alias.as_deref(), Some("next"));
+
+        let query = "INSERT INTO t (a) SELECT id FROM s RETURNING a;";
+        let Ok(SQLQuery::Insert(insert_query)) = parse(&tokenize(query).unwrap()) else {
+            panic!("Expected INSERT query");
+        };
+        assert_eq!(insert_query.returning.len(), 1);
+
+        let query = "UPDATE t SET a = 1;";
+        let Ok(SQLQuery::Update(update_query)) = parse(&tokenize(query).unwrap()) else {
+            panic!("Expected UPDATE query");
+        };
+        assert!(update_query.returning.is_empty());
+
+        assert!(parse(&tokenize("UPDATE t SET a = 1 RETURNING;").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_parse_in_between_and_like() {
+        let where_clause = |condition: &str| {
+            let query = format!("SELECT * FROM t WHERE {};", condition);
+            match parse(&tokenize(&query).unwrap()) {
+                Ok(SQLQuery::Select(select_query)) => Ok(select_query.where_clause.unwrap()),
+                Ok(_) => panic!("Expected SELECT query"),
+                Err(e) => Err(e),
+            }
+        };
+
+        // BETWEEN bounds stop at AND, and the predicates bind like comparisons
+        let expr =
+            where_clause("a BETWEEN 1 AND b + 2 AND c NOT IN (1, 'x') OR NOT d LIKE 'x%'").unwrap();
+        let Expression::BinaryOp { left, op, .. } = &expr else {
+            panic!("Expected OR");
+        };
+        assert_eq!(op, "OR");
+        assert!(matches!(**left, Expression::BinaryOp { ref op, .. } if op == "AND"));
+        assert_eq!(
+            expr.to_string(),
+            "a BETWEEN 1 AND b + 2 AND c NOT IN (1, 'x') OR NOT d LIKE 'x%'"
+        );
+
+        let expr = where_clause("email NOT ILIKE '%!_%' ESCAPE '!'").unwrap();
+        assert!(matches!(
+            expr,
+            Expression::Like {
+                escape: Some('!'),
+                negated: true,
+                case_insensitive: true,
+                ..
+            }
+        ));
+        assert_eq!(expr.to_string(), "email NOT ILIKE '%!_%' ESCAPE '!'");
+
+        for condition in [
+            "a IN 1, 2",
+            "a IN ()",
+            "a BETWEEN 1 OR 2",
+            "a LIKE 'x' ESCAPE 'ab'",
+            "a NOT 1",
+        ] {
+            assert!(where_clause(condition).is_err(), "{}", condition);
+        }
+    }
+
+    #[test]
+    fn test_parse_function_calls() {
+        let query = "SELECT upper(TRIM(name)) || 'x', POSITION('@' IN email), CONCAT() FROM t;";
+        assert!(matches!(
+            parse(&tokenize(query).unwrap()),
+            Err(SQLError::Located { error, .. })
+                if error.to_string().contains("CONCAT takes at least 1 argument")
+        ));
+
+        let query = "SELECT upper(TRIM(name)) || 'x', POSITION('@' IN email) FROM t;";
+        let Ok(SQLQuery::Select(select_query)) = parse(&tokenize(query).unwrap()) else {
+            panic!("Expected SELECT query");
+        };
+        let printed: Vec<String> = select_query
+            .columns
+            .iter()
+            .map(|item| match item {
+                SelectItem::Expression { expr, .. } => expr.to_string(),
+                _ => panic!("Expected an expression"),
+            })
+            .collect();
+        assert_eq!(
+            printed,
+            vec!["UPPER(TRIM(name)) || 'x'", "POSITION('@', email)"]
+        );
+
+        let Err(SQLError::Located { error, .. }) =
+            parse(&tokenize("SELECT LOWR(name) FROM t;").unwrap())
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(
+            error.to_string(),
+            "INVALID_SYNTAX: Unknown function 'LOWR' (did you mean 'LOWER'?)"
+        );
+        assert!(parse(&tokenize("SELECT LOWER(name, FROM t;").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_parse_dates() {
+        let query = "SELECT DATE '2024-01-31' + INTERVAL '1 month', EXTRACT(year FROM d), CURRENT_DATE, '10:30'::TIME FROM t;";
+        let Ok(SQLQuery::Select(select_query)) = parse(&tokenize(query).unwrap()) else {
+            panic!("Expected SELECT query");
+        };
+        let printed: Vec<String> = select_query
+            .columns
+            .iter()
+            .map(|item| match item {
+                SelectItem::Expression { expr, .. } => expr.to_string(),
+                _ => panic!("Expected an expression"),
+            })
+            .collect();
+        assert_eq!(
+            printed,
+            vec![
+                "DATE '2024-01-31' + INTERVAL '1 mon'",
+                "EXTRACT(YEAR FROM d)",
+                "CURRENT_DATE",
+                "'10:30'::TIME"
+            ]
+        );
+
+        let Err(SQLError::Located { error, span }) =
+            parse(&tokenize("SELECT DATE '2024-02-30' FROM t;").unwrap())
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(
+            error.to_string(),
+            "INVALID_SYNTAX: Invalid DATE literal '2024-02-30'"
+        );
+        assert_eq!(span, Span { start: 12, end: 24 });
+        assert!(parse(&tokenize("SELECT EXTRACT(YEAR d) FROM t;").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_parse_case_and_cast() {
+        let query = "SELECT CASE WHEN quantity > 1 THEN 'many' WHEN quantity = 1 THEN 'one' END, case status when 'A' then 1 else 0 end + 1, CAST(price * 2 AS INTEGER) FROM t;";
+        let Ok(SQLQuery::Select(select_query)) = parse(&tokenize(query).unwrap()) else {
+            panic!("Expected SELECT query");
+        };
+        let printed: Vec<String> = select_query
+            .columns
+            .iter()
+            .map(|item| match item {
+                SelectItem::Expression { expr, .. } => expr.to_string(),
+                _ => panic!("Expected an expression"),
+            })
+            .collect();
+        assert_eq!(
+            printed,
+            vec![
+                "CASE WHEN quantity > 1 THEN 'many' WHEN quantity = 1 THEN 'one' END",
+                "CASE status WHEN 'A' THEN 1 ELSE 0 END + 1",
+                "(price * 2)::INTEGER"
+            ]
+        );
+
+        for (query, message) in [
+            ("SELECT CASE ELSE 1 END FROM t;", "Expected 'WHEN' in CASE"),
+            (
+                "SELECT CASE WHEN a 1 END FROM t;",
+                "Expected 'THEN' after 'WHEN a'",
+            ),
+            (
+                "SELECT CASE WHEN a THEN 1 FROM t;",
+                "Expected 'END' to close CASE",
+            ),
+            (
+                "SELECT CAST(a INTEGER) FROM t;",
+                "Expected 'AS' followed by a type in CAST",
+            ),
+            ("SELECT CAST(a AS MONEY) FROM t;", "Unknown type 'MONEY'"),
+        ] {
+            let Err(SQLError::Located { error, .. }) = parse(&tokenize(query).unwrap()) else {
+                panic!("Expected an error for {}", query);
+            };
+            assert_eq!(error.to_string(), format!("INVALID_SYNTAX: {}", message));
+        }
+    }
+
+    #[test]
+    fn test_parse_subqueries() {
+        let query = "SELECT name, (SELECT COUNT(*) FROM ordenes o WHERE o.client = c.id) AS orders FROM (select * from clientes where active) as c WHERE id in (select client from ordenes) AND NOT EXISTS (SELECT 1 FROM bajas WHERE bajas.id = c.id);";
+        let Ok(SQLQuery::Select(select_query)) = parse(&tokenize(query).unwrap()) else {
+            panic!("Expected SELECT query");
+        };
+        assert_eq!(
+            select_query.to_string(),
+            "SELECT name, (SELECT COUNT(*) FROM ordenes AS o WHERE o.client = c.id) AS orders FROM (SELECT * FROM clientes WHERE active) AS c WHERE id IN (SELECT client FROM ordenes) AND NOT EXISTS (SELECT 1 FROM bajas WHERE bajas.id = c.id)"
+        );
+        assert_eq!(select_query.table, "c");
+        assert!(select_query.subquery.is_some());
+
+        for (query, message) in [
+            (
+                "SELECT * FROM (SELECT * FROM t);",
+                "Expected an alias for the subquery in FROM",
+            ),
+            (
+                "SELECT * FROM t WHERE EXISTS t;",
+                "Expected a subquery in parentheses after 'EXISTS'",
+            ),
+            (
+                "SELECT (SELECT a FROM t FROM t;",
+                "Expected ')' after subquery",
+            ),
+        ] {
+            let Err(SQLError::Located { error, .. }) = parse(&tokenize(query).unwrap()) else {
+                panic!("Expected an error for {}", query);
+            };
+            assert_eq!(error.to_string(), format!("INVALID_SYNTAX: {}", message));
+        }
+    }
 }
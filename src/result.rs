@@ -0,0 +1,221 @@
+use crate::csv::format_record;
+use crate::errors::SQLError;
+use crate::value::{DataType, Value};
+use std::io::Write;
+use std::ops::Index;
+
+/// An output column of a query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    /// The type every value in the column has, when it is known before running
+    /// the query: that of a table column with a declared type, or of a CAST.
+    pub data_type: Option<DataType>,
+}
+
+/// One output row of a query, with a value for each of its columns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    values: Vec<Value>,
+}
+
+impl Row {
+    pub fn new(values: Vec<Value>) -> Row {
+        Row { values }
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&Value> {
+        self.values.get(idx)
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    pub fn into_values(self) -> Vec<Value> {
+        self.values
+    }
+}
+
+impl Index<usize> for Row {
+    type Output = Value;
+
+    fn index(&self, idx: usize) -> &Value {
+        &self.values[idx]
+    }
+}
+
+/// The columns and rows a query produced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultSet {
+    columns: Vec<Column>,
+    rows: Vec<Row>,
+}
+
+impl ResultSet {
+    pub fn new(columns: Vec<Column>, rows: Vec<Row>) -> ResultSet {
+        ResultSet { columns, rows }
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// The position of the output column called `name`, if there is exactly one.
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        let mut matches = self
+            .columns
+            .iter()
+            .enumerate()
+            .filter(|(_, column)| column.name == name);
+        match (matches.next(), matches.next()) {
+            (Some((idx, _)), None) => Some(idx),
+            _ => None,
+        }
+    }
+
+    /// Writes the column names and then every row as CSV records.
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> Result<(), SQLError> {
+        write_header(writer, &self.columns)?;
+        for row in &self.rows {
+            write_row(writer, row)?;
+        }
+        Ok(())
+    }
+}
+
+/// The rows of a query, produced one at a time as they are read from the tables.
+/// Only sorting and grouping hold every row in memory.
+pub struct RowIter {
+    columns: Vec<Column>,
+    rows: Box<dyn Iterator<Item = Result<Vec<Value>, SQLError>> + Send>,
+    rows_affected: Option<usize>,
+}
+
+impl RowIter {
+    pub(crate) fn new(
+        columns: Vec<Column>,
+        rows: Box<dyn Iterator<Item = Result<Vec<Value>, SQLError>> + Send>,
+    ) -> RowIter {
+        RowIter {
+            columns,
+            rows,
+            rows_affected: None,
+        }
+    }
+
+    /// The output of a statement that changed `count` rows, with the rows of its
+    /// RETURNING clause, if it has one.
+    pub(crate) fn affected(count: usize, columns: Vec<Column>, rows: Vec<Vec<Value>>) -> RowIter {
+        RowIter {
+            columns,
+            rows: Box::new(rows.into_iter().map(Ok)),
+            rows_affected: Some(count),
+        }
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// How many rows an INSERT, UPDATE or DELETE changed, or `None` for a SELECT.
+    pub fn rows_affected(&self) -> Option<usize> {
+        self.rows_affected
+    }
+
+    /// Reads the remaining rows into a result set.
+    pub fn into_result_set(self) -> Result<ResultSet, SQLError> {
+        let columns = self.columns.clone();
+        let rows = self.collect::<Result<Vec<Row>, SQLError>>()?;
+        Ok(ResultSet::new(columns, rows))
+    }
+
+    /// Writes the column names and then each remaining row as CSV records, as
+    /// the rows are produced.
+    pub fn write_csv<W: Write>(self, writer: &mut W) -> Result<(), SQLError> {
+        write_header(writer, &self.columns)?;
+        for row in self {
+            write_row(writer, &row?)?;
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for RowIter {
+    type Item = Result<Row, SQLError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rows.next().map(|values| values.map(Row::new))
+    }
+}
+
+fn write_header<W: Write>(writer: &mut W, columns: &[Column]) -> Result<(), SQLError> {
+    let names: Vec<String> = columns.iter().map(|column| column.name.clone()).collect();
+    write_line(writer, &names)
+}
+
+fn write_row<W: Write>(writer: &mut W, row: &Row) -> Result<(), SQLError> {
+    let fields: Vec<String> = row.values.iter().map(|value| value.to_string()).collect();
+    write_line(writer, &fields)
+}
+
+fn write_line<W: Write>(writer: &mut W, fields: &[String]) -> Result<(), SQLError> {
+    writer
+        .write_all(format_record(fields).as_bytes())
+        .map_err(|_| SQLError::GenericError("Failed to write query results".to_string()))
+}
+
+impl IntoIterator for ResultSet {
+    type Item = Row;
+    type IntoIter = std::vec::IntoIter<Row>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.rows.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_csv() {
+        let column = |name: &str| Column {
+            name: name.to_string(),
+            data_type: None,
+        };
+        let results = ResultSet::new(
+            vec![column("id"), column("product"), column("id")],
+            vec![
+                Row::new(vec![
+                    Value::Integer(1),
+                    Value::Text("Mouse, USB".to_string()),
+                    Value::Null,
+                ]),
+                Row::new(vec![Value::Float(2.0), Value::Boolean(true), Value::Null]),
+            ],
+        );
+        let mut output = Vec::new();
+        results.write_csv(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "id,product,id\n1,\"Mouse, USB\",\n2.0,true,\n"
+        );
+        assert_eq!(results.column_index("product"), Some(1));
+        // An ambiguous name has no single position
+        assert_eq!(results.column_index("id"), None);
+        assert_eq!(results.rows()[1][1], Value::Boolean(true));
+    }
+}
@@ -0,0 +1,166 @@
+use crate::errors::SQLError;
+
+/// Number of single-character insertions, deletions, substitutions and
+/// adjacent transpositions needed to turn `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // distances[i][j] is the distance between the first i chars of a and the
+    // first j chars of b
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+/// The candidate `word` is most likely a misspelling of: the closest one, as
+/// long as it is about one edit per three characters away. An exact match is
+/// not a misspelling.
+pub fn closest_match<'a>(
+    word: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max_distance = word.chars().count() / 3;
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(word, candidate), candidate))
+        .filter(|&(distance, _)| distance > 0 && distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// A suggestion to append to an error message about `word`, or an empty string
+/// when no candidate is close.
+pub fn did_you_mean<'a>(word: &str, candidates: impl IntoIterator<Item = &'a str>) -> String {
+    match closest_match(word, candidates) {
+        Some(candidate) => format!(" (did you mean '{}'?)", candidate),
+        None => String::new(),
+    }
+}
+
+/// Whether `text` matches a LIKE `pattern`, in which `%` matches any run of
+/// characters, `_` matches any one character and `escape` makes the character
+/// after it match only itself.
+pub fn like_match(
+    text: &str,
+    pattern: &str,
+    escape: char,
+    ignore_case: bool,
+) -> Result<bool, SQLError> {
+    enum Part {
+        AnyRun,
+        AnyChar,
+        Char(char),
+    }
+    let mut parts = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(ch) = chars.next() {
+        parts.push(match ch {
+            _ if ch == escape => match chars.next() {
+                Some(escaped) => Part::Char(escaped),
+                None => {
+                    return Err(SQLError::InvalidSyntax(format!(
+                        "LIKE pattern '{}' ends with the escape character",
+                        pattern
+                    )))
+                }
+            },
+            '%' => Part::AnyRun,
+            '_' => Part::AnyChar,
+            _ => Part::Char(ch),
+        });
+    }
+    let same = |a: char, b: char| a == b || (ignore_case && a.to_lowercase().eq(b.to_lowercase()));
+
+    // Match greedily, and on a mismatch let the last `%` seen take one more
+    // character and retry from there
+    let text: Vec<char> = text.chars().collect();
+    let (mut t, mut p) = (0, 0);
+    let mut retry: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match parts.get(p) {
+            Some(Part::AnyRun) => {
+                retry = Some((p, t));
+                p += 1;
+            }
+            Some(Part::AnyChar) => (t, p) = (t + 1, p + 1),
+            Some(Part::Char(ch)) if same(*ch, text[t]) => (t, p) = (t + 1, p + 1),
+            _ => match retry {
+                Some((run, start)) => {
+                    retry = Some((run, start + 1));
+                    (t, p) = (start + 1, run + 1);
+                }
+                None => return Ok(false),
+            },
+        }
+    }
+    Ok(parts[p..].iter().all(|part| matches!(part, Part::AnyRun)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("WERE", "WHERE"), 1);
+        assert_eq!(edit_distance("FORM", "FROM"), 1);
+        assert_eq!(edit_distance("quantity", "quantity"), 0);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("añoz", "años"), 1);
+    }
+
+    #[test]
+    fn test_closest_match() {
+        let columns = ["id", "product", "quantity"];
+        assert_eq!(closest_match("prodcut", columns), Some("product"));
+        assert_eq!(closest_match("quantities", columns), Some("quantity"));
+        // Short words need an exact match
+        assert_eq!(closest_match("ix", columns), None);
+        assert_eq!(closest_match("id", columns), None);
+        assert_eq!(closest_match("price", columns), None);
+        assert_eq!(
+            did_you_mean("prodct", columns),
+            " (did you mean 'product'?)"
+        );
+        assert_eq!(did_you_mean("xyz", columns), "");
+    }
+
+    #[test]
+    fn test_like_match() {
+        let like = |text, pattern| like_match(text, pattern, '\\', false).unwrap();
+        assert!(like("ana@hotmail.com", "%@hotmail.com"));
+        assert!(!like("ana@gmail.com", "%@hotmail.com"));
+        assert!(like("Mouse", "M_use"));
+        assert!(!like("Mouse", "M_se"));
+        assert!(like("", "%"));
+        assert!(like("abcabd", "%ab_"));
+        assert!(like("año", "a_o"));
+        assert!(!like("Mouse", "mouse"));
+        assert!(like_match("Mouse", "mOUSE", '\\', true).unwrap());
+
+        // Escaped wildcards match only themselves
+        assert!(like("50%", "50\\%"));
+        assert!(!like("500", "50\\%"));
+        assert!(like_match("a_b", "a!_b", '!', false).unwrap());
+        assert!(!like_match("axb", "a!_b", '!', false).unwrap());
+        assert!(like_match("a", "a!", '!', false).is_err());
+    }
+}
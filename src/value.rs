@@ -0,0 +1,635 @@
+use crate::datetime::{self, Date, Interval, Time, Timestamp};
+use crate::errors::SQLError;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// A typed SQL value, either read from a CSV field or written as a literal in a query.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Float(f64),
+    Text(String),
+    Boolean(bool),
+    Date(Date),
+    Time(Time),
+    Timestamp(Timestamp),
+    Interval(Interval),
+}
+
+impl Value {
+    /// Infers the type of text such as a raw CSV field: integers, then floats,
+    /// then booleans, falling back to text.
+    pub fn from_field(field: &str) -> Value {
+        if let Some(number) = parse_number(field) {
+            return number;
+        }
+        match field.to_ascii_lowercase().as_str() {
+            "true" => Value::Boolean(true),
+            "false" => Value::Boolean(false),
+            _ => Value::Text(field.to_string()),
+        }
+    }
+
+    /// The number or boolean that text reads as, which is how the fields of a
+    /// column without a declared type are compared, sorted and grouped. Any
+    /// other value is itself.
+    pub fn inferred(&self) -> Cow<'_, Value> {
+        match self {
+            Value::Text(text) => match Value::from_field(text.trim()) {
+                Value::Text(_) => Cow::Borrowed(self),
+                value => Cow::Owned(value),
+            },
+            value => Cow::Borrowed(value),
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// The type of the value, or `None` for NULL.
+    pub fn data_type(&self) -> Option<DataType> {
+        match self {
+            Value::Null => None,
+            Value::Integer(_) => Some(DataType::Integer),
+            Value::Float(_) => Some(DataType::Float),
+            Value::Text(_) => Some(DataType::Text),
+            Value::Boolean(_) => Some(DataType::Boolean),
+            Value::Date(_) => Some(DataType::Date),
+            Value::Time(_) => Some(DataType::Time),
+            Value::Timestamp(_) => Some(DataType::Timestamp),
+            Value::Interval(_) => Some(DataType::Interval),
+        }
+    }
+
+    /// A key under which equal values hash together, for grouping and removing
+    /// duplicates. Integers and whole floats share a key since they compare equal,
+    /// as do dates and timestamps at their midnight, and text takes the key of
+    /// the number or boolean it reads as.
+    pub fn group_key(&self) -> String {
+        match self.inferred().as_ref() {
+            Value::Null => "null".to_string(),
+            Value::Integer(n) => format!("n{}", n),
+            Value::Float(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => {
+                format!("n{}", *n as i64)
+            }
+            Value::Float(n) => format!("f{}", n),
+            Value::Text(s) => format!("t{}", s),
+            Value::Boolean(b) => format!("b{}", b),
+            Value::Date(date) => format!("s{}", Timestamp::from(*date)),
+            Value::Timestamp(timestamp) => format!("s{}", timestamp),
+            Value::Time(time) => format!("c{}", time),
+            Value::Interval(interval) => format!("i{}", interval.total_micros()),
+        }
+    }
+
+    /// Compares two values for a WHERE predicate, coercing between types:
+    ///
+    /// * integers and floats compare numerically;
+    /// * dates compare with timestamps as their midnight, and intervals by their
+    ///   length with a month taken as 30 days;
+    /// * text compared against another type is converted to that type when it
+    ///   parses as one, otherwise both sides are compared as text;
+    /// * text compared against text is compared as numbers or booleans when both
+    ///   sides read as such, as the fields of columns without a declared type;
+    /// * anything compared with NULL has no ordering.
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Null, _) | (_, Value::Null) => None,
+            (Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
+            (Value::Integer(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
+            (Value::Float(a), Value::Integer(b)) => a.partial_cmp(&(*b as f64)),
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+            (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+            (Value::Text(a), Value::Text(b)) => match (self.inferred(), other.inferred()) {
+                (Cow::Owned(a), Cow::Owned(b)) if a.type_rank() == b.type_rank() => a.compare(&b),
+                _ => Some(a.cmp(b)),
+            },
+            (Value::Date(a), Value::Date(b)) => Some(a.cmp(b)),
+            (Value::Time(a), Value::Time(b)) => Some(a.cmp(b)),
+            (Value::Timestamp(a), Value::Timestamp(b)) => Some(a.cmp(b)),
+            (Value::Date(a), Value::Timestamp(b)) => Some(Timestamp::from(*a).cmp(b)),
+            (Value::Timestamp(a), Value::Date(b)) => Some(a.cmp(&Timestamp::from(*b))),
+            (Value::Interval(a), Value::Interval(b)) => {
+                Some(a.total_micros().cmp(&b.total_micros()))
+            }
+            (Value::Text(text), typed) => match coerce_text(text, typed) {
+                Some(coerced) => coerced.compare(typed),
+                None => Some(text.as_str().cmp(typed.to_string().as_str())),
+            },
+            (typed, Value::Text(text)) => match coerce_text(text, typed) {
+                Some(coerced) => typed.compare(&coerced),
+                None => Some(typed.to_string().as_str().cmp(text.as_str())),
+            },
+            (a, b) => Some(a.to_string().cmp(&b.to_string())),
+        }
+    }
+
+    /// Total ordering used for sorting result rows. Values are ranked by type
+    /// first: numbers, text, booleans, dates and timestamps, times, intervals,
+    /// and NULL after every other value. Values of the same rank follow
+    /// `compare`. Comparing across types first would not be transitive, as in
+    /// `9 < 10`, `10 < '1a'` and `'1a' < 9`. Text that reads as a number or
+    /// boolean is ranked and compared as one.
+    pub fn sort_cmp(&self, other: &Value) -> Ordering {
+        let (this, other) = (self.inferred(), other.inferred());
+        let (this, other) = (this.as_ref(), other.as_ref());
+        this.type_rank()
+            .cmp(&other.type_rank())
+            .then_with(|| match this.compare(other) {
+                Some(ordering) => ordering,
+                // NaN, which `total_cmp` puts after every number
+                None => this.as_f64().total_cmp(&other.as_f64()),
+            })
+    }
+
+    /// Applies an arithmetic operator (`+ - * / %`). Integers stay integers, with
+    /// division truncating towards zero, and any float operand makes the result a
+    /// float. Text that reads as a number is used as that number, and NULL
+    /// operands give NULL. Dates, times, timestamps and intervals follow the
+    /// rules of `datetime::arithmetic`.
+    pub fn arithmetic(&self, op: &str, other: &Value) -> Result<Value, SQLError> {
+        if let Some(result) = datetime::arithmetic(self, op, other) {
+            return result;
+        }
+        let (left, right) = match (self.to_number(op)?, other.to_number(op)?) {
+            (Some(left), Some(right)) => (left, right),
+            _ => return Ok(Value::Null),
+        };
+        if matches!(op, "/" | "%") && right.is_zero() {
+            return Err(SQLError::GenericError("Division by zero".to_string()));
+        }
+
+        match (left, right) {
+            (Value::Integer(a), Value::Integer(b)) => {
+                let result = match op {
+                    "+" => a.checked_add(b),
+                    "-" => a.checked_sub(b),
+                    "*" => a.checked_mul(b),
+                    "/" => a.checked_div(b),
+                    "%" => a.checked_rem(b),
+                    _ => return Err(unknown_operator(op)),
+                };
+                result.map(Value::Integer).ok_or_else(|| {
+                    SQLError::GenericError(format!("Integer overflow in {} {} {}", a, op, b))
+                })
+            }
+            (left, right) => {
+                let (a, b) = (left.as_f64(), right.as_f64());
+                let result = match op {
+                    "+" => a + b,
+                    "-" => a - b,
+                    "*" => a * b,
+                    "/" => a / b,
+                    "%" => a % b,
+                    _ => return Err(unknown_operator(op)),
+                };
+                if !result.is_finite() {
+                    return Err(SQLError::GenericError(format!(
+                        "Numeric overflow in {} {} {}",
+                        a, op, b
+                    )));
+                }
+                Ok(Value::Float(result))
+            }
+        }
+    }
+
+    /// Concatenates the text of two values, giving NULL if either is NULL.
+    pub fn concat(&self, other: &Value) -> Value {
+        match (self, other) {
+            (Value::Null, _) | (_, Value::Null) => Value::Null,
+            (a, b) => Value::Text(format!("{}{}", a, b)),
+        }
+    }
+
+    /// Converts the value to `data_type`. Floats are rounded to the nearest
+    /// integer, integers convert to booleans by comparing with zero, timestamps
+    /// convert to their date or time of day, and text is read the way a field of
+    /// that type would be. NULL stays NULL.
+    pub fn cast(&self, data_type: DataType) -> Result<Value, SQLError> {
+        let converted = match (self, data_type) {
+            (Value::Null, _) => Some(Value::Null),
+            (_, DataType::Text) => Some(Value::Text(self.to_string())),
+            (Value::Integer(n), DataType::Integer) => Some(Value::Integer(*n)),
+            (Value::Float(n), DataType::Integer) => {
+                let rounded = n.round();
+                (rounded.abs() < i64::MAX as f64).then_some(Value::Integer(rounded as i64))
+            }
+            (Value::Boolean(b), DataType::Integer) => Some(Value::Integer(*b as i64)),
+            (Value::Integer(n), DataType::Float) => Some(Value::Float(*n as f64)),
+            (Value::Float(n), DataType::Float) => Some(Value::Float(*n)),
+            (Value::Integer(n), DataType::Boolean) => Some(Value::Boolean(*n != 0)),
+            (Value::Boolean(b), DataType::Boolean) => Some(Value::Boolean(*b)),
+            (Value::Date(date), DataType::Date) => Some(Value::Date(*date)),
+            (Value::Date(date), DataType::Timestamp) => {
+                Some(Value::Timestamp(Timestamp::from(*date)))
+            }
+            (Value::Timestamp(timestamp), DataType::Timestamp) => {
+                Some(Value::Timestamp(*timestamp))
+            }
+            (Value::Timestamp(timestamp), DataType::Date) => Some(Value::Date(timestamp.date())),
+            (Value::Timestamp(timestamp), DataType::Time) => Some(Value::Time(timestamp.time())),
+            (Value::Time(time), DataType::Time) => Some(Value::Time(*time)),
+            (Value::Interval(interval), DataType::Interval) => Some(Value::Interval(*interval)),
+            (Value::Text(text), _) => data_type.parse_field(text.trim()),
+            _ => None,
+        };
+        converted.ok_or_else(|| {
+            SQLError::TypeMismatch(format!("Cannot convert '{}' to {}", self, data_type))
+        })
+    }
+
+    /// Negates a number, leaving NULL as NULL.
+    pub fn negate(&self) -> Result<Value, SQLError> {
+        match self.to_number("-")? {
+            Some(Value::Integer(n)) => n
+                .checked_neg()
+                .map(Value::Integer)
+                .ok_or_else(|| SQLError::GenericError(format!("Integer overflow in -{}", n))),
+            Some(Value::Float(n)) => Ok(Value::Float(-n)),
+            _ => Ok(Value::Null),
+        }
+    }
+
+    /// Reads the value as an operand of an arithmetic operator, or `None` for NULL.
+    fn to_number(&self, op: &str) -> Result<Option<Value>, SQLError> {
+        match self {
+            Value::Null => Ok(None),
+            Value::Integer(_) | Value::Float(_) => Ok(Some(self.clone())),
+            Value::Text(text) => match parse_number(text.trim()) {
+                Some(number) => Ok(Some(number)),
+                None => Err(non_numeric_operand(op, self)),
+            },
+            _ => Err(non_numeric_operand(op, self)),
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        match self {
+            Value::Integer(n) => *n == 0,
+            Value::Float(n) => *n == 0.0,
+            _ => false,
+        }
+    }
+
+    fn as_f64(&self) -> f64 {
+        match self {
+            Value::Integer(n) => *n as f64,
+            Value::Float(n) => *n,
+            _ => f64::NAN,
+        }
+    }
+
+    fn type_rank(&self) -> u8 {
+        match self {
+            Value::Integer(_) | Value::Float(_) => 0,
+            Value::Text(_) => 1,
+            Value::Boolean(_) => 2,
+            Value::Date(_) | Value::Timestamp(_) => 3,
+            Value::Time(_) => 4,
+            Value::Interval(_) => 5,
+            Value::Null => 6,
+        }
+    }
+}
+
+/// A column type declared in a table's schema sidecar file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataType {
+    Integer,
+    Float,
+    Text,
+    Boolean,
+    Date,
+    Time,
+    Timestamp,
+    Interval,
+}
+
+impl DataType {
+    pub fn from_name(name: &str) -> Option<DataType> {
+        match name.to_ascii_uppercase().as_str() {
+            "INTEGER" | "INT" | "BIGINT" => Some(DataType::Integer),
+            "FLOAT" | "REAL" | "DOUBLE" | "DECIMAL" | "NUMERIC" => Some(DataType::Float),
+            "TEXT" | "VARCHAR" | "CHAR" | "STRING" => Some(DataType::Text),
+            "BOOLEAN" | "BOOL" => Some(DataType::Boolean),
+            "DATE" => Some(DataType::Date),
+            "TIME" => Some(DataType::Time),
+            "TIMESTAMP" | "DATETIME" => Some(DataType::Timestamp),
+            "INTERVAL" => Some(DataType::Interval),
+            _ => None,
+        }
+    }
+
+    /// Reads a raw CSV field as a value of this type, or `None` if it does not fit.
+    pub fn parse_field(&self, field: &str) -> Option<Value> {
+        match self {
+            DataType::Integer => field.parse::<i64>().ok().map(Value::Integer),
+            DataType::Float => match parse_number(field)? {
+                Value::Integer(n) => Some(Value::Float(n as f64)),
+                number => Some(number),
+            },
+            DataType::Text => Some(Value::Text(field.to_string())),
+            DataType::Boolean => match field.to_ascii_lowercase().as_str() {
+                "true" => Some(Value::Boolean(true)),
+                "false" => Some(Value::Boolean(false)),
+                _ => None,
+            },
+            DataType::Date => Date::parse(field).map(Value::Date),
+            DataType::Time => Time::parse(field).map(Value::Time),
+            DataType::Timestamp => Timestamp::parse(field).map(Value::Timestamp),
+            DataType::Interval => Interval::parse(field).map(Value::Interval),
+        }
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataType::Integer => write!(f, "INTEGER"),
+            DataType::Float => write!(f, "FLOAT"),
+            DataType::Text => write!(f, "TEXT"),
+            DataType::Boolean => write!(f, "BOOLEAN"),
+            DataType::Date => write!(f, "DATE"),
+            DataType::Time => write!(f, "TIME"),
+            DataType::Timestamp => write!(f, "TIMESTAMP"),
+            DataType::Interval => write!(f, "INTERVAL"),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => Ok(()),
+            Value::Integer(n) => write!(f, "{}", n),
+            // Keep a decimal point on whole floats so they read back as floats
+            Value::Float(n) if n.is_finite() && n.fract() == 0.0 => write!(f, "{:.1}", n),
+            Value::Float(n) => write!(f, "{}", n),
+            Value::Text(s) => write!(f, "{}", s),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Date(date) => write!(f, "{}", date),
+            Value::Time(time) => write!(f, "{}", time),
+            Value::Timestamp(timestamp) => write!(f, "{}", timestamp),
+            Value::Interval(interval) => write!(f, "{}", interval),
+        }
+    }
+}
+
+/// Parses a numeric string into an `Integer` or `Float`, rejecting the textual
+/// forms such as `inf` or `NaN` that `f64::from_str` would otherwise accept.
+pub fn parse_number(text: &str) -> Option<Value> {
+    if let Ok(n) = text.parse::<i64>() {
+        return Some(Value::Integer(n));
+    }
+    let looks_numeric = text.chars().any(|c| c.is_ascii_digit())
+        && text
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E'));
+    if looks_numeric {
+        text.parse::<f64>().ok().map(Value::Float)
+    } else {
+        None
+    }
+}
+
+fn unknown_operator(op: &str) -> SQLError {
+    SQLError::InvalidSyntax(format!("Unknown operator '{}'", op))
+}
+
+fn non_numeric_operand(op: &str, value: &Value) -> SQLError {
+    SQLError::TypeMismatch(format!(
+        "Operator '{}' expects numeric values, found '{}'",
+        op, value
+    ))
+}
+
+/// Converts text to the type of `target`, if the text can be read as that type.
+fn coerce_text(text: &str, target: &Value) -> Option<Value> {
+    match target {
+        Value::Integer(_) | Value::Float(_) => parse_number(text.trim()),
+        Value::Boolean(_) => match text.trim().to_ascii_lowercase().as_str() {
+            "true" => Some(Value::Boolean(true)),
+            "false" => Some(Value::Boolean(false)),
+            _ => None,
+        },
+        // A date compared with text holding a date and time is compared as a
+        // timestamp
+        Value::Date(_) => DataType::Date
+            .parse_field(text.trim())
+            .or_else(|| DataType::Timestamp.parse_field(text.trim())),
+        Value::Time(_) | Value::Timestamp(_) | Value::Interval(_) => {
+            target.data_type()?.parse_field(text.trim())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_field_infers_types() {
+        assert_eq!(Value::from_field("42"), Value::Integer(42));
+        assert_eq!(Value::from_field("-2.5"), Value::Float(-2.5));
+        assert_eq!(Value::from_field("TRUE"), Value::Boolean(true));
+        assert_eq!(Value::from_field("inf"), Value::Text("inf".to_string()));
+        assert_eq!(Value::from_field(""), Value::Text(String::new()));
+    }
+
+    #[test]
+    fn test_numbers_compare_numerically() {
+        assert_eq!(
+            Value::Integer(9).compare(&Value::Integer(10)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            Value::Integer(2).compare(&Value::Float(2.0)),
+            Some(Ordering::Equal)
+        );
+        // A quoted literal compared against a number is read as a number
+        assert_eq!(
+            Value::Integer(9).compare(&Value::Text("10".to_string())),
+            Some(Ordering::Less)
+        );
+        // As do two pieces of text that read as numbers, such as untyped fields
+        assert_eq!(
+            Value::Text("9".to_string()).compare(&Value::Text("10".to_string())),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            Value::Text("2".to_string()).compare(&Value::Text("2.0".to_string())),
+            Some(Ordering::Equal)
+        );
+        // Otherwise they compare as text
+        assert_eq!(
+            Value::Text("9".to_string()).compare(&Value::Text("10a".to_string())),
+            Some(Ordering::Greater)
+        );
+    }
+
+    #[test]
+    fn test_data_type_parse_field() {
+        assert_eq!(
+            DataType::Integer.parse_field("12"),
+            Some(Value::Integer(12))
+        );
+        assert_eq!(DataType::Integer.parse_field("1.5"), None);
+        assert_eq!(DataType::Float.parse_field("2"), Some(Value::Float(2.0)));
+        assert_eq!(
+            DataType::Text.parse_field("42"),
+            Some(Value::Text("42".to_string()))
+        );
+        assert_eq!(DataType::Boolean.parse_field("yes"), None);
+        assert_eq!(DataType::Integer.parse_field(""), None);
+    }
+
+    #[test]
+    fn test_null_has_no_ordering() {
+        assert_eq!(Value::Null.compare(&Value::Integer(1)), None);
+        assert_eq!(Value::Null.sort_cmp(&Value::Integer(1)), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_sort_mixed_values() {
+        let text = |s: &str| Value::Text(s.to_string());
+        let date = DataType::Date.parse_field("2024-01-01").unwrap();
+        let sorted = vec![
+            Value::Float(f64::NEG_INFINITY),
+            Value::Integer(9),
+            Value::Float(9.5),
+            text("09.7"),
+            Value::Integer(10),
+            Value::Float(f64::NAN),
+            text("1a"),
+            text("b"),
+            Value::Boolean(false),
+            text("TRUE"),
+            date,
+            Value::Null,
+        ];
+        // Every starting order sorts the same way
+        for rotation in 0..sorted.len() {
+            let mut values = sorted.clone();
+            values.rotate_left(rotation);
+            values.reverse();
+            values.sort_by(Value::sort_cmp);
+            let printed: Vec<String> = values.iter().map(Value::to_string).collect();
+            let expected: Vec<String> = sorted.iter().map(Value::to_string).collect();
+            assert_eq!(printed, expected);
+        }
+        assert_eq!(Value::Integer(10).sort_cmp(&text("1a")), Ordering::Less);
+        assert_eq!(text("1a").sort_cmp(&Value::Integer(9)), Ordering::Greater);
+        // Text groups with the number it reads as
+        assert_eq!(text(" 2.0").group_key(), Value::Integer(2).group_key());
+        assert_ne!(text("2a").group_key(), Value::Integer(2).group_key());
+    }
+
+    #[test]
+    fn test_arithmetic_types() {
+        let int = Value::Integer;
+        assert_eq!(int(7).arithmetic("+", &int(2)).unwrap(), int(9));
+        assert_eq!(int(7).arithmetic("/", &int(2)).unwrap(), int(3));
+        assert_eq!(int(-7).arithmetic("/", &int(2)).unwrap(), int(-3));
+        assert_eq!(int(-7).arithmetic("%", &int(3)).unwrap(), int(-1));
+        assert_eq!(
+            int(7).arithmetic("/", &Value::Float(2.0)).unwrap(),
+            Value::Float(3.5)
+        );
+        assert_eq!(
+            Value::Text("4".to_string())
+                .arithmetic("*", &int(2))
+                .unwrap(),
+            int(8)
+        );
+        assert_eq!(Value::Null.arithmetic("-", &int(2)).unwrap(), Value::Null);
+        assert_eq!(int(5).negate().unwrap(), int(-5));
+    }
+
+    #[test]
+    fn test_arithmetic_errors() {
+        let int = Value::Integer;
+        assert!(matches!(
+            int(1).arithmetic("/", &int(0)),
+            Err(SQLError::GenericError(_))
+        ));
+        assert!(matches!(
+            Value::Float(1.0).arithmetic("%", &Value::Float(0.0)),
+            Err(SQLError::GenericError(_))
+        ));
+        assert!(int(i64::MAX).arithmetic("+", &int(1)).is_err());
+        assert!(int(i64::MIN).negate().is_err());
+        assert!(matches!(
+            Value::Text("abc".to_string()).arithmetic("+", &int(1)),
+            Err(SQLError::TypeMismatch(_))
+        ));
+        // A NULL operand still makes division by zero NULL
+        assert_eq!(Value::Null.arithmetic("/", &int(0)).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn test_cast_and_concat() {
+        let text = |s: &str| Value::Text(s.to_string());
+        assert_eq!(
+            text(" 42 ").cast(DataType::Integer).unwrap(),
+            Value::Integer(42)
+        );
+        assert_eq!(
+            Value::Float(2.5).cast(DataType::Integer).unwrap(),
+            Value::Integer(3)
+        );
+        assert_eq!(
+            Value::Integer(0).cast(DataType::Boolean).unwrap(),
+            Value::Boolean(false)
+        );
+        assert_eq!(Value::Float(1.0).cast(DataType::Text).unwrap(), text("1.0"));
+        assert_eq!(Value::Null.cast(DataType::Integer).unwrap(), Value::Null);
+        assert!(matches!(
+            text("1.5").cast(DataType::Integer),
+            Err(SQLError::TypeMismatch(_))
+        ));
+        assert!(Value::Boolean(true).cast(DataType::Float).is_err());
+
+        assert_eq!(text("a").concat(&Value::Integer(1)), text("a1"));
+        assert_eq!(text("a").concat(&Value::Null), Value::Null);
+    }
+
+    #[test]
+    fn test_temporal_values() {
+        let date = DataType::Date.parse_field("2024-03-01").unwrap();
+        let timestamp = DataType::Timestamp
+            .parse_field("2024-03-01 09:30:00")
+            .unwrap();
+        assert_eq!(date.compare(&timestamp), Some(Ordering::Less));
+        assert_eq!(
+            date.compare(&Value::Text("2024-03-01".to_string())),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            date.compare(&Value::Text("2024-02-29 23:00".to_string())),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            timestamp.cast(DataType::Date).unwrap().group_key(),
+            date.cast(DataType::Timestamp).unwrap().group_key()
+        );
+        assert_eq!(
+            timestamp.cast(DataType::Time).unwrap().to_string(),
+            "09:30:00"
+        );
+        assert_eq!(
+            Value::Text("2024-02-30".to_string())
+                .cast(DataType::Date)
+                .ok(),
+            None
+        );
+        let month = DataType::Interval.parse_field("1 mon").unwrap();
+        let days = DataType::Interval.parse_field("30 days").unwrap();
+        assert_eq!(month.compare(&days), Some(Ordering::Equal));
+        assert!(date.negate().is_err());
+    }
+}